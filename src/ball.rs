@@ -0,0 +1,1392 @@
+//! The ball: its flight, spin and size, and the collisions that bounce it around the arena.
+
+use crate::{
+    Phase,
+    interpolation::Interpolated,
+    paddle::{Held, Paddle, PaddleController, PaddleIntent},
+    pickups::{BallSizeModifier, Shield, SpeedBurst},
+    practice::{Backboard, BallMachine},
+    score::{Celebrating, Volley},
+    serve::{GameRng, SERVE_CONE_DEGREES, ServeConfig, ServeDirection},
+    wall::{Bumper, DampZone, TopologyMode, WALL_OFFSET, WALL_WIDTH},
+};
+use bevy::{
+    math::{
+        StableInterpolate,
+        bounding::{Aabb2d, BoundingCircle, IntersectsVolume},
+    },
+    prelude::*,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU},
+    str::FromStr,
+};
+
+pub(crate) const BALL_SIZE: f32 = 20.;
+pub(crate) const BALL_SPEED: f32 = 200.;
+const BALL_ESCAPE_MARGIN: f32 = 100.;
+const BALL_SIZE_TWEEN_RATE: f32 = 10.;
+const SQUASH_DURATION: f32 = 0.1;
+const SQUASH_AMOUNT: f32 = 0.3;
+/// How quickly the ball eases to a damp zone's speed on entry and back on exit.
+const DAMP_SMOOTHING: f32 = 12.;
+
+const DEFAULT_SKIN_WIDTH: f32 = 1.;
+pub(crate) const MAX_SKIN_WIDTH: f32 = BALL_SIZE / 2.;
+
+pub(crate) const DEFAULT_RALLY_SPEEDUP_CAP: f32 = 0.5;
+
+/// The ball's flight, what it bounces off and what keeps it in play.
+pub struct BallPlugin;
+
+impl Plugin for BallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BallCollision>()
+            .init_resource::<Rally>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    (assist_ball, curve_spinning_ball, damp_ball).in_set(Phase::Steer),
+                    apply_velocity.in_set(Phase::Move),
+                    resize_ball.in_set(Phase::Resize),
+                    detect_collisions.in_set(Phase::Collide),
+                    impart_spin.in_set(Phase::Respond),
+                    reset_escaped_ball.in_set(Phase::Escape),
+                    break_stalls.in_set(Phase::Stall),
+                ),
+            )
+            .add_systems(
+                Update,
+                apply_physics_profile.run_if(resource_changed::<PhysicsProfile>),
+            );
+    }
+}
+
+#[derive(Component, Clone, Deref, DerefMut)]
+pub(crate) struct Velocity(pub(crate) Vec2);
+
+#[derive(Component, Clone)]
+pub(crate) struct Collider;
+
+#[derive(Component, Clone)]
+#[require(Spin, BallSize, Squash, Volley, Damping)]
+pub(crate) struct Ball;
+
+/// A ball beyond the first, from a multiball pickup. It leaves play at its first goal. The AI,
+/// online play and the lockstep hash follow every ball; whatever follows a single one, like the
+/// camera and replays, follows the first.
+#[derive(Component, Clone)]
+pub(crate) struct ExtraBall;
+
+/// Diameter the ball collides with. The `Transform` scale only follows it, and briefly deforms
+/// while the ball squashes against whatever it hit.
+#[derive(Component, Clone, Deref, DerefMut)]
+pub(crate) struct BallSize(pub(crate) f32);
+
+impl Default for BallSize {
+    fn default() -> Self {
+        Self(BALL_SIZE)
+    }
+}
+
+/// Squash-and-stretch of the ball after a bounce, along the `normal` of the surface it hit.
+#[derive(Component, Clone)]
+pub(crate) struct Squash {
+    pub(crate) normal: Vec2,
+    pub(crate) timer: Timer,
+}
+
+impl Default for Squash {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(SQUASH_DURATION, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self {
+            normal: Vec2::X,
+            timer,
+        }
+    }
+}
+
+/// Counter-clockwise turn rate of the ball's flight, in radians per second.
+#[derive(Component, Clone, Default, Deref, DerefMut)]
+struct Spin(f32);
+
+/// Advanced physics where a moving paddle drags the ball along and puts spin on it, curving its
+/// flight until the spin wears off. Curving only turns the velocity, so the ball keeps its speed.
+#[derive(Resource)]
+pub(crate) struct SpinConfig {
+    pub(crate) enabled: bool,
+    /// Vertical speed added by a hit from a paddle moving at full speed, in its direction.
+    pub(crate) kick: f32,
+    /// Spin from a hit by a paddle moving at full speed. Zero keeps the flight straight.
+    pub(crate) per_paddle_speed: f32,
+    /// Fraction of the spin lost per second.
+    pub(crate) decay: f32,
+}
+
+impl Default for SpinConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kick: BALL_SPEED * 0.25,
+            per_paddle_speed: 1.2,
+            decay: 0.8,
+        }
+    }
+}
+
+/// How much of its speed the ball currently keeps, eased toward the factor of the damp zone it is
+/// in, or 1 outside of them.
+#[derive(Component, Clone, Deref)]
+struct Damping(f32);
+
+impl Default for Damping {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Fraction of the ball's speed kept on every wall bounce, for a heavy ball that needs hitting to
+/// stay fast. Paddle hits and the ball machine always return the ball at their own speed, so a
+/// rally restarts from full speed with each hit, and speed bursts scale whatever is left.
+#[derive(Resource, Deref)]
+pub(crate) struct Restitution(pub(crate) f32);
+
+impl Default for Restitution {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Named feel for the ball, picked with `--physics` or from the settings menu, bundling the flags
+/// that shape it.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum PhysicsProfile {
+    Classic,
+    /// Slow serves and a ball that loses speed on every wall bounce.
+    Heavy,
+    /// Slow serves at steep angles that keep their speed.
+    Floaty,
+    /// Fast, flat serves and returns aimed by where the ball meets the paddle.
+    Arcade,
+    /// Whatever the individual flags say, kept in [`CustomPhysics`].
+    #[default]
+    Custom,
+}
+
+impl PhysicsProfile {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            PhysicsProfile::Custom => PhysicsProfile::Classic,
+            PhysicsProfile::Classic => PhysicsProfile::Heavy,
+            PhysicsProfile::Heavy => PhysicsProfile::Floaty,
+            PhysicsProfile::Floaty => PhysicsProfile::Arcade,
+            PhysicsProfile::Arcade => PhysicsProfile::Custom,
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            PhysicsProfile::Classic => "Classic",
+            PhysicsProfile::Heavy => "Heavy",
+            PhysicsProfile::Floaty => "Floaty",
+            PhysicsProfile::Arcade => "Arcade",
+            PhysicsProfile::Custom => "Custom",
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            PhysicsProfile::Classic => "classic",
+            PhysicsProfile::Heavy => "heavy",
+            PhysicsProfile::Floaty => "floaty",
+            PhysicsProfile::Arcade => "arcade",
+            PhysicsProfile::Custom => "custom",
+        }
+    }
+
+    /// `None` for [`PhysicsProfile::Custom`].
+    pub(crate) fn physics(self) -> Option<BallPhysics> {
+        let classic = BallPhysics {
+            serve_speed: BALL_SPEED,
+            serve_cone: SERVE_CONE_DEGREES.to_radians(),
+            restitution: Restitution::default().0,
+            speed_floor: SpeedFloor::default().0,
+            aimed_bounces: false,
+        };
+        match self {
+            PhysicsProfile::Classic => Some(classic),
+            PhysicsProfile::Heavy => Some(BallPhysics {
+                serve_speed: BALL_SPEED * 0.75,
+                restitution: 0.85,
+                speed_floor: BALL_SPEED * 0.4,
+                ..classic
+            }),
+            PhysicsProfile::Floaty => Some(BallPhysics {
+                serve_speed: BALL_SPEED * 0.6,
+                serve_cone: 50_f32.to_radians(),
+                speed_floor: BALL_SPEED * 0.3,
+                ..classic
+            }),
+            PhysicsProfile::Arcade => Some(BallPhysics {
+                serve_cone: 15_f32.to_radians(),
+                speed_floor: BALL_SPEED,
+                aimed_bounces: true,
+                ..classic
+            }),
+            PhysicsProfile::Custom => None,
+        }
+    }
+}
+
+impl FromStr for PhysicsProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(PhysicsProfile::Classic),
+            "heavy" => Ok(PhysicsProfile::Heavy),
+            "floaty" => Ok(PhysicsProfile::Floaty),
+            "arcade" => Ok(PhysicsProfile::Arcade),
+            "custom" => Ok(PhysicsProfile::Custom),
+            _ => Err(format!(
+                "unknown physics profile '{s}', expected classic, heavy, floaty, arcade or custom"
+            )),
+        }
+    }
+}
+
+/// The values a [`PhysicsProfile`] sets in place of their flags.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct BallPhysics {
+    pub(crate) serve_speed: f32,
+    /// Half-angle in radians, like [`ServeConfig::cone`].
+    pub(crate) serve_cone: f32,
+    pub(crate) restitution: f32,
+    pub(crate) speed_floor: f32,
+    pub(crate) aimed_bounces: bool,
+}
+
+impl BallPhysics {
+    pub(crate) fn apply(
+        self,
+        serve: &mut ServeConfig,
+        restitution: &mut Restitution,
+        speed_floor: &mut SpeedFloor,
+        aimed: &mut AimedBounces,
+    ) {
+        serve.speed = self.serve_speed;
+        serve.cone = self.serve_cone;
+        restitution.0 = self.restitution;
+        speed_floor.0 = self.speed_floor;
+        aimed.enabled = self.aimed_bounces;
+    }
+}
+
+/// What the individual flags set, for [`PhysicsProfile::Custom`] to go back to.
+#[derive(Resource, Deref)]
+pub(crate) struct CustomPhysics(pub(crate) BallPhysics);
+
+/// Skill over luck: paddles return the ball at an angle set by where it hit them, up to 45 degrees
+/// at the ends, instead of a random one. On unless `--random-bounces` is given.
+#[derive(Resource)]
+pub(crate) struct AimedBounces {
+    pub(crate) enabled: bool,
+}
+
+/// Speed of the ball off a paddle, before any [`RallySpeedup`].
+#[derive(Resource, Deref)]
+pub(crate) struct BallSpeed(pub(crate) f32);
+
+impl Default for BallSpeed {
+    fn default() -> Self {
+        Self(BALL_SPEED)
+    }
+}
+
+/// Escalating rallies: every paddle hit returns the ball `per_hit` faster than the last, as a
+/// fraction of the [`BallSpeed`], up to `cap` extra. Zero `per_hit` turns it off.
+#[derive(Resource)]
+pub(crate) struct RallySpeedup {
+    pub(crate) per_hit: f32,
+    pub(crate) cap: f32,
+}
+
+impl RallySpeedup {
+    fn multiplier(&self, hits: u32) -> f32 {
+        1. + (self.per_hit * hits as f32).min(self.cap)
+    }
+}
+
+impl Default for RallySpeedup {
+    fn default() -> Self {
+        Self {
+            per_hit: 0.,
+            cap: DEFAULT_RALLY_SPEEDUP_CAP,
+        }
+    }
+}
+
+/// Paddle hits since the last serve from the center.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct Rally(pub(crate) u32);
+
+/// Slowest the ball can leave a bounce, so damping like restitution can't grind rallies to a crawl.
+/// Zero turns it off.
+#[derive(Resource)]
+pub(crate) struct SpeedFloor(pub(crate) f32);
+
+impl SpeedFloor {
+    /// Speeds `velocity` up to the floor, leaving a ball at rest alone.
+    pub(crate) fn apply(&self, velocity: Vec2) -> Vec2 {
+        if velocity == Vec2::ZERO {
+            velocity
+        } else {
+            velocity.clamp_length_min(self.0)
+        }
+    }
+}
+
+impl Default for SpeedFloor {
+    fn default() -> Self {
+        Self(BALL_SPEED * 0.5)
+    }
+}
+
+/// Margin in pixels the ball's collision circle is grown by, so a fast ball bounces a step early
+/// instead of a step late, visibly sunk into the paddle. Too wide and slow balls bounce off thin
+/// air just short of what they hit.
+#[derive(Resource, Deref)]
+pub(crate) struct SkinWidth(pub(crate) f32);
+
+impl Default for SkinWidth {
+    fn default() -> Self {
+        Self(DEFAULT_SKIN_WIDTH)
+    }
+}
+
+/// Breaks rallies where the ball keeps travelling almost exactly horizontally, which two
+/// well-placed paddles could otherwise keep up forever.
+#[derive(Resource, Clone)]
+pub(crate) struct StallWatchdog {
+    /// Vertical speed below which the ball counts as stalled.
+    pub(crate) threshold: f32,
+    /// How long the ball may stay stalled before it is nudged.
+    pub(crate) timer: Timer,
+    /// Largest vertical speed added by a nudge.
+    pub(crate) nudge: f32,
+}
+
+impl Default for StallWatchdog {
+    fn default() -> Self {
+        Self {
+            threshold: BALL_SPEED * 0.05,
+            timer: Timer::from_seconds(5., TimerMode::Once),
+            nudge: BALL_SPEED * 0.3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum CollisionKind {
+    Paddle,
+    Wall,
+    Goal,
+}
+
+#[derive(Event)]
+pub(crate) struct BallCollision {
+    pub(crate) ball: Entity,
+    pub(crate) position: Vec2,
+    /// Surface normal at `position`, pointing toward the ball. Zero if the ball's center had
+    /// already sunk into the surface.
+    pub(crate) normal: Vec2,
+    pub(crate) kind: CollisionKind,
+}
+
+impl CollisionKind {
+    pub(crate) fn color(self) -> Color {
+        match self {
+            CollisionKind::Paddle => Color::WHITE,
+            CollisionKind::Wall => Color::srgb(0.6, 0.7, 1.),
+            CollisionKind::Goal => Color::srgb(1., 0.3, 0.3),
+        }
+    }
+}
+
+/// Accessibility assist that gently turns the ball toward a human player's paddle as it
+/// approaches, so a near miss becomes a hit. It only ever rotates the velocity, so the ball keeps
+/// its speed.
+#[derive(Resource)]
+pub(crate) struct Assist {
+    /// From 0 (off) to 1.
+    pub(crate) strength: f32,
+    /// Horizontal distance from the paddle within which the ball is steered.
+    pub(crate) range: f32,
+    /// Radians per second the ball turns at full strength.
+    pub(crate) max_turn_rate: f32,
+}
+
+impl Default for Assist {
+    fn default() -> Self {
+        Self {
+            strength: 0.,
+            range: 150.,
+            max_turn_rate: 1.5,
+        }
+    }
+}
+
+fn apply_velocity(
+    query: Query<
+        (
+            &mut Transform,
+            &Velocity,
+            Option<&SpeedBurst>,
+            Option<&Damping>,
+        ),
+        (Without<Held>, Without<Celebrating>),
+    >,
+    time: Res<Time>,
+) {
+    for (mut transform, velocity, burst, damping) in query {
+        let scale = burst.map_or(1., SpeedBurst::scale) * damping.map_or(1., |damping| **damping);
+        transform.translation.x += velocity.x * scale * time.delta_secs();
+        transform.translation.y += velocity.y * scale * time.delta_secs();
+    }
+}
+
+fn damp_ball(
+    balls: Query<(&Transform, &mut Damping), With<Ball>>,
+    zones: Query<(&Transform, &DampZone), Without<Ball>>,
+    time: Res<Time>,
+) {
+    for (transform, mut damping) in balls {
+        let position = transform.translation.xy();
+        let target = zones
+            .iter()
+            .filter(|(zone, _)| {
+                let offset = position - zone.translation.xy();
+                offset.abs().cmple(zone.scale.xy() / 2.).all()
+            })
+            .map(|(_, zone)| zone.factor)
+            .fold(1., f32::min);
+        damping
+            .0
+            .smooth_nudge(&target, DAMP_SMOOTHING, time.delta_secs());
+    }
+}
+
+fn assist_ball(
+    balls: Query<(&Transform, &mut Velocity), (With<Ball>, Without<Held>)>,
+    paddles: Query<(&Transform, &Paddle), Without<Ball>>,
+    assist: Res<Assist>,
+    time: Res<Time>,
+) {
+    if assist.strength <= 0. {
+        return;
+    }
+    let max_turn = assist.strength * assist.max_turn_rate * time.delta_secs();
+    for (transform, mut velocity) in balls {
+        for (paddle_transform, paddle) in &paddles {
+            if paddle.controller == PaddleController::Ai {
+                continue;
+            }
+            let to_paddle = paddle_transform.translation.xy() - transform.translation.xy();
+            if to_paddle.x * velocity.x <= 0. || to_paddle.x.abs() > assist.range {
+                continue;
+            }
+            let turn = velocity.angle_to(to_paddle).clamp(-max_turn, max_turn);
+            **velocity = Vec2::from_angle(turn).rotate(**velocity);
+        }
+    }
+}
+
+fn impart_spin(
+    mut collisions: EventReader<BallCollision>,
+    mut balls: Query<(&mut Velocity, &mut Spin), With<Ball>>,
+    paddles: Query<(&Transform, &PaddleIntent), With<Paddle>>,
+    config: Res<SpinConfig>,
+) {
+    for collision in collisions.read() {
+        let Ok((mut velocity, mut spin)) = balls.get_mut(collision.ball) else {
+            continue;
+        };
+        match collision.kind {
+            CollisionKind::Paddle if config.enabled => {
+                let Some((_, intent)) = paddles.iter().min_by(|(a, _), (b, _)| {
+                    let distance = |t: &Transform| t.translation.xy().distance(collision.position);
+                    distance(a).total_cmp(&distance(b))
+                }) else {
+                    continue;
+                };
+                let intent = intent.clamp(-1., 1.);
+                velocity.y += intent * config.kick;
+                // Curve toward the way the paddle was moving, whichever way the ball now travels.
+                **spin = intent * config.per_paddle_speed * velocity.x.signum();
+            }
+            CollisionKind::Goal => **spin = 0.,
+            _ => {}
+        }
+    }
+}
+
+fn curve_spinning_ball(
+    balls: Query<(&mut Velocity, &mut Spin), (With<Ball>, Without<Held>)>,
+    config: Res<SpinConfig>,
+    time: Res<Time>,
+) {
+    if !config.enabled {
+        return;
+    }
+    for (mut velocity, mut spin) in balls {
+        **velocity = Vec2::from_angle(**spin * time.delta_secs()).rotate(**velocity);
+        **spin *= (1. - config.decay).powf(time.delta_secs());
+    }
+}
+
+pub(crate) fn resize_ball(
+    mut commands: Commands,
+    balls: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut BallSize,
+            Option<&mut BallSizeModifier>,
+        ),
+        With<Ball>,
+    >,
+    topology: Res<TopologyMode>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut size, modifier) in balls {
+        let mut scale = 1.;
+        if let Some(mut modifier) = modifier {
+            if modifier.timer.tick(time.delta()).finished() {
+                commands.entity(entity).remove::<BallSizeModifier>();
+            } else {
+                scale = modifier.scale;
+            }
+        }
+
+        let max_size = 2. * WALL_OFFSET - WALL_WIDTH;
+        size.smooth_nudge(
+            &(BALL_SIZE * scale).min(max_size),
+            BALL_SIZE_TWEEN_RATE,
+            time.delta_secs(),
+        );
+
+        if *topology == TopologyMode::Bounce {
+            let limit = WALL_OFFSET - WALL_WIDTH / 2. - **size / 2.;
+            transform.translation.y = transform.translation.y.clamp(-limit, limit);
+        }
+    }
+}
+
+/// Mirrors `velocity` across a surface facing `normal`, unless it is already moving away from it.
+fn reflect_off(velocity: Vec2, normal: Vec2) -> Vec2 {
+    let approach = velocity.dot(normal);
+    if approach >= 0. {
+        velocity
+    } else {
+        velocity - 2. * approach * normal
+    }
+}
+
+fn bumper_contact(bumper: &Bumper, center: Vec2, point: Vec2) -> Vec2 {
+    let tangent = bumper.normal.perp();
+    let along = (point - center)
+        .dot(tangent)
+        .clamp(-bumper.half_length, bumper.half_length);
+    center + tangent * along
+}
+
+pub(crate) fn detect_collisions(
+    mut collisions: EventWriter<BallCollision>,
+    mut rng: ResMut<GameRng>,
+    topology: Res<TopologyMode>,
+    balls: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Interpolated,
+            &BallSize,
+        ),
+        (With<Ball>, Without<Collider>),
+    >,
+    mut colliders: Query<
+        (
+            &Transform,
+            Option<&Paddle>,
+            Option<&mut Shield>,
+            Option<&BallMachine>,
+            Has<Backboard>,
+        ),
+        With<Collider>,
+    >,
+    bumpers: Query<(&Transform, &Bumper), Without<Ball>>,
+    restitution: Res<Restitution>,
+    skin: Res<SkinWidth>,
+    speed_floor: Res<SpeedFloor>,
+    aimed: Res<AimedBounces>,
+    ball_speed: Res<BallSpeed>,
+    speedup: Res<RallySpeedup>,
+    mut rally: ResMut<Rally>,
+) {
+    'balls: for (ball, mut ball_transform, mut ball_velocity, mut ball_interpolated, ball_size) in
+        balls
+    {
+        let ball_radius = **ball_size / 2. + **skin;
+
+        if *topology == TopologyMode::Wrap && ball_transform.translation.y.abs() > WALL_OFFSET {
+            ball_transform.translation.y -=
+                2. * WALL_OFFSET * ball_transform.translation.y.signum();
+            ball_interpolated.snap(ball_transform.translation);
+        }
+
+        // Sweep from where the ball started the step so a fast ball can't skip over a thin
+        // collider, and bounce off whichever one it reached first.
+        let start = ball_interpolated.previous.xy();
+        let end = ball_transform.translation.xy();
+        let reach = ball_radius + WALL_WIDTH / 2.;
+        for (transform, bumper) in &bumpers {
+            let center = transform.translation.xy();
+            if ball_velocity.dot(bumper.normal) >= 0. {
+                continue;
+            }
+            // Where the ball crossed the bumper's face during the step, if within its length. Its
+            // rounded ends are left to the overlap test below.
+            let from = (start - center).dot(bumper.normal);
+            let to = (end - center).dot(bumper.normal);
+            let crossing = (from >= reach && to < reach)
+                .then(|| start.lerp(end, (from - reach) / (from - to)))
+                .filter(|point| {
+                    (*point - center).dot(bumper.normal.perp()).abs() <= bumper.half_length
+                });
+            if let Some(point) = crossing {
+                **ball_velocity =
+                    speed_floor.apply(reflect_off(**ball_velocity, bumper.normal) * **restitution);
+                let z = ball_transform.translation.z;
+                ball_transform.translation = point.extend(z);
+                collisions.write(BallCollision {
+                    ball,
+                    position: bumper_contact(bumper, center, point),
+                    normal: bumper.normal,
+                    kind: CollisionKind::Wall,
+                });
+                continue 'balls;
+            }
+
+            let position = bumper_contact(bumper, center, end);
+            let overlap = reach - end.distance(position);
+            if overlap > 0. {
+                **ball_velocity =
+                    speed_floor.apply(reflect_off(**ball_velocity, bumper.normal) * **restitution);
+                ball_transform.translation += (bumper.normal * overlap).extend(0.);
+                collisions.write(BallCollision {
+                    ball,
+                    position,
+                    normal: bumper.normal,
+                    kind: CollisionKind::Wall,
+                });
+                continue 'balls;
+            }
+        }
+
+        let bounding_circle = BoundingCircle::new(end, ball_radius);
+        let first_hit = colliders
+            .iter_mut()
+            .filter(|(transform, _, maybe_shield, _, _)| {
+                maybe_shield.as_ref().is_none_or(|shield| {
+                    let away_from_goal = -transform.translation.x.signum();
+                    shield.uses > 0 && ball_velocity.x * away_from_goal <= 0.
+                })
+            })
+            .filter_map(|collider| {
+                let (transform, ..) = &collider;
+                let center = transform.translation.xy();
+                let half_size = transform.scale.xy() / 2.;
+                segment_entry(start, end, &Aabb2d::new(center, half_size + ball_radius))
+                    .or_else(|| {
+                        bounding_circle
+                            .intersects(&Aabb2d::new(center, half_size))
+                            .then_some(1.)
+                    })
+                    .map(|entry| (entry, collider))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let Some((entry, (transform, maybe_paddle, maybe_shield, maybe_machine, is_backboard))) =
+            first_hit
+        else {
+            continue 'balls;
+        };
+        if entry < 1. {
+            let z = ball_transform.translation.z;
+            ball_transform.translation = start.lerp(end, entry).extend(z);
+        }
+        let bounding_circle = BoundingCircle::new(ball_transform.translation.xy(), ball_radius);
+        let bounding_box = Aabb2d::new(transform.translation.xy(), transform.scale.xy() / 2.);
+        enum Side {
+            Left,
+            Right,
+        }
+        let side = if ball_transform.translation.x < transform.translation.x {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        let mut kind = CollisionKind::Wall;
+        let position = bounding_box.closest_point(bounding_circle.center);
+
+        if maybe_paddle.is_some() {
+            kind = CollisionKind::Paddle;
+            let angle = if aimed.enabled {
+                let offset = (ball_transform.translation.y - transform.translation.y)
+                    / (transform.scale.y / 2.);
+                // Returns to the left are turned half a circle, which would mirror the aim.
+                let mirror = match side {
+                    Side::Right => 1.,
+                    Side::Left => -1.,
+                };
+                offset.clamp(-1., 1.) * FRAC_PI_4 * mirror
+            } else {
+                (PI * rng.random::<f32>() - FRAC_PI_2) / 2.
+            };
+            rally.0 += 1;
+            let speed = **ball_speed * speedup.multiplier(rally.0);
+            **ball_velocity = match side {
+                Side::Right => Vec2::from_angle(angle) * speed,
+                Side::Left => Vec2::from_angle(angle + PI) * speed,
+            };
+        } else if let Some(mut shield) = maybe_shield {
+            shield.uses -= 1;
+            ball_velocity.x = ball_velocity.x.abs() * -transform.translation.x.signum();
+            **ball_velocity *= **restitution;
+        } else if let Some(machine) = maybe_machine {
+            **ball_velocity = machine.feed(-transform.translation.x.signum());
+        } else if is_backboard {
+            ball_velocity.x = ball_velocity.x.abs() * -transform.translation.x.signum();
+            **ball_velocity *= **restitution;
+        } else {
+            // Always away from the wall and back out of it, so a wall nudged into the ball can't
+            // trap it flipping back and forth inside.
+            let away_from_wall = -transform.translation.y.signum();
+            ball_velocity.y = ball_velocity.y.abs() * away_from_wall;
+            **ball_velocity *= **restitution;
+            ball_transform.translation.y =
+                transform.translation.y + away_from_wall * (transform.scale.y / 2. + ball_radius);
+        }
+        **ball_velocity = speed_floor.apply(**ball_velocity);
+
+        collisions.write(BallCollision {
+            ball,
+            position,
+            normal: (bounding_circle.center - position).normalize_or_zero(),
+            kind,
+        });
+    }
+}
+
+/// Fraction of the way from `start` to `end` at which the segment enters `bounds`, if it starts
+/// outside them.
+pub(crate) fn segment_entry(start: Vec2, end: Vec2, bounds: &Aabb2d) -> Option<f32> {
+    let inside = bounds.min.cmple(start).all() && start.cmple(bounds.max).all();
+    if inside {
+        return None;
+    }
+    let delta = end - start;
+    let (mut enter, mut exit) = (0f32, 1f32);
+    for axis in 0..2 {
+        if delta[axis] == 0. {
+            if start[axis] < bounds.min[axis] || start[axis] > bounds.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let to_min = (bounds.min[axis] - start[axis]) / delta[axis];
+        let to_max = (bounds.max[axis] - start[axis]) / delta[axis];
+        enter = enter.max(to_min.min(to_max));
+        exit = exit.min(to_min.max(to_max));
+    }
+    (enter <= exit).then_some(enter)
+}
+
+/// Serves an escaped ball again, or takes an escaped extra ball out of play.
+fn reset_escaped_ball(
+    mut commands: Commands,
+    balls: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Interpolated,
+            Has<ExtraBall>,
+        ),
+        With<Ball>,
+    >,
+    serve: Res<ServeConfig>,
+    mut rng: ResMut<GameRng>,
+) {
+    let bounds = WALL_OFFSET + BALL_ESCAPE_MARGIN;
+    for (entity, mut transform, mut velocity, mut interpolated, extra) in balls {
+        if transform.translation.x.abs() <= bounds && transform.translation.y.abs() <= bounds {
+            continue;
+        }
+
+        warn!(
+            "Ball escaped the arena at {}, resetting",
+            transform.translation.xy()
+        );
+        if extra {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation = Vec3::ZERO;
+        interpolated.snap(Vec3::ZERO);
+        let direction = ServeDirection::random(&mut **rng);
+        **velocity = direction.velocity(&serve, &mut **rng);
+    }
+}
+
+fn break_stalls(
+    ball: Single<&mut Velocity, (With<Ball>, Without<Held>, Without<ExtraBall>)>,
+    mut watchdog: ResMut<StallWatchdog>,
+    mut rng: ResMut<GameRng>,
+    time: Res<Time>,
+) {
+    let mut velocity = ball.into_inner();
+    if velocity.y.abs() >= watchdog.threshold {
+        watchdog.timer.reset();
+        return;
+    }
+    if !watchdog.timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    let speed = velocity.length();
+    let nudge = rng.random_range(watchdog.threshold..=watchdog.threshold.max(watchdog.nudge));
+    velocity.y += if rng.random() { nudge } else { -nudge };
+    **velocity = velocity.normalize_or_zero() * speed;
+    watchdog.timer.reset();
+    debug!("Ball stalled horizontally, nudged to {}", **velocity);
+}
+
+/// Puts the picked profile's values, or the flags' for [`PhysicsProfile::Custom`], in place.
+fn apply_physics_profile(
+    profile: Res<PhysicsProfile>,
+    custom: Res<CustomPhysics>,
+    mut serve: ResMut<ServeConfig>,
+    mut restitution: ResMut<Restitution>,
+    mut speed_floor: ResMut<SpeedFloor>,
+    mut aimed: ResMut<AimedBounces>,
+) {
+    profile.physics().unwrap_or(**custom).apply(
+        &mut serve,
+        &mut restitution,
+        &mut speed_floor,
+        &mut aimed,
+    );
+}
+
+/// Flattens the ball against the surface it just hit, then springs it back through a slight
+/// stretch before it settles into a circle again.
+pub(crate) fn squash_ball(
+    mut collisions: EventReader<BallCollision>,
+    mut balls: Query<(&mut Transform, &BallSize, &mut Squash), With<Ball>>,
+    time: Res<Time>,
+) {
+    for collision in collisions.read() {
+        if collision.kind == CollisionKind::Goal {
+            continue;
+        }
+        if let Ok((_, _, mut squash)) = balls.get_mut(collision.ball) {
+            if collision.normal != Vec2::ZERO {
+                squash.normal = collision.normal;
+            }
+            squash.timer.reset();
+        }
+    }
+
+    for (mut transform, size, mut squash) in &mut balls {
+        squash.timer.tick(time.delta());
+        let progress = squash.timer.fraction();
+        let amount = SQUASH_AMOUNT * (1. - progress) * (progress * TAU).cos();
+        transform.rotation = Quat::from_rotation_z(squash.normal.to_angle());
+        transform.scale = Vec3::new(**size * (1. - amount), **size * (1. + amount), 1.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        paddle::{PADDLE_OFFSET, PADDLE_SIZE},
+        testing::{self, STEP},
+    };
+
+    fn flying_app() -> App {
+        let mut app = testing::app();
+        app.add_systems(FixedUpdate, apply_velocity);
+        app
+    }
+
+    /// An app bouncing balls off whatever is spawned into it, with every physics option at its
+    /// default.
+    fn collision_app() -> App {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let mut app = testing::app();
+        app.add_event::<BallCollision>()
+            .insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .init_resource::<TopologyMode>()
+            .init_resource::<Restitution>()
+            .init_resource::<SkinWidth>()
+            .init_resource::<SpeedFloor>()
+            .insert_resource(AimedBounces { enabled: true })
+            .init_resource::<BallSpeed>()
+            .init_resource::<RallySpeedup>()
+            .init_resource::<Rally>()
+            .add_systems(FixedUpdate, (apply_velocity, detect_collisions).chain());
+        app
+    }
+
+    /// The top wall of the arena.
+    fn spawn_top_wall(app: &mut App) {
+        app.world_mut().spawn((
+            Transform::from_xyz(0., WALL_OFFSET, 0.)
+                .with_scale(Vec2::new(2. * WALL_OFFSET, WALL_WIDTH).extend(1.)),
+            Collider,
+        ));
+    }
+
+    /// A right paddle at its usual place.
+    fn spawn_paddle(app: &mut App) {
+        app.world_mut().spawn((
+            Transform::from_xyz(PADDLE_OFFSET, 0., 0.).with_scale(PADDLE_SIZE.extend(1.)),
+            Paddle {
+                up: KeyCode::ArrowUp,
+                down: KeyCode::ArrowDown,
+                catch: KeyCode::ArrowLeft,
+                controller: PaddleController::Local,
+            },
+            Collider,
+        ));
+    }
+
+    fn spawn_ball(app: &mut App, position: Vec2, velocity: Vec2) -> Entity {
+        app.world_mut()
+            .spawn((
+                Ball,
+                Transform::from_translation(position.extend(0.)),
+                Velocity(velocity),
+                Interpolated::new(position.extend(0.)),
+            ))
+            .id()
+    }
+
+    /// Kinds of the collisions since the last call.
+    fn collisions(app: &mut App) -> Vec<CollisionKind> {
+        let mut events = app.world_mut().resource_mut::<Events<BallCollision>>();
+        let kinds = events
+            .iter_current_update_events()
+            .map(|c| c.kind)
+            .collect();
+        events.clear();
+        kinds
+    }
+
+    /// Steps until the ball hits something of `kind`, for at most a second.
+    fn run_until(app: &mut App, kind: CollisionKind) {
+        for _ in 0..64 {
+            app.update();
+            if collisions(app).contains(&kind) {
+                return;
+            }
+        }
+        panic!("the ball never hit a {kind:?}");
+    }
+
+    fn speed(app: &App, ball: Entity) -> f32 {
+        app.world().get::<Velocity>(ball).unwrap().length()
+    }
+
+    fn position(app: &App, entity: Entity) -> Vec2 {
+        app.world()
+            .get::<Transform>(entity)
+            .unwrap()
+            .translation
+            .xy()
+    }
+
+    #[test]
+    fn ball_moves_by_its_velocity() {
+        let mut app = flying_app();
+        let ball = app
+            .world_mut()
+            .spawn((Transform::default(), Velocity(Vec2::new(BALL_SPEED, -100.))))
+            .id();
+        app.update();
+        assert_eq!(position(&app, ball), Vec2::new(BALL_SPEED, -100.) * STEP);
+    }
+
+    #[test]
+    fn damping_scales_the_step() {
+        let mut app = flying_app();
+        let ball = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                Velocity(Vec2::new(BALL_SPEED, 0.)),
+                Damping(0.5),
+            ))
+            .id();
+        app.update();
+        assert_eq!(position(&app, ball), Vec2::new(BALL_SPEED * 0.5 * STEP, 0.));
+    }
+
+    #[test]
+    fn escaped_balls_are_served_again_within_a_step() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let mut app = testing::app();
+        app.insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .init_resource::<ServeConfig>()
+            .add_systems(FixedUpdate, reset_escaped_ball);
+        let far = Vec3::new(WALL_OFFSET * 10., -WALL_OFFSET * 3., 0.);
+        let mut spawn_ball = |translation: Vec3| {
+            app.world_mut()
+                .spawn((
+                    Ball,
+                    Transform::from_translation(translation),
+                    Velocity(Vec2::new(BALL_SPEED, 0.)),
+                    Interpolated::new(translation),
+                ))
+                .id()
+        };
+        let ball = spawn_ball(far);
+        let in_play = spawn_ball(Vec3::new(0., WALL_OFFSET - BALL_SIZE, 0.));
+        let extra = spawn_ball(-far);
+        app.world_mut().entity_mut(extra).insert(ExtraBall);
+        app.update();
+
+        assert_eq!(position(&app, ball), Vec2::ZERO);
+        let speed = app.world().get::<Velocity>(ball).unwrap().length();
+        assert!((speed - ServeConfig::default().speed).abs() < 1e-3);
+        assert_eq!(
+            position(&app, in_play),
+            Vec2::new(0., WALL_OFFSET - BALL_SIZE)
+        );
+        assert!(app.world().get_entity(extra).is_err());
+    }
+
+    #[test]
+    fn collision_radius_follows_a_growing_ball() {
+        let mut app = collision_app();
+        app.add_systems(FixedUpdate, resize_ball.before(detect_collisions));
+        spawn_top_wall(&mut app);
+        let gap = 15.;
+        let y = WALL_OFFSET - WALL_WIDTH / 2. - gap;
+        let ball = spawn_ball(&mut app, Vec2::new(0., y), Vec2::ZERO);
+        app.world_mut().entity_mut(ball).insert(BallSizeModifier {
+            scale: 2.,
+            timer: Timer::from_seconds(1., TimerMode::Once),
+        });
+
+        let reach =
+            |app: &App| **app.world().get::<BallSize>(ball).unwrap() / 2. + DEFAULT_SKIN_WIDTH;
+        let mut reached = reach(&app);
+        for _ in 0..30 {
+            app.update();
+            let hit = !collisions(&mut app).is_empty();
+            let before = reached;
+            reached = reach(&app);
+            assert_eq!(hit, reached > gap, "reach {before} to {reached}");
+            if hit {
+                return;
+            }
+        }
+        panic!("the ball never grew into the wall");
+    }
+
+    #[test]
+    fn restitution_damps_wall_bounces_but_not_paddle_hits() {
+        let mut app = collision_app();
+        app.insert_resource(Restitution(0.8))
+            .insert_resource(RallySpeedup {
+                per_hit: 0.1,
+                ..default()
+            });
+        spawn_top_wall(&mut app);
+        spawn_paddle(&mut app);
+        let ball = spawn_ball(
+            &mut app,
+            Vec2::new(0., WALL_OFFSET - 30.),
+            Vec2::new(0., BALL_SPEED),
+        );
+        run_until(&mut app, CollisionKind::Wall);
+        assert!((speed(&app, ball) - 0.8 * BALL_SPEED).abs() < 1e-3);
+
+        app.world_mut().entity_mut(ball).insert((
+            Transform::from_xyz(PADDLE_OFFSET - 40., 0., 0.),
+            Velocity(Vec2::new(BALL_SPEED, 0.)),
+            Interpolated::new(Vec3::new(PADDLE_OFFSET - 40., 0., 0.)),
+        ));
+        run_until(&mut app, CollisionKind::Paddle);
+        assert!((speed(&app, ball) - 1.1 * BALL_SPEED).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fast_balls_bounce_off_the_paddle_skin_without_sinking_in() {
+        for skin in [0., DEFAULT_SKIN_WIDTH, 5.] {
+            let mut app = collision_app();
+            app.insert_resource(SkinWidth(skin));
+            spawn_paddle(&mut app);
+            let start = Vec2::new(PADDLE_OFFSET - 100., 0.);
+            let ball = spawn_ball(&mut app, start, Vec2::new(3. * BALL_SPEED, 0.));
+            run_until(&mut app, CollisionKind::Paddle);
+            let face = PADDLE_OFFSET - PADDLE_SIZE.x / 2.;
+            let gap = face - position(&app, ball).x;
+            assert!(
+                (gap - (BALL_SIZE / 2. + skin)).abs() < 1e-3,
+                "skin {skin}, gap {gap}"
+            );
+        }
+    }
+
+    #[test]
+    fn very_fast_balls_bounce_instead_of_tunnelling() {
+        let mut app = collision_app();
+        app.insert_resource(SpeedFloor(0.));
+        spawn_paddle(&mut app);
+        spawn_top_wall(&mut app);
+        // Far enough in a single step to clear the paddle or the wall entirely.
+        let speed = 6000.;
+        let ball = spawn_ball(
+            &mut app,
+            Vec2::new(PADDLE_OFFSET - 40., 0.),
+            Vec2::new(speed, 0.),
+        );
+        app.update();
+        assert_eq!(collisions(&mut app), [CollisionKind::Paddle]);
+        assert!(position(&app, ball).x < PADDLE_OFFSET);
+
+        let ball = spawn_ball(
+            &mut app,
+            Vec2::new(0., WALL_OFFSET - 40.),
+            Vec2::new(0., speed),
+        );
+        app.update();
+        assert!(collisions(&mut app).contains(&CollisionKind::Wall));
+        assert!(position(&app, ball).y < WALL_OFFSET);
+        assert_eq!(app.world().get::<Velocity>(ball).unwrap().y, -speed);
+    }
+
+    #[test]
+    fn serves_go_at_serve_speed_until_the_first_hit() {
+        let mut app = collision_app();
+        app.insert_resource(SpeedFloor(0.));
+        spawn_paddle(&mut app);
+        let serve = ServeConfig {
+            cone: 0.,
+            speed: BALL_SPEED / 2.,
+            ..default()
+        };
+        let velocity = ServeDirection::Right
+            .velocity(&serve, &mut **app.world_mut().resource_mut::<GameRng>());
+        let ball = spawn_ball(&mut app, Vec2::new(PADDLE_OFFSET - 50., 0.), velocity);
+        assert_eq!(speed(&app, ball), BALL_SPEED / 2.);
+        run_until(&mut app, CollisionKind::Paddle);
+        assert!((speed(&app, ball) - BALL_SPEED).abs() < 1e-3);
+    }
+
+    #[test]
+    fn paddles_return_the_ball_at_the_configured_speed() {
+        let mut app = collision_app();
+        app.insert_resource(SpeedFloor(0.))
+            .insert_resource(BallSpeed(300.));
+        spawn_paddle(&mut app);
+        let ball = spawn_ball(
+            &mut app,
+            Vec2::new(PADDLE_OFFSET - 50., 0.),
+            Vec2::new(BALL_SPEED, 0.),
+        );
+        run_until(&mut app, CollisionKind::Paddle);
+        assert!((speed(&app, ball) - 300.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn damped_bounces_never_leave_below_the_speed_floor() {
+        let mut app = collision_app();
+        app.insert_resource(Restitution(0.3));
+        spawn_top_wall(&mut app);
+        let floor = SpeedFloor::default().0;
+        let ball = spawn_ball(
+            &mut app,
+            Vec2::new(0., WALL_OFFSET - 30.),
+            Vec2::new(0., BALL_SPEED),
+        );
+        run_until(&mut app, CollisionKind::Wall);
+        assert!((speed(&app, ball) - floor).abs() < 1e-3);
+        // A ball at rest is left alone rather than sent off in no direction.
+        assert_eq!(SpeedFloor(floor).apply(Vec2::ZERO), Vec2::ZERO);
+    }
+
+    #[test]
+    fn aimed_bounces_leave_nothing_to_chance() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let hit_with_seed = |aimed: bool, seed: u64| {
+            let mut app = collision_app();
+            app.insert_resource(GameRng(StdRng::seed_from_u64(seed)))
+                .insert_resource(AimedBounces { enabled: aimed });
+            spawn_paddle(&mut app);
+            let ball = spawn_ball(
+                &mut app,
+                Vec2::new(PADDLE_OFFSET - 50., 10.),
+                Vec2::new(BALL_SPEED, 0.),
+            );
+            run_until(&mut app, CollisionKind::Paddle);
+            **app.world().get::<Velocity>(ball).unwrap()
+        };
+        assert_eq!(hit_with_seed(true, 1), hit_with_seed(true, 2));
+        assert_ne!(hit_with_seed(false, 1), hit_with_seed(false, 2));
+    }
+
+    #[test]
+    fn bumpers_turn_the_ball_through_a_right_angle() {
+        let mut app = collision_app();
+        app.world_mut().spawn((
+            Transform::default(),
+            Bumper {
+                normal: Vec2::ONE.normalize(),
+                half_length: 40.,
+            },
+        ));
+        let ball = spawn_ball(&mut app, Vec2::new(80., 0.), Vec2::new(-BALL_SPEED, 0.));
+        for _ in 0..30 {
+            app.update();
+            if !collisions(&mut app).is_empty() {
+                let velocity = **app.world().get::<Velocity>(ball).unwrap();
+                assert!(
+                    velocity.distance(Vec2::new(0., BALL_SPEED)) < 1e-3,
+                    "{velocity}"
+                );
+                return;
+            }
+        }
+        panic!("the ball never reached the bumper");
+    }
+
+    #[test]
+    fn stalled_balls_are_nudged_off_the_horizontal_at_the_same_speed() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let mut app = testing::app();
+        app.insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .insert_resource(StallWatchdog {
+                timer: Timer::from_seconds(64. * STEP, TimerMode::Once),
+                ..default()
+            })
+            .add_systems(FixedUpdate, break_stalls);
+        let ball = spawn_ball(&mut app, Vec2::ZERO, Vec2::new(BALL_SPEED, 0.));
+        let velocity = |app: &App| **app.world().get::<Velocity>(ball).unwrap();
+        for _ in 0..63 {
+            app.update();
+            assert_eq!(velocity(&app).y, 0.);
+        }
+        app.update();
+        assert_ne!(velocity(&app).y, 0.);
+        assert!((velocity(&app).length() - BALL_SPEED).abs() < 1e-3);
+    }
+
+    #[test]
+    fn spin_curves_the_flight_its_way_and_wears_off() {
+        let mut app = testing::app();
+        app.insert_resource(SpinConfig {
+            enabled: true,
+            decay: 0.5,
+            ..default()
+        })
+        .add_systems(FixedUpdate, curve_spinning_ball);
+        let ball = spawn_ball(&mut app, Vec2::ZERO, Vec2::new(BALL_SPEED, 0.));
+        app.world_mut().entity_mut(ball).insert(Spin(1.));
+        app.update();
+
+        let velocity = **app.world().get::<Velocity>(ball).unwrap();
+        assert!(
+            velocity.y > 0.,
+            "counter-clockwise spin turns a rightward ball up"
+        );
+        assert!((velocity.length() - BALL_SPEED).abs() < 1e-3);
+        assert!((velocity.to_angle() - STEP).abs() < 1e-5);
+        assert_eq!(**app.world().get::<Spin>(ball).unwrap(), 0.5f32.powf(STEP));
+    }
+
+    #[test]
+    fn profiles_match_their_descriptions() {
+        let classic = PhysicsProfile::Classic.physics().unwrap();
+        assert_eq!(classic.serve_speed, BALL_SPEED);
+        assert_eq!(classic.serve_cone, SERVE_CONE_DEGREES.to_radians());
+        assert_eq!(classic.restitution, 1.);
+        assert!(!classic.aimed_bounces);
+
+        let heavy = PhysicsProfile::Heavy.physics().unwrap();
+        assert!(heavy.serve_speed < BALL_SPEED);
+        assert!(heavy.restitution < 1.);
+
+        let floaty = PhysicsProfile::Floaty.physics().unwrap();
+        assert!(floaty.serve_speed < BALL_SPEED);
+        assert!(floaty.serve_cone > classic.serve_cone);
+        assert_eq!(floaty.restitution, 1.);
+
+        let arcade = PhysicsProfile::Arcade.physics().unwrap();
+        assert_eq!(arcade.serve_speed, BALL_SPEED);
+        assert!(arcade.serve_cone < classic.serve_cone);
+        assert!(arcade.aimed_bounces);
+
+        assert_eq!(PhysicsProfile::Custom.physics(), None);
+    }
+
+    #[test]
+    fn picking_custom_goes_back_to_the_flags() {
+        let custom = BallPhysics {
+            serve_speed: 150.,
+            serve_cone: 0.1,
+            restitution: 0.9,
+            speed_floor: 20.,
+            aimed_bounces: false,
+        };
+        let mut app = testing::app();
+        app.insert_resource(PhysicsProfile::Arcade)
+            .insert_resource(CustomPhysics(custom))
+            .init_resource::<ServeConfig>()
+            .init_resource::<Restitution>()
+            .init_resource::<SpeedFloor>()
+            .insert_resource(AimedBounces { enabled: false })
+            .add_systems(
+                Update,
+                apply_physics_profile.run_if(resource_changed::<PhysicsProfile>),
+            );
+        let applied = |app: &App| {
+            let world = app.world();
+            let serve = world.resource::<ServeConfig>();
+            BallPhysics {
+                serve_speed: serve.speed,
+                serve_cone: serve.cone,
+                restitution: world.resource::<Restitution>().0,
+                speed_floor: world.resource::<SpeedFloor>().0,
+                aimed_bounces: world.resource::<AimedBounces>().enabled,
+            }
+        };
+
+        app.update();
+        assert_eq!(Some(applied(&app)), PhysicsProfile::Arcade.physics());
+        *app.world_mut().resource_mut::<PhysicsProfile>() = PhysicsProfile::Custom;
+        app.update();
+        assert_eq!(applied(&app), custom);
+    }
+}
@@ -0,0 +1,881 @@
+//! Effects that dress the game up without changing how it plays out: particles, flashes, glows,
+//! camera moves, slow motion and debug overlays. They only run with a window.
+
+use crate::{
+    ball::{
+        BALL_SPEED, Ball, BallCollision, BallSize, CollisionKind, ExtraBall, Velocity, squash_ball,
+    },
+    paddle::{Held, PaddleConfig, predict_path},
+    pickups::{DOUBLE_POINTS_COLOR, DoublePoints},
+    replay::ReplayMode,
+    score::{Celebrating, Celebration, MatchClock, MatchLimit, Score, TieBreak},
+    ui::EFFECT_BUDGETS,
+    wall::{TopologyMode, WALL_OFFSET, WALL_WIDTH},
+};
+use bevy::{math::StableInterpolate, prelude::*};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{PI, TAU};
+
+const GOAL_FLASH_DURATION: f32 = 0.6;
+
+const CONTACT_MARKER_LIFETIME: f32 = 0.25;
+const CONTACT_MARKER_RADIUS: f32 = 3.;
+const CONTACT_NORMAL_LENGTH: f32 = 30.;
+const CONTACT_MARKER_COLOR: Color = Color::srgb(1., 0.2, 0.8);
+
+const PARTICLE_SIZE: f32 = 4.;
+const PARTICLE_COUNT: usize = 8;
+const PARTICLE_SPEED: f32 = 150.;
+const PARTICLE_LIFETIME: f32 = 0.4;
+
+/// Pulses per second of the edge glow on the side holding double points.
+const DOUBLE_POINTS_PULSE_RATE: f32 = 2.;
+
+const SKIP_CELEBRATION_KEY: KeyCode = KeyCode::Enter;
+
+const GRID_LINE_WIDTH: f32 = 1.;
+pub(crate) const GRID_COLOR: Color = Color::srgba(1., 1., 1., 0.06);
+/// Behind everything else in the arena.
+const GRID_Z: f32 = -10.;
+
+/// Camera offset in pixels of a full strength shake.
+const SCREEN_SHAKE_MAX_OFFSET: f32 = 12.;
+/// Shake lost per second, where a goal starts a full one.
+const SCREEN_SHAKE_DECAY: f32 = 2.;
+const PADDLE_HIT_SHAKE: f32 = 0.3;
+
+/// Game speed while the ball closes in on a goal that would end the match.
+const MATCH_POINT_SLOW_SPEED: f32 = 0.6;
+/// How close to that goal the slow motion starts, as a fraction of the distance from the center.
+const MATCH_POINT_SLOW_ZONE: f32 = 0.4;
+
+const WIN_ZOOM_DURATION: f32 = 1.;
+/// Camera scale at the height of the win zoom.
+const WIN_ZOOM_SCALE: f32 = 0.6;
+/// Fraction of the win zoom spent punching in, the rest easing back out.
+const WIN_ZOOM_ATTACK: f32 = 0.2;
+
+const TRAJECTORY_MAX_BOUNCES: usize = 2;
+const TRAJECTORY_MAX_LENGTH: f32 = 600.;
+const TRAJECTORY_COLOR: Color = Color::srgba(1., 1., 1., 0.2);
+
+pub(crate) const EDGE_GLOW_WIDTH: Val = Val::Px(24.);
+/// Alpha at full intensity with the ball right at the goal.
+const EDGE_GLOW_MAX_ALPHA: f32 = 0.35;
+
+/// Only added with a window, like the UI.
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugDraw>().add_systems(
+            Update,
+            (
+                (toggle_debug_draw, draw_contact_points).chain(),
+                (spawn_collision_particles, move_particles, fade_out).chain(),
+                squash_ball,
+                (flash_goals, fade_goal_flashes, skip_celebration).chain(),
+                update_edge_glow,
+                (shake_camera, slow_match_point),
+                draw_trajectory_preview,
+                sync_arena_grid.run_if(resource_changed::<ArenaGrid>),
+                (toggle_camera_zoom, zoom_camera)
+                    .chain()
+                    .run_if(not(resource_exists::<WinZoom>)),
+                (
+                    start_win_zoom,
+                    play_win_zoom.run_if(resource_exists::<WinZoom>),
+                )
+                    .chain()
+                    .after(shake_camera)
+                    .after(zoom_camera),
+            ),
+        );
+    }
+}
+
+/// Cosmetic entity counted against the [`EffectBudget`].
+#[derive(Component, Default)]
+struct Effect;
+
+/// Most effect entities alive at once, so long games can't pile them up on weak hardware. Effects
+/// that would go over budget are skipped.
+#[derive(Resource)]
+pub(crate) struct EffectBudget(pub(crate) usize);
+
+impl Default for EffectBudget {
+    fn default() -> Self {
+        Self(EFFECT_BUDGETS[2])
+    }
+}
+
+/// Cosmetic only, so it moves outside the fixed-step simulation.
+#[derive(Component)]
+#[require(Effect)]
+struct Particle {
+    velocity: Vec2,
+}
+
+/// Fades the entity's material out over the timer, then despawns it.
+#[derive(Component)]
+struct Fade(Timer);
+
+#[derive(Resource)]
+pub(crate) struct ParticleMesh(pub(crate) Handle<Mesh>);
+
+/// Easy mode line showing where the ball is headed, off by default since it gives the game away.
+#[derive(Resource, Default)]
+pub(crate) struct TrajectoryPreview {
+    pub(crate) sides: PreviewSides,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum PreviewSides {
+    #[default]
+    Off,
+    /// Only once the ball is in the half of the player who has to return it.
+    Defending,
+    Both,
+}
+
+impl PreviewSides {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            PreviewSides::Off => PreviewSides::Defending,
+            PreviewSides::Defending => PreviewSides::Both,
+            PreviewSides::Both => PreviewSides::Off,
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            PreviewSides::Off => "Off",
+            PreviewSides::Defending => "Defending side",
+            PreviewSides::Both => "Both sides",
+        }
+    }
+}
+
+/// Glow along each side of the screen that brightens as the ball closes in on that side's goal.
+#[derive(Resource)]
+pub(crate) struct EdgeGlow {
+    /// From 0 (off) to 1.
+    pub(crate) intensity: f32,
+}
+
+impl Default for EdgeGlow {
+    fn default() -> Self {
+        Self { intensity: 0.5 }
+    }
+}
+
+/// Camera shake on goals and paddle hits.
+#[derive(Resource)]
+pub(crate) struct ScreenShake {
+    /// From 0 (off) to 1.
+    pub(crate) intensity: f32,
+}
+
+impl Default for ScreenShake {
+    fn default() -> Self {
+        Self { intensity: 0.5 }
+    }
+}
+
+/// Faint grid behind the arena, which also makes the ball's speed easier to judge.
+#[derive(Resource)]
+pub(crate) struct ArenaGrid {
+    /// Side of a cell in pixels, 0 for no grid.
+    pub(crate) cell: f32,
+    pub(crate) color: Color,
+}
+
+#[derive(Component)]
+struct GridLine;
+
+/// Slows the game down as the ball heads for a goal that would end the match.
+#[derive(Resource, Default)]
+pub(crate) struct MatchPointSlowMotion {
+    pub(crate) enabled: bool,
+}
+
+/// Turns off the effects that move the whole view or change the game's pace: the camera zooms,
+/// the screen shake and the match point slow motion.
+#[derive(Resource, Default)]
+pub(crate) struct ReduceMotion {
+    pub(crate) enabled: bool,
+}
+
+/// Screen edge panel of the [`EdgeGlow`], on the side `x` has the sign of.
+#[derive(Component)]
+pub(crate) struct EdgeGlowPanel {
+    pub(crate) x: f32,
+    pub(crate) color: Color,
+}
+
+/// Lights up a goal wall in the conceding player's color when the ball passes it.
+#[derive(Component)]
+pub(crate) struct GoalFlash {
+    pub(crate) color: Color,
+    timer: Timer,
+}
+
+impl GoalFlash {
+    pub(crate) fn new(color: Color) -> Self {
+        let mut timer = Timer::from_seconds(GOAL_FLASH_DURATION, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self { color, timer }
+    }
+}
+
+/// Zooms the camera out as the ball speeds up. The scale never drops below 1, so the whole arena
+/// stays in view.
+#[derive(Resource)]
+pub(crate) struct CameraZoom {
+    pub(crate) enabled: bool,
+    pub(crate) toggle: KeyCode,
+    pub(crate) max_scale: f32,
+    pub(crate) zoom_per_speed: f32,
+    pub(crate) smoothing: f32,
+}
+
+impl Default for CameraZoom {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            toggle: KeyCode::F2,
+            max_scale: 1.3,
+            zoom_per_speed: 0.25 / BALL_SPEED,
+            smoothing: 3.,
+        }
+    }
+}
+
+/// Quick zoom toward the goal that just won the match, in and back out before the result shows.
+/// Leaves the projection exactly as it found it, so the next match doesn't start zoomed.
+#[derive(Resource)]
+pub(crate) struct WinZoom {
+    timer: Timer,
+    focus: Vec2,
+    scale: f32,
+}
+
+/// Overlays debugging gizmos, such as where the ball made contact and the normal it bounced off.
+#[derive(Resource)]
+pub(crate) struct DebugDraw {
+    pub(crate) enabled: bool,
+    toggle: KeyCode,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            toggle: KeyCode::F3,
+        }
+    }
+}
+
+/// A contact point drawn by [`draw_contact_points`] until `timer` runs out.
+struct ContactMarker {
+    position: Vec2,
+    normal: Vec2,
+    timer: Timer,
+}
+
+fn toggle_debug_draw(mut debug_draw: ResMut<DebugDraw>, keyboard: Res<ButtonInput<KeyCode>>) {
+    if keyboard.just_pressed(debug_draw.toggle) {
+        debug_draw.enabled = !debug_draw.enabled;
+    }
+}
+
+/// Marks every contact point with its normal, keeping each one up long enough to be seen even when
+/// the ball bounces on consecutive frames.
+fn draw_contact_points(
+    mut gizmos: Gizmos,
+    mut collisions: EventReader<BallCollision>,
+    debug_draw: Res<DebugDraw>,
+    time: Res<Time>,
+    mut markers: Local<Vec<ContactMarker>>,
+) {
+    if !debug_draw.enabled {
+        collisions.clear();
+        markers.clear();
+        return;
+    }
+    markers.extend(collisions.read().map(|collision| ContactMarker {
+        position: collision.position,
+        normal: collision.normal,
+        timer: Timer::from_seconds(CONTACT_MARKER_LIFETIME, TimerMode::Once),
+    }));
+    markers.retain_mut(|marker| !marker.timer.tick(time.delta()).finished());
+
+    for marker in markers.iter() {
+        gizmos.circle_2d(marker.position, CONTACT_MARKER_RADIUS, CONTACT_MARKER_COLOR);
+        gizmos.arrow_2d(
+            marker.position,
+            marker.position + marker.normal * CONTACT_NORMAL_LENGTH,
+            CONTACT_MARKER_COLOR,
+        );
+    }
+}
+
+/// Serves right away, clearing the goal's effects instead of letting them play out.
+fn skip_celebration(
+    mut commands: Commands,
+    ball: Single<Entity, (With<Ball>, With<Celebrating>)>,
+    particles: Query<Entity, With<Particle>>,
+    goals: Query<(&mut GoalFlash, &MeshMaterial2d<ColorMaterial>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    replay_mode: Res<ReplayMode>,
+    celebration: Res<Celebration>,
+) {
+    if !keyboard.just_pressed(SKIP_CELEBRATION_KEY)
+        || !celebration.skippable
+        || matches!(*replay_mode, ReplayMode::Playing { .. })
+    {
+        return;
+    }
+    commands.entity(*ball).remove::<Celebrating>();
+    for particle in &particles {
+        commands.entity(particle).despawn();
+    }
+    for (mut flash, material) in goals {
+        let remaining = flash.timer.remaining();
+        flash.timer.tick(remaining);
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color = Color::WHITE;
+        }
+    }
+}
+
+fn toggle_camera_zoom(mut zoom: ResMut<CameraZoom>, keyboard: Res<ButtonInput<KeyCode>>) {
+    if keyboard.just_pressed(zoom.toggle) {
+        zoom.enabled = !zoom.enabled;
+    }
+}
+
+fn zoom_camera(
+    zoom: Res<CameraZoom>,
+    reduce_motion: Res<ReduceMotion>,
+    ball: Single<&Velocity, (With<Ball>, Without<ExtraBall>)>,
+    mut projection: Single<&mut Projection, With<Camera2d>>,
+    time: Res<Time>,
+) {
+    let Projection::Orthographic(ortho) = &mut **projection else {
+        return;
+    };
+    let target = if zoom.enabled && !reduce_motion.enabled {
+        let excess_speed = (ball.length() - BALL_SPEED).max(0.);
+        (1. + excess_speed * zoom.zoom_per_speed).clamp(1., zoom.max_scale)
+    } else {
+        1.
+    };
+    ortho
+        .scale
+        .smooth_nudge(&target, zoom.smoothing, time.delta_secs());
+}
+
+/// Starts the [`WinZoom`] when a goal ends the match. Matches that run out of time end without one.
+pub(crate) fn start_win_zoom(
+    mut commands: Commands,
+    mut collisions: EventReader<BallCollision>,
+    clock: Res<MatchClock>,
+    projection: Single<&Projection, With<Camera2d>>,
+    reduce_motion: Res<ReduceMotion>,
+) {
+    let goal = collisions
+        .read()
+        .filter(|collision| collision.kind == CollisionKind::Goal)
+        .last();
+    let (Some(goal), Projection::Orthographic(ortho)) = (goal, *projection) else {
+        return;
+    };
+    if clock.over && !reduce_motion.enabled {
+        commands.insert_resource(WinZoom {
+            timer: Timer::from_seconds(WIN_ZOOM_DURATION, TimerMode::Once),
+            focus: goal.position,
+            scale: ortho.scale,
+        });
+    }
+}
+
+/// Runs on real time, so the match point slow motion winding down doesn't drag it out.
+fn play_win_zoom(
+    mut commands: Commands,
+    mut zoom: ResMut<WinZoom>,
+    camera: Single<(&mut Transform, &mut Projection), With<Camera2d>>,
+    time: Res<Time<Real>>,
+) {
+    let (mut transform, mut projection) = camera.into_inner();
+    let Projection::Orthographic(ortho) = &mut *projection else {
+        return;
+    };
+    if zoom.timer.tick(time.delta()).finished() {
+        ortho.scale = zoom.scale;
+        transform.translation.x = 0.;
+        transform.translation.y = 0.;
+        commands.remove_resource::<WinZoom>();
+        return;
+    }
+    let progress = zoom.timer.fraction();
+    let punch = if progress < WIN_ZOOM_ATTACK {
+        progress / WIN_ZOOM_ATTACK
+    } else {
+        (1. - progress) / (1. - WIN_ZOOM_ATTACK)
+    };
+    let punch = punch * punch * (3. - 2. * punch);
+    ortho.scale = zoom.scale + (WIN_ZOOM_SCALE - zoom.scale) * punch;
+    transform.translation.x = zoom.focus.x * punch;
+    transform.translation.y = zoom.focus.y * punch;
+}
+
+/// Respawns the grid lines, one through the center and then every cell out to the walls.
+fn sync_arena_grid(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    grid: Res<ArenaGrid>,
+    lines: Query<Entity, With<GridLine>>,
+) {
+    for line in &lines {
+        commands.entity(line).despawn();
+    }
+    if grid.cell <= 0. {
+        return;
+    }
+    let mesh = meshes.add(Rectangle::default());
+    let material = materials.add(grid.color);
+    let bound = WALL_OFFSET - WALL_WIDTH / 2.;
+    let cells = (bound / grid.cell) as i32;
+    for i in -cells..=cells {
+        let at = i as f32 * grid.cell;
+        for (translation, scale) in [
+            (Vec2::new(at, 0.), Vec2::new(GRID_LINE_WIDTH, 2. * bound)),
+            (Vec2::new(0., at), Vec2::new(2. * bound, GRID_LINE_WIDTH)),
+        ] {
+            commands.spawn((
+                Mesh2d(mesh.clone()),
+                MeshMaterial2d(material.clone()),
+                Transform::from_translation(translation.extend(GRID_Z))
+                    .with_scale(scale.extend(1.)),
+                GridLine,
+            ));
+        }
+    }
+}
+
+fn spawn_collision_particles(
+    mut commands: Commands,
+    mut collisions: EventReader<BallCollision>,
+    effects: Query<(), With<Effect>>,
+    budget: Res<EffectBudget>,
+    mesh: Res<ParticleMesh>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let mut live = effects.iter().count();
+    let mut rng = rand::rng();
+    for collision in collisions.read() {
+        let count = PARTICLE_COUNT.min(budget.0.saturating_sub(live));
+        live += count;
+        for _ in 0..count {
+            let velocity = Vec2::from_angle(rng.random_range(0. ..2. * PI))
+                * PARTICLE_SPEED
+                * rng.random_range(0.5..=1.);
+            commands.spawn((
+                Mesh2d(mesh.0.clone()),
+                MeshMaterial2d(materials.add(collision.kind.color())),
+                Transform::from_translation(collision.position.extend(1.))
+                    .with_scale(Vec2::splat(PARTICLE_SIZE).extend(1.)),
+                Particle { velocity },
+                Fade(Timer::from_seconds(PARTICLE_LIFETIME, TimerMode::Once)),
+            ));
+        }
+    }
+}
+
+fn move_particles(particles: Query<(&mut Transform, &Particle)>, time: Res<Time>) {
+    for (mut transform, particle) in particles {
+        transform.translation += (particle.velocity * time.delta_secs()).extend(0.);
+    }
+}
+
+fn fade_out(
+    mut commands: Commands,
+    fading: Query<(Entity, &mut Fade, &MeshMaterial2d<ColorMaterial>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+) {
+    for (entity, mut fade, material) in fading {
+        if fade.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        } else if let Some(material) = materials.get_mut(&material.0) {
+            material.color.set_alpha(fade.0.fraction_remaining());
+        }
+    }
+}
+
+fn draw_trajectory_preview(
+    mut gizmos: Gizmos,
+    ball: Single<
+        (&Transform, &Velocity, &BallSize),
+        (With<Ball>, Without<Held>, Without<ExtraBall>),
+    >,
+    preview: Res<TrajectoryPreview>,
+    paddles: Res<PaddleConfig>,
+    topology: Res<TopologyMode>,
+) {
+    let (transform, velocity, size) = *ball;
+    let position = transform.translation.xy();
+    let defending = position.x * velocity.x > 0.;
+    match preview.sides {
+        PreviewSides::Off => return,
+        PreviewSides::Defending if !defending => return,
+        PreviewSides::Defending | PreviewSides::Both => {}
+    }
+
+    // The same band the AI predicts bounces in.
+    let bound = match *topology {
+        TopologyMode::Bounce => WALL_OFFSET - WALL_WIDTH / 2. - **size / 2.,
+        TopologyMode::Wrap => f32::INFINITY,
+    };
+    let target_x = paddles.offset * velocity.x.signum();
+    let path = predict_path(
+        position,
+        **velocity,
+        target_x,
+        bound,
+        TRAJECTORY_MAX_BOUNCES,
+    );
+
+    let mut remaining = TRAJECTORY_MAX_LENGTH;
+    let mut points = vec![path[0]];
+    for segment in path.windows(2) {
+        let length = segment[0].distance(segment[1]);
+        if length >= remaining {
+            points.push(segment[0].move_towards(segment[1], remaining));
+            break;
+        }
+        remaining -= length;
+        points.push(segment[1]);
+    }
+    gizmos.linestrip_2d(points, TRAJECTORY_COLOR);
+}
+
+/// Offsets the camera by a random amount that grows with how recently the last goal or hit was.
+/// With the intensity at zero the camera sits exactly at the origin.
+fn shake_camera(
+    mut camera: Single<&mut Transform, With<Camera2d>>,
+    mut collisions: EventReader<BallCollision>,
+    mut trauma: Local<f32>,
+    shake: Res<ScreenShake>,
+    reduce_motion: Res<ReduceMotion>,
+    time: Res<Time<Virtual>>,
+) {
+    for collision in collisions.read() {
+        *trauma = match collision.kind {
+            CollisionKind::Goal => 1.,
+            CollisionKind::Paddle => (*trauma + PADDLE_HIT_SHAKE).min(1.),
+            CollisionKind::Wall => *trauma,
+        };
+    }
+    *trauma = (*trauma - SCREEN_SHAKE_DECAY * time.delta_secs()).max(0.);
+
+    let offset =
+        if shake.intensity > 0. && !reduce_motion.enabled && *trauma > 0. && !time.is_paused() {
+            let mut rng = rand::rng();
+            let direction = Vec2::new(rng.random_range(-1. ..=1.), rng.random_range(-1. ..=1.));
+            direction * shake.intensity * *trauma * *trauma * SCREEN_SHAKE_MAX_OFFSET
+        } else {
+            Vec2::ZERO
+        };
+    camera.translation.x = offset.x;
+    camera.translation.y = offset.y;
+}
+
+/// Slows down from whatever speed the game ran at, fast forward included, and goes back to it
+/// after.
+fn slow_match_point(
+    ball: Single<(&Transform, &Velocity), (With<Ball>, Without<ExtraBall>)>,
+    slow_motion: Res<MatchPointSlowMotion>,
+    reduce_motion: Res<ReduceMotion>,
+    limit: Res<MatchLimit>,
+    clock: Res<MatchClock>,
+    scores: Res<Score>,
+    replay_mode: Res<ReplayMode>,
+    mut time: ResMut<Time<Virtual>>,
+    // The speed before slowing down, and the one slowed down to.
+    mut slowed: Local<Option<(f32, f32)>>,
+) {
+    // Goals that would end the match, by the sign of their x.
+    let deciding = match *limit {
+        MatchLimit::Score { .. } => [
+            limit.won_at((scores.0, scores.1 + 1)),
+            limit.won_at((scores.0 + 1, scores.1)),
+        ],
+        MatchLimit::Time { seconds, tie } => {
+            let sudden_death =
+                clock.elapsed >= seconds && tie == TieBreak::Overtime && scores.0 == scores.1;
+            [sudden_death; 2]
+        }
+        MatchLimit::Endless => [false; 2],
+    };
+    let (transform, velocity) = *ball;
+    let side = usize::from(velocity.x > 0.);
+    let closeness = transform.translation.x.abs() / WALL_OFFSET;
+    let slow = slow_motion.enabled
+        && !reduce_motion.enabled
+        && !clock.over
+        && !matches!(*replay_mode, ReplayMode::Playing { .. })
+        && deciding[side]
+        && transform.translation.x * velocity.x > 0.
+        && closeness >= 1. - MATCH_POINT_SLOW_ZONE;
+
+    match *slowed {
+        None if slow => {
+            let previous = time.relative_speed();
+            let speed = previous * MATCH_POINT_SLOW_SPEED;
+            time.set_relative_speed(speed);
+            *slowed = Some((previous, speed));
+        }
+        Some((previous, speed)) if !slow => {
+            // Whatever changed the speed in the meantime wins.
+            if time.relative_speed() == speed {
+                time.set_relative_speed(previous);
+            }
+            *slowed = None;
+        }
+        _ => {}
+    }
+}
+
+fn update_edge_glow(
+    panels: Query<(&EdgeGlowPanel, &mut BackgroundColor)>,
+    ball: Single<(&Transform, &Velocity), (With<Ball>, Without<ExtraBall>)>,
+    glow: Res<EdgeGlow>,
+    double: Option<Res<DoublePoints>>,
+    time: Res<Time>,
+) {
+    let (transform, velocity) = *ball;
+    // The double points glow is gameplay information, so it shows even with the edge glow off.
+    let double_side = double.map(|double| if double.player == 0 { -1. } else { 1. });
+    let pulse = (time.elapsed_secs() * DOUBLE_POINTS_PULSE_RATE * TAU).sin() * 0.5 + 0.5;
+    for (panel, mut background) in panels {
+        if double_side == Some(panel.x) {
+            let color = DOUBLE_POINTS_COLOR.with_alpha(pulse * EDGE_GLOW_MAX_ALPHA);
+            background.set_if_neq(BackgroundColor(color));
+            continue;
+        }
+        let approaching = velocity.x * panel.x > 0.;
+        let distance = WALL_OFFSET - transform.translation.x * panel.x;
+        let closeness = if approaching {
+            (1. - distance / WALL_OFFSET).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let alpha = glow.intensity * closeness * EDGE_GLOW_MAX_ALPHA;
+        background.set_if_neq(BackgroundColor(panel.color.with_alpha(alpha)));
+    }
+}
+
+fn flash_goals(
+    mut collisions: EventReader<BallCollision>,
+    goals: Query<(&Transform, &mut GoalFlash)>,
+) {
+    let scored: Vec<_> = collisions
+        .read()
+        .filter(|collision| collision.kind == CollisionKind::Goal)
+        .map(|collision| collision.position)
+        .collect();
+    for (transform, mut flash) in goals {
+        let side = transform.translation.x.signum();
+        if scored.iter().any(|position| position.x.signum() == side) {
+            flash.timer.reset();
+        }
+    }
+}
+
+fn fade_goal_flashes(
+    goals: Query<(&mut GoalFlash, &MeshMaterial2d<ColorMaterial>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+) {
+    for (mut flash, material) in goals {
+        if flash.timer.finished() {
+            continue;
+        }
+        flash.timer.tick(time.delta());
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color = flash.color.mix(&Color::WHITE, flash.timer.fraction());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, STEP};
+
+    fn scale(app: &mut App) -> f32 {
+        let projection = app
+            .world_mut()
+            .query_filtered::<&Projection, With<Camera2d>>()
+            .single(app.world())
+            .unwrap();
+        let Projection::Orthographic(ortho) = projection else {
+            panic!("the camera should be orthographic");
+        };
+        ortho.scale
+    }
+
+    #[test]
+    fn win_zoom_puts_the_projection_back() {
+        let mut app = testing::app();
+        let camera = app
+            .world_mut()
+            .spawn((
+                Camera2d,
+                Projection::Orthographic(OrthographicProjection {
+                    scale: 1.2,
+                    ..OrthographicProjection::default_2d()
+                }),
+            ))
+            .id();
+        app.insert_resource(WinZoom {
+            timer: Timer::from_seconds(WIN_ZOOM_DURATION, TimerMode::Once),
+            focus: Vec2::new(WALL_OFFSET, 40.),
+            scale: 1.2,
+        })
+        .add_systems(Update, play_win_zoom.run_if(resource_exists::<WinZoom>));
+
+        let steps = (WIN_ZOOM_DURATION / STEP).ceil() as usize;
+        for _ in 0..steps / 4 {
+            app.update();
+        }
+        assert!(scale(&mut app) < 1.);
+        for _ in 0..steps {
+            app.update();
+        }
+        assert!(!app.world().contains_resource::<WinZoom>());
+        assert_eq!(scale(&mut app), 1.2);
+        let translation = app.world().get::<Transform>(camera).unwrap().translation;
+        assert_eq!(translation.xy(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn particles_stop_at_the_effect_budget() {
+        let mut app = testing::app();
+        app.add_event::<BallCollision>()
+            .insert_resource(EffectBudget(PARTICLE_COUNT * 2 + 3))
+            .insert_resource(ParticleMesh(Handle::default()))
+            .init_resource::<Assets<ColorMaterial>>()
+            .add_systems(Update, spawn_collision_particles);
+        let collide = |app: &mut App, times: usize| {
+            for _ in 0..times {
+                app.world_mut().send_event(BallCollision {
+                    ball: Entity::PLACEHOLDER,
+                    position: Vec2::ZERO,
+                    normal: Vec2::Y,
+                    kind: CollisionKind::Wall,
+                });
+            }
+            app.update();
+            app.world_mut()
+                .query_filtered::<(), With<Effect>>()
+                .iter(app.world())
+                .count()
+        };
+        assert_eq!(collide(&mut app, 2), PARTICLE_COUNT * 2);
+        // The third burst is cut short, then nothing more fits.
+        assert_eq!(collide(&mut app, 2), PARTICLE_COUNT * 2 + 3);
+        assert_eq!(collide(&mut app, 1), PARTICLE_COUNT * 2 + 3);
+    }
+
+    /// How far a goal shakes the camera under `intensity`, over a few frames.
+    fn goal_shake(intensity: f32, reduce_motion: bool) -> f32 {
+        let mut app = testing::app();
+        app.add_event::<BallCollision>()
+            .insert_resource(ScreenShake { intensity })
+            .insert_resource(ReduceMotion {
+                enabled: reduce_motion,
+            })
+            .add_systems(Update, shake_camera);
+        let camera = app.world_mut().spawn((Camera2d, Transform::default())).id();
+        app.world_mut().send_event(BallCollision {
+            ball: Entity::PLACEHOLDER,
+            position: Vec2::ZERO,
+            normal: Vec2::X,
+            kind: CollisionKind::Goal,
+        });
+        (0..8)
+            .map(|_| {
+                app.update();
+                let translation = app.world().get::<Transform>(camera).unwrap().translation;
+                translation.xy().abs().max_element()
+            })
+            .fold(0., f32::max)
+    }
+
+    #[test]
+    fn screen_shake_scales_with_its_intensity() {
+        assert_eq!(goal_shake(0., false), 0.);
+        assert_eq!(goal_shake(1., true), 0.);
+        let full = goal_shake(1., false);
+        assert!(full > 0. && full <= SCREEN_SHAKE_MAX_OFFSET);
+        assert!(goal_shake(0.25, false) <= SCREEN_SHAKE_MAX_OFFSET / 4.);
+    }
+
+    /// An app at match point for the right player, running at `speed`, with the ball at `x`
+    /// headed for the left goal.
+    fn match_point_app(speed: f32, x: f32) -> (App, Entity) {
+        let mut app = testing::app();
+        app.world_mut()
+            .resource_mut::<Time<Virtual>>()
+            .set_relative_speed(speed);
+        app.insert_resource(MatchPointSlowMotion { enabled: true })
+            .init_resource::<ReduceMotion>()
+            .insert_resource(MatchLimit::Score {
+                points: 5,
+                win_by: 1,
+            })
+            .init_resource::<MatchClock>()
+            .insert_resource(Score(0, 4))
+            .insert_resource(ReplayMode::Off)
+            .add_systems(Update, slow_match_point);
+        let ball = app
+            .world_mut()
+            .spawn((
+                Ball,
+                Transform::from_xyz(x, 0., 0.),
+                Velocity(Vec2::new(-BALL_SPEED, 0.)),
+            ))
+            .id();
+        (app, ball)
+    }
+
+    fn speed(app: &App) -> f32 {
+        app.world().resource::<Time<Virtual>>().relative_speed()
+    }
+
+    #[test]
+    fn match_point_slow_motion_goes_back_to_the_previous_speed() {
+        let (mut app, ball) = match_point_app(2., -WALL_OFFSET * 0.9);
+        app.update();
+        assert_eq!(speed(&app), 2. * MATCH_POINT_SLOW_SPEED);
+
+        // Returned, so the ball heads away from the deciding goal.
+        app.world_mut().get_mut::<Velocity>(ball).unwrap().x = BALL_SPEED;
+        app.update();
+        assert_eq!(speed(&app), 2.);
+    }
+
+    #[test]
+    fn reduce_motion_keeps_the_match_point_at_full_speed() {
+        let (mut app, _) = match_point_app(1., -WALL_OFFSET * 0.9);
+        app.insert_resource(ReduceMotion { enabled: true });
+        app.update();
+        assert_eq!(speed(&app), 1.);
+    }
+}
@@ -0,0 +1,189 @@
+//! Moving between the title screen, a match and its result.
+
+use crate::{
+    ball::{Ball, ExtraBall, Rally, StallWatchdog, Velocity},
+    interpolation::Interpolated,
+    paddle::Paddle,
+    pickups::DoublePoints,
+    practice::Practice,
+    replay::BestRally,
+    score::{
+        Celebrating, MatchClock, MatchHistory, PlayerNames, Ratings, Score, ScoreChanged,
+        ScoreDecay, save_match,
+    },
+    serve::{GameRng, GameSeed, ServeConfig, Serving},
+    settings::PendingSaves,
+    ui::{ResultBanner, SettingsMenu},
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand::{SeedableRng, rngs::StdRng};
+
+/// Only added with a window. Headless runs start out playing and never pause.
+pub struct FlowPlugin;
+
+impl Plugin for FlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Title), pause_time)
+            .add_systems(OnExit(GameState::Title), unpause_time)
+            .add_systems(OnEnter(GameState::Paused), pause_time)
+            .add_systems(OnExit(GameState::Paused), unpause_time)
+            .add_systems(
+                Update,
+                (
+                    start_match.run_if(in_state(GameState::Title)),
+                    end_match.run_if(
+                        in_state(GameState::Playing).and(|clock: Res<MatchClock>| clock.over),
+                    ),
+                    rematch.run_if(
+                        in_state(GameState::GameOver)
+                            .and(|rematches: Res<Rematches>| rematches.enabled),
+                    ),
+                ),
+            );
+    }
+}
+
+/// Where the game is, from the title screen to the result. Pausing and the title screen also
+/// pause virtual time, so fixed steps and the effects driven by it stop too.
+#[derive(States, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub(crate) enum GameState {
+    #[default]
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// Whether a finished match can be restarted from the result screen. Online games and recorded
+/// replays only hold one match.
+#[derive(Resource)]
+pub(crate) struct Rematches {
+    pub(crate) enabled: bool,
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unpause_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn start_match(keyboard: Res<ButtonInput<KeyCode>>, mut next_state: ResMut<NextState<GameState>>) {
+    if keyboard.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn end_match(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::GameOver);
+}
+
+/// What a match starts from: the score, the clock, the balls and the paddles.
+#[derive(SystemParam)]
+pub(crate) struct MatchReset<'w, 's> {
+    commands: Commands<'w, 's>,
+    balls: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static mut Transform,
+            &'static mut Velocity,
+            &'static mut Interpolated,
+            Has<ExtraBall>,
+        ),
+        (With<Ball>, Without<Paddle>),
+    >,
+    paddles: Query<'w, 's, (&'static mut Transform, &'static mut Interpolated), With<Paddle>>,
+    score_changed: EventWriter<'w, ScoreChanged>,
+    pub(crate) scores: ResMut<'w, Score>,
+    pub(crate) clock: ResMut<'w, MatchClock>,
+    decay: ResMut<'w, ScoreDecay>,
+    watchdog: ResMut<'w, StallWatchdog>,
+    rng: ResMut<'w, GameRng>,
+    seed: Res<'w, GameSeed>,
+    serve: Res<'w, ServeConfig>,
+}
+
+impl MatchReset<'_, '_> {
+    /// Starts the random draws over from the share code, so the next match replays like the first.
+    pub(crate) fn reseed(&mut self) {
+        **self.rng = StdRng::seed_from_u64(self.seed.seed.into());
+    }
+
+    /// Clears the score, the clock and the timers running over the match, and serves from the
+    /// center the way the first match did.
+    pub(crate) fn reset(&mut self) {
+        *self.scores = Score(0, 0);
+        *self.clock = MatchClock::default();
+        self.decay.timer.reset();
+        self.watchdog.timer.reset();
+        self.score_changed.write(ScoreChanged);
+        self.commands.remove_resource::<DoublePoints>();
+        self.commands.insert_resource(Rally::default());
+
+        for (entity, mut transform, mut velocity, mut interpolated, extra) in &mut self.balls {
+            if extra {
+                self.commands.entity(entity).despawn();
+                continue;
+            }
+            self.commands
+                .entity(entity)
+                .remove::<Celebrating>()
+                .insert(Serving);
+            transform.translation = Vec3::ZERO;
+            interpolated.snap(Vec3::ZERO);
+            **velocity = self.seed.serve.velocity(&self.serve, &mut **self.rng);
+        }
+        for (mut transform, mut interpolated) in &mut self.paddles {
+            transform.translation.y = 0.;
+            interpolated.snap(transform.translation);
+        }
+    }
+}
+
+/// Records the finished match and resets the score, balls and paddles for another, played right
+/// away on Enter or started from the title screen on Backspace.
+fn rematch(
+    mut game: MatchReset,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut banner: Single<&mut Visibility, (With<ResultBanner>, Without<SettingsMenu>)>,
+    menu: Single<&Visibility, With<SettingsMenu>>,
+    names: Res<PlayerNames>,
+    practice: Res<Practice>,
+    mut records: (
+        ResMut<MatchHistory>,
+        ResMut<Ratings>,
+        ResMut<PendingSaves>,
+        ResMut<BestRally>,
+    ),
+) {
+    let to_title = keyboard.just_pressed(KeyCode::Backspace);
+    if !(keyboard.just_pressed(KeyCode::Enter) || to_title) || **menu != Visibility::Hidden {
+        return;
+    }
+    let (history, ratings, saves, best_rally) = &mut records;
+    if !practice.enabled {
+        save_match(
+            history,
+            ratings,
+            saves,
+            &game.scores,
+            &names,
+            game.clock.elapsed,
+            game.clock.over,
+        );
+    }
+    // Replays run from the share code, so the rematch has to as well for its rallies to replay.
+    game.reseed();
+    best_rally.restart();
+    game.reset();
+    **banner = Visibility::Hidden;
+    next_state.set(if to_title {
+        GameState::Title
+    } else {
+        GameState::Playing
+    });
+}
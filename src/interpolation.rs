@@ -0,0 +1,97 @@
+//! Smooth rendering between fixed steps, drawing entities between their last two physics
+//! translations.
+
+use bevy::prelude::*;
+
+/// The fixed-step half also runs headless, since resets move entities through
+/// [`Interpolated::snap`]; the render half needs a window.
+pub struct InterpolationPlugin {
+    pub headless: bool,
+}
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedPreUpdate, restore_physical_translation)
+            .add_systems(FixedPostUpdate, store_physical_translation);
+        if !self.headless {
+            app.add_systems(
+                Update,
+                (toggle_interpolation, interpolate_translation).chain(),
+            );
+        }
+    }
+}
+
+/// Physics translations at the last two fixed steps, used to smooth rendering.
+#[derive(Component, Clone)]
+pub(crate) struct Interpolated {
+    pub(crate) previous: Vec3,
+    pub(crate) current: Vec3,
+}
+
+impl Interpolated {
+    pub(crate) fn new(translation: Vec3) -> Self {
+        Self {
+            previous: translation,
+            current: translation,
+        }
+    }
+
+    pub(crate) fn snap(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
+    }
+}
+
+/// Rendering lags physics by up to one fixed step while enabled.
+#[derive(Resource)]
+pub(crate) struct Interpolation {
+    pub(crate) enabled: bool,
+    pub(crate) toggle: KeyCode,
+}
+
+impl Default for Interpolation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            toggle: KeyCode::F1,
+        }
+    }
+}
+
+fn restore_physical_translation(query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in query {
+        transform.translation = interpolated.current;
+        interpolated.previous = interpolated.current;
+    }
+}
+
+pub(crate) fn store_physical_translation(query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query {
+        interpolated.current = transform.translation;
+    }
+}
+
+fn toggle_interpolation(
+    mut interpolation: ResMut<Interpolation>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+) {
+    if keyboard.just_pressed(interpolation.toggle) {
+        interpolation.enabled = !interpolation.enabled;
+    }
+}
+
+pub(crate) fn interpolate_translation(
+    query: Query<(&mut Transform, &Interpolated)>,
+    interpolation: Res<Interpolation>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    let alpha = if interpolation.enabled {
+        fixed_time.overstep_fraction()
+    } else {
+        1.
+    };
+    for (mut transform, interpolated) in query {
+        transform.translation = interpolated.previous.lerp(interpolated.current, alpha);
+    }
+}
@@ -0,0 +1,365 @@
+//! Deterministic lockstep LAN mode. Both peers run the full simulation and only exchange paddle
+//! input. A tick is simulated once both inputs for it have arrived; local input is scheduled
+//! `INPUT_DELAY` ticks ahead so latency below that is hidden. After every tick each peer sends a
+//! hash of its game state, so a desync is reported on the tick it happens.
+//!
+//! Both peers must be started with the same gameplay flags and settings, down to the physics
+//! profile, assist and goal celebration. The host picks the seed.
+
+use crate::{
+    Simulation,
+    ball::{Ball, ExtraBall, Velocity},
+    net::Connection,
+    paddle::{Paddle, PaddleController, PaddleIntent, ai_move_paddles, read_paddle_input},
+    score::{Celebration, Score},
+    serve::{GameRng, GameSeed, ServeConfig},
+    settings::cli_arg,
+    simulate::state_hash,
+    ui::TEXT_PADDING,
+};
+use bevy::prelude::*;
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    io::ErrorKind,
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+const INPUT_DELAY: u64 = 3;
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+const RECONNECT_INTERVAL: f32 = 1.;
+const STATUS_TEXT_SIZE: f32 = 24.;
+
+#[derive(Clone, Copy, Debug)]
+pub enum LockstepMode {
+    Host(SocketAddr),
+    Client(SocketAddr),
+}
+
+impl LockstepMode {
+    pub fn from_args() -> Option<Self> {
+        cli_arg("--lockstep-host")
+            .map(LockstepMode::Host)
+            .or_else(|| cli_arg("--lockstep-connect").map(LockstepMode::Client))
+    }
+}
+
+/// Like the authoritative mode, the host plays the right paddle and the client the left one.
+pub struct LockstepPlugin(pub LockstepMode);
+
+impl Plugin for LockstepPlugin {
+    fn build(&self, app: &mut App) {
+        let endpoint = match self.0 {
+            LockstepMode::Host(addr) => match TcpListener::bind(addr)
+                .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            {
+                Ok(listener) => {
+                    info!("Hosting lockstep game on {addr}");
+                    Endpoint::Host(listener)
+                }
+                Err(err) => {
+                    error!("Failed to host on {addr}: {err}");
+                    return;
+                }
+            },
+            LockstepMode::Client(addr) => Endpoint::Client {
+                addr,
+                retry: Timer::from_seconds(RECONNECT_INTERVAL, TimerMode::Repeating),
+            },
+        };
+
+        // Skipping isn't part of the exchanged input, so it would only end one peer's celebration.
+        app.world_mut().resource_mut::<Celebration>().skippable = false;
+        app.insert_resource(endpoint)
+            .insert_resource(Lockstep::new())
+            .configure_sets(
+                FixedUpdate,
+                Simulation.run_if(|lockstep: Res<Lockstep>| lockstep.ready),
+            )
+            .add_systems(
+                Startup,
+                (assign_paddles.after(crate::setup::setup), spawn_status_text),
+            )
+            .add_systems(Update, (connect, update_status_text))
+            .add_systems(
+                FixedUpdate,
+                (
+                    exchange_inputs
+                        .after(read_paddle_input)
+                        .after(ai_move_paddles)
+                        .before(Simulation),
+                    check_sync.after(Simulation),
+                ),
+            );
+    }
+}
+
+#[derive(Resource)]
+enum Endpoint {
+    Host(TcpListener),
+    Client { addr: SocketAddr, retry: Timer },
+}
+
+#[derive(Resource)]
+struct Lockstep {
+    connection: Option<Connection>,
+    /// Whether the seed is agreed on. The host decides it, so it only waits for a connection.
+    synced_seed: bool,
+    tick: u64,
+    local_inputs: BTreeMap<u64, f32>,
+    remote_inputs: BTreeMap<u64, f32>,
+    local_hashes: BTreeMap<u64, u64>,
+    remote_hashes: BTreeMap<u64, u64>,
+    ready: bool,
+    desynced: bool,
+}
+
+impl Lockstep {
+    fn new() -> Self {
+        let delay: BTreeMap<u64, f32> = (0..INPUT_DELAY).map(|tick| (tick, 0.)).collect();
+        Self {
+            connection: None,
+            synced_seed: false,
+            tick: 0,
+            local_inputs: delay.clone(),
+            remote_inputs: delay,
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            ready: false,
+            desynced: false,
+        }
+    }
+
+    fn disconnect(&mut self, reason: impl std::fmt::Display) {
+        warn!("Lockstep peer disconnected: {reason}");
+        self.connection = None;
+        self.ready = false;
+    }
+
+    fn send(&mut self, message: &LockstepMessage) {
+        if let Some(connection) = &mut self.connection
+            && let Err(err) = connection.send(message)
+        {
+            self.disconnect(err);
+        }
+    }
+
+    fn compare_hashes(&mut self) {
+        while let Some((&tick, &remote)) = self.remote_hashes.first_key_value() {
+            let Some(local) = self.local_hashes.remove(&tick) else {
+                break;
+            };
+            self.remote_hashes.remove(&tick);
+            if local != remote && !self.desynced {
+                error!("Desync at tick {tick}: local state {local:016x}, remote {remote:016x}");
+                self.desynced = true;
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum LockstepMessage {
+    /// The host's share code, sent once on connect.
+    Hello(String),
+    Input {
+        tick: u64,
+        intent: f32,
+    },
+    Hash {
+        tick: u64,
+        hash: u64,
+    },
+}
+
+#[derive(Component)]
+struct LockstepStatusText;
+
+fn assign_paddles(paddles: Query<(&Transform, &mut Paddle)>, endpoint: Res<Endpoint>) {
+    let remote_on_left = matches!(*endpoint, Endpoint::Host(_));
+    for (transform, mut paddle) in paddles {
+        let on_left = transform.translation.x < 0.;
+        paddle.controller = if on_left == remote_on_left {
+            PaddleController::Remote
+        } else {
+            PaddleController::Local
+        };
+    }
+}
+
+fn spawn_status_text(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: STATUS_TEXT_SIZE,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: TEXT_PADDING,
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        LockstepStatusText,
+    ));
+}
+
+fn update_status_text(
+    mut text: Single<&mut Text, With<LockstepStatusText>>,
+    lockstep: Res<Lockstep>,
+    endpoint: Res<Endpoint>,
+) {
+    let status = if lockstep.desynced {
+        format!("Desynced at tick {}", lockstep.tick)
+    } else if lockstep.connection.is_some() {
+        String::new()
+    } else {
+        match &*endpoint {
+            Endpoint::Host(listener) => listener
+                .local_addr()
+                .map(|addr| format!("Waiting for opponent on {addr}"))
+                .unwrap_or_default(),
+            Endpoint::Client { addr, .. } => format!("Connecting to {addr}"),
+        }
+    };
+    if text.0 != status {
+        text.0 = status;
+    }
+}
+
+fn connect(
+    mut endpoint: ResMut<Endpoint>,
+    mut lockstep: ResMut<Lockstep>,
+    seed: Res<GameSeed>,
+    time: Res<Time>,
+) {
+    if lockstep.connection.is_some() {
+        return;
+    }
+    match &mut *endpoint {
+        Endpoint::Host(listener) => match listener.accept() {
+            Ok((stream, addr)) => match Connection::new(stream) {
+                Ok(connection) => {
+                    info!("Lockstep opponent connected from {addr}");
+                    lockstep.connection = Some(connection);
+                    lockstep.synced_seed = true;
+                    lockstep.send(&LockstepMessage::Hello(seed.to_string()));
+                }
+                Err(err) => warn!("Failed to set up connection from {addr}: {err}"),
+            },
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => warn!("Failed to accept opponent: {err}"),
+        },
+        Endpoint::Client { addr, retry } => {
+            if !retry.tick(time.delta()).just_finished() {
+                return;
+            }
+            match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT).and_then(Connection::new) {
+                Ok(connection) => {
+                    info!("Connected to lockstep host {addr}");
+                    lockstep.connection = Some(connection);
+                }
+                Err(err) => debug!("Failed to connect to {addr}: {err}"),
+            }
+        }
+    }
+}
+
+fn exchange_inputs(
+    mut commands: Commands,
+    mut lockstep: ResMut<Lockstep>,
+    paddles: Query<(&Paddle, &mut PaddleIntent)>,
+    mut ball: Single<&mut Velocity, (With<Ball>, Without<ExtraBall>)>,
+    serve: Res<ServeConfig>,
+) {
+    let lockstep = &mut *lockstep;
+    lockstep.ready = false;
+    let Some(connection) = &mut lockstep.connection else {
+        return;
+    };
+    let messages = match connection.receive::<LockstepMessage>() {
+        Ok(messages) => messages,
+        Err(err) => return lockstep.disconnect(err),
+    };
+    // The new RNG is only in place once commands are applied, so never simulate on the same step.
+    let mut reseeded = false;
+    for message in messages {
+        match message {
+            LockstepMessage::Hello(code) => match code.parse::<GameSeed>() {
+                Ok(seed) => {
+                    info!("Using host's share code {seed}");
+                    // Redraw the serve from the fresh RNG, the same first draw the host made.
+                    let mut rng = StdRng::seed_from_u64(seed.seed.into());
+                    ***ball = seed.serve.velocity(&serve, &mut rng);
+                    commands.insert_resource(GameRng(rng));
+                    commands.insert_resource(seed);
+                    lockstep.synced_seed = true;
+                    reseeded = true;
+                }
+                Err(err) => return lockstep.disconnect(format!("bad share code: {err}")),
+            },
+            LockstepMessage::Input { tick, intent } => {
+                lockstep.remote_inputs.insert(tick, intent);
+            }
+            LockstepMessage::Hash { tick, hash } => {
+                lockstep.remote_hashes.insert(tick, hash);
+            }
+        }
+    }
+    lockstep.compare_hashes();
+    if !lockstep.synced_seed || reseeded {
+        return;
+    }
+
+    let scheduled = lockstep.tick + INPUT_DELAY;
+    if let Entry::Vacant(entry) = lockstep.local_inputs.entry(scheduled) {
+        let intent = paddles
+            .iter()
+            .find(|(paddle, _)| paddle.controller != PaddleController::Remote)
+            .map_or(0., |(_, intent)| **intent);
+        entry.insert(intent);
+        lockstep.send(&LockstepMessage::Input {
+            tick: scheduled,
+            intent,
+        });
+    }
+
+    let tick = lockstep.tick;
+    let (Some(&local), Some(&remote)) = (
+        lockstep.local_inputs.get(&tick),
+        lockstep.remote_inputs.get(&tick),
+    ) else {
+        return;
+    };
+    for (paddle, mut intent) in paddles {
+        **intent = if paddle.controller == PaddleController::Remote {
+            remote
+        } else {
+            local
+        };
+    }
+    lockstep.local_inputs.remove(&tick);
+    lockstep.remote_inputs.remove(&tick);
+    lockstep.ready = true;
+}
+
+fn check_sync(
+    mut lockstep: ResMut<Lockstep>,
+    balls: Query<(&Transform, &Velocity), With<Ball>>,
+    paddles: Query<&Transform, (With<Paddle>, Without<Ball>)>,
+    score: Res<Score>,
+) {
+    if !lockstep.ready {
+        return;
+    }
+    let tick = lockstep.tick;
+    let hash = state_hash(balls, paddles, &score);
+    lockstep.local_hashes.insert(tick, hash);
+    lockstep.send(&LockstepMessage::Hash { tick, hash });
+    lockstep.compare_hashes();
+    lockstep.tick += 1;
+}
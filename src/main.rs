@@ -1,187 +1,322 @@
-use bevy::{
-    math::bounding::{Aabb2d, BoundingCircle, IntersectsVolume},
-    prelude::*,
+// Bevy systems take everything they use as parameters, queries included, which these flag.
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+mod ball;
+mod effects;
+mod flow;
+mod interpolation;
+#[cfg(feature = "lockstep")]
+mod lockstep;
+#[cfg(feature = "net")]
+mod net;
+mod paddle;
+mod pickups;
+mod practice;
+mod replay;
+mod score;
+mod serve;
+mod settings;
+mod setup;
+mod simulate;
+mod sound;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod ui;
+mod wall;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy, window::WindowResolution};
+use rand::{SeedableRng, rngs::StdRng};
+use std::{fs::File, io::LineWriter, path::PathBuf, time::Duration};
+
+use ball::BallPlugin;
+use effects::{DebugDraw, EffectsPlugin};
+use flow::{FlowPlugin, GameState, Rematches};
+use interpolation::InterpolationPlugin;
+use paddle::{PaddleInputPlugin, PaddlePlugin};
+use pickups::PickupPlugin;
+use practice::PracticePlugin;
+use replay::{ReplayControlsPlugin, ReplayMode, ReplayPlugin, StepMode, save_replay};
+use score::{MatchClock, ScorePlugin, check_match_limit, record_match};
+use serve::{GameRng, GameSeed};
+use settings::{
+    Resolution, SavedSettings, SettingsPlugin, WindowBranding, cli_arg, cli_flag,
+    flush_saves_on_exit,
 };
-use std::f32::consts::{FRAC_PI_2, PI};
+#[cfg(not(target_arch = "wasm32"))]
+use settings::{WindowIcon, set_window_icon};
+use setup::SetupPlugin;
+use simulate::{HashLog, log_state_hash};
+use sound::SoundPlugin;
+use theme::ThemePlugin;
+use ui::UiPlugin;
+use wall::WallPlugin;
 
-const WALL_WIDTH: f32 = 10.;
-const WALL_OFFSET: f32 = 220.;
-const WALL_LENGTH: f32 = WALL_OFFSET * 2. + WALL_WIDTH;
+#[cfg(debug_assertions)]
+const FAST_FORWARD_KEY: KeyCode = KeyCode::Tab;
+#[cfg(debug_assertions)]
+const FAST_FORWARD_SPEED: f32 = 4.;
 
-const PADDLE_SPEED: f32 = 200.;
-const PADDLE_OFFSET: f32 = 200.;
-const PADDLE_SIZE: Vec2 = Vec2::new(10., 50.);
+const WINDOW_TITLE: &str = "Bong";
 
-const BALL_SIZE: f32 = 20.;
-const BALL_SPEED: f32 = 200.;
+const FIXED_TIMESTEP_HZ: f64 = 64.;
 
-const TEXT_PADDING: Val = Val::Px(20.);
-const TEXT_SIZE: f32 = 36.;
+fn main() -> AppExit {
+    let replay_mode = ReplayMode::from_args();
+    let seed = match &replay_mode {
+        ReplayMode::Playing { seed, .. } => *seed,
+        _ => GameSeed::from_args(),
+    };
+    let simulate = cli_arg("--simulate").filter(|games: &u32| *games > 0);
+    let simulate_points = cli_arg("--simulate-points").filter(|points: &u32| *points > 0);
+    let resolution: Option<Resolution> = cli_arg("--resolution");
+    let settings = SavedSettings::load();
+    let branding = WindowBranding {
+        title: cli_arg("--title").or_else(|| settings.window_title.clone()),
+        icon: cli_arg("--icon").or_else(|| settings.window_icon.clone()),
+    };
+    if branding.icon.is_some() && cfg!(target_arch = "wasm32") {
+        eprintln!("Ignoring the window icon: browsers show the page's favicon instead");
+    }
 
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_systems(Startup, setup)
-        .add_systems(
-            Update,
+    #[cfg(feature = "net")]
+    let net_mode = net::NetMode::from_args();
+    #[cfg(feature = "lockstep")]
+    let lockstep_mode = lockstep::LockstepMode::from_args();
+    #[allow(unused_mut)]
+    let mut online = false;
+    #[cfg(feature = "net")]
+    {
+        online |= net_mode.is_some();
+    }
+    #[cfg(feature = "lockstep")]
+    {
+        online |= lockstep_mode.is_some();
+    }
+    let step_mode = cli_flag("--step-mode");
+
+    let mut app = App::new();
+    let exporting = matches!(
+        replay_mode,
+        ReplayMode::Playing {
+            export: Some(_),
+            ..
+        }
+    );
+    let checking = matches!(replay_mode, ReplayMode::Playing { check: Some(_), .. });
+    let headless = simulate.is_some() || checking;
+    // Anything the player didn't start by hand skips the title screen.
+    let initial_state =
+        if headless || online || step_mode || matches!(replay_mode, ReplayMode::Playing { .. }) {
+            GameState::Playing
+        } else {
+            GameState::Title
+        };
+    let rematches = Rematches {
+        enabled: !headless && !online && matches!(replay_mode, ReplayMode::Off),
+    };
+    if let Some(games) = simulate {
+        app.add_plugins(simulate::SimulatePlugin {
+            games,
+            points: simulate_points,
+        });
+    } else if checking {
+        app.add_plugins(simulate::HeadlessPlugin);
+    } else {
+        let mut window = Window {
+            title: branding
+                .title
+                .as_deref()
+                .unwrap_or(WINDOW_TITLE)
+                .to_string(),
+            ..default()
+        };
+        if let Some(resolution) = resolution {
+            window.resolution = WindowResolution::new(resolution.width, resolution.height);
+        }
+        if exporting {
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1. / FIXED_TIMESTEP_HZ,
+            )));
+        }
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(window),
+            ..default()
+        }));
+    }
+
+    app.insert_state(initial_state)
+        .insert_resource(rematches)
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_TIMESTEP_HZ))
+        .insert_resource(GameRng(StdRng::seed_from_u64(seed.seed.into())))
+        .insert_resource(seed)
+        .insert_resource(replay_mode)
+        .insert_resource(branding.clone())
+        .add_plugins((
+            SettingsPlugin {
+                settings,
+                simulated: simulate.is_some(),
+                exporting,
+            },
+            BallPlugin,
+            PaddlePlugin,
+            WallPlugin,
+            ScorePlugin,
+            PickupPlugin,
+            PracticePlugin,
+            ReplayPlugin,
+            SetupPlugin,
+            InterpolationPlugin { headless },
+        ))
+        .configure_sets(
+            FixedUpdate,
+            (Phase::Input, Phase::Replay, Simulation).chain(),
+        )
+        .configure_sets(
+            FixedUpdate,
             (
-                (apply_velocity, move_paddles),
-                detect_collisions,
-                update_score,
+                Phase::Steer,
+                Phase::Move,
+                Phase::Carry,
+                Phase::Resize,
+                Phase::Burst,
+                Phase::Collide,
+                Phase::Score,
+                Phase::Respond,
+                Phase::Targets,
+                Phase::Pickups,
+                Phase::Celebrate,
+                Phase::Escape,
+                Phase::Stall,
+                Phase::Decay,
+                Phase::Limit,
             )
-                .chain(),
+                .chain()
+                .in_set(Simulation),
         )
-        .run();
-}
-
-#[derive(Component, Deref, DerefMut)]
-struct Velocity(Vec2);
-
-#[derive(Component)]
-struct Collider;
+        .configure_sets(
+            FixedUpdate,
+            Simulation
+                .run_if(in_state(GameState::Playing).and(|clock: Res<MatchClock>| !clock.over)),
+        );
 
-#[derive(Component)]
-struct Ball;
+    if let Some(path) = cli_arg::<PathBuf>("--hash-log") {
+        match File::create(&path) {
+            Ok(file) => {
+                app.insert_resource(HashLog {
+                    file: LineWriter::new(file),
+                    step: 0,
+                })
+                .add_systems(
+                    FixedUpdate,
+                    log_state_hash.after(check_match_limit).in_set(Simulation),
+                );
+            }
+            Err(err) => eprintln!(
+                "Ignoring --hash-log: failed to create {}: {err}",
+                path.display()
+            ),
+        }
+    }
 
-#[derive(Component)]
-struct Paddle {
-    up: KeyCode,
-    down: KeyCode,
-}
+    if headless {
+        return app.run();
+    }
 
-#[derive(Component)]
-struct Wall;
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = branding.icon {
+        let icon = app.world().resource::<AssetServer>().load(path);
+        app.insert_resource(WindowIcon(icon)).add_systems(
+            Update,
+            set_window_icon.run_if(resource_exists::<WindowIcon>),
+        );
+    }
 
-#[derive(Resource)]
-struct Score(u32, u32);
+    app.add_plugins((
+        UiPlugin,
+        SoundPlugin,
+        PaddleInputPlugin,
+        FlowPlugin,
+        ReplayControlsPlugin,
+        EffectsPlugin,
+        ThemePlugin,
+    ))
+    .add_systems(
+        Last,
+        (save_replay, record_match, flush_saves_on_exit).chain(),
+    );
+    #[cfg(debug_assertions)]
+    app.add_systems(Update, fast_forward);
+    if step_mode {
+        app.world_mut().resource_mut::<Time<Virtual>>().pause();
+        app.world_mut().resource_mut::<DebugDraw>().enabled = true;
+        app.insert_resource(StepMode {
+            stepping: true,
+            step: false,
+        });
+    }
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    commands.spawn(Camera2d);
-
-    commands.spawn((
-        Mesh2d(meshes.add(Circle::default())),
-        MeshMaterial2d(materials.add(Color::WHITE)),
-        Transform::from_xyz(0., 0., 0.).with_scale(Vec2::splat(BALL_SIZE).extend(1.)),
-        Ball,
-        Velocity(Vec2::new(BALL_SPEED, 0.)),
-    ));
-
-    let mut create_paddle = |x, up, down| {
-        commands.spawn((
-            Mesh2d(meshes.add(Rectangle::default())),
-            MeshMaterial2d(materials.add(Color::WHITE)),
-            Transform::from_xyz(x, 0., 0.).with_scale(PADDLE_SIZE.extend(1.)),
-            Paddle { up, down },
-            Collider,
-        ));
-    };
-    create_paddle(PADDLE_OFFSET, KeyCode::ArrowUp, KeyCode::ArrowDown);
-    create_paddle(-PADDLE_OFFSET, KeyCode::KeyW, KeyCode::KeyS);
-
-    let mut create_wall = |x, y, width, height| {
-        commands.spawn((
-            Mesh2d(meshes.add(Rectangle::default())),
-            MeshMaterial2d(materials.add(Color::WHITE)),
-            Transform::from_xyz(x, y, 0.).with_scale(Vec2::new(width, height).extend(1.)),
-            Wall,
-            Collider,
-        ));
-    };
-    create_wall(WALL_OFFSET, 0., WALL_WIDTH, WALL_LENGTH);
-    create_wall(-WALL_OFFSET, 0., WALL_WIDTH, WALL_LENGTH);
-    create_wall(0., WALL_OFFSET, WALL_LENGTH, WALL_WIDTH);
-    create_wall(0., -WALL_OFFSET, WALL_LENGTH, WALL_WIDTH);
-
-    commands.spawn((
-        Text::new("0 - 0"),
-        TextFont {
-            font_size: TEXT_SIZE,
-            ..default()
-        },
-        TextColor(Color::WHITE),
-        Node {
-            position_type: PositionType::Absolute,
-            top: TEXT_PADDING,
-            left: TEXT_PADDING,
-            ..default()
-        },
-    ));
+    #[cfg(feature = "net")]
+    if let Some(mode) = net_mode {
+        app.add_plugins(net::NetPlugin(mode));
+    }
+    #[cfg(feature = "lockstep")]
+    if let Some(mode) = lockstep_mode {
+        app.add_plugins(lockstep::LockstepPlugin(mode));
+    }
 
-    commands.insert_resource(Score(0, 0));
+    app.run()
 }
 
-fn apply_velocity(query: Query<(&mut Transform, &Velocity)>, time: Res<Time>) {
-    for (mut transform, velocity) in query {
-        transform.translation.x += velocity.x * time.delta_secs();
-        transform.translation.y += velocity.y * time.delta_secs();
-    }
-}
+/// Systems that advance the game world. Skipped wherever another process owns the simulation.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct Simulation;
 
-fn move_paddles(
-    query: Query<(&mut Transform, &Paddle)>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-) {
-    for (mut transform, paddle) in query {
-        if keyboard.pressed(paddle.up) {
-            transform.translation.y += PADDLE_SPEED * time.delta_secs();
-        }
-        if keyboard.pressed(paddle.down) {
-            transform.translation.y -= PADDLE_SPEED * time.delta_secs();
-        }
-    }
+/// The order of a fixed step, so each plugin can slot its systems in without naming the systems of
+/// the others. Replays and lockstep rely on every run stepping in the same order. Everything from
+/// `Steer` on is part of [`Simulation`].
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Phase {
+    /// Paddle intents from the players and the AI.
+    Input,
+    /// Replays recording those intents or replacing them.
+    Replay,
+    Steer,
+    Move,
+    Carry,
+    Resize,
+    Burst,
+    Collide,
+    Score,
+    /// Whatever reacts to a hit, after goals are scored.
+    Respond,
+    Targets,
+    Pickups,
+    Celebrate,
+    Escape,
+    Stall,
+    Decay,
+    Limit,
 }
 
-fn detect_collisions(
-    mut scores: ResMut<Score>,
-    ball: Single<(&mut Transform, &mut Velocity), (With<Ball>, Without<Collider>)>,
-    colliders: Query<(&Transform, Option<&Paddle>), With<Collider>>,
+/// Debug builds only: runs the game faster while held, so long AI rallies resolve quickly. Only
+/// more fixed steps run per frame, so the simulation and replays stay deterministic.
+#[cfg(debug_assertions)]
+fn fast_forward(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+    // The speed before fast forwarding, to go back to on release.
+    mut previous: Local<Option<f32>>,
 ) {
-    let (mut ball_transform, mut ball_velocity) = ball.into_inner();
-    let ball_radius = ball_transform.scale.x / 2.;
-
-    let bounding_circle = BoundingCircle::new(ball_transform.translation.xy(), ball_radius);
-    for (transform, maybe_paddle) in colliders {
-        let bounding_box = Aabb2d::new(transform.translation.xy(), transform.scale.xy() / 2.);
-        if bounding_circle.intersects(&bounding_box) {
-            enum Side {
-                Left,
-                Right,
-            }
-            let side = if ball_transform.translation.x < transform.translation.x {
-                Side::Left
-            } else {
-                Side::Right
-            };
-
-            if maybe_paddle.is_some() {
-                let angle = (PI * rand::random::<f32>() - FRAC_PI_2) / 2.;
-                **ball_velocity = match side {
-                    Side::Right => Vec2::from_angle(angle) * BALL_SPEED,
-                    Side::Left => Vec2::from_angle(angle + PI) * BALL_SPEED,
-                };
-            } else {
-                ball_velocity.y *= -1.;
-                if ball_transform.translation.x - ball_radius < -WALL_OFFSET
-                    || ball_transform.translation.x + ball_radius > WALL_OFFSET
-                {
-                    ball_transform.translation = Vec3::ZERO;
-                    match side {
-                        Side::Left => scores.0 += 1,
-                        Side::Right => scores.1 += 1,
-                    };
-                }
-            }
-
-            break;
+    if keyboard.just_pressed(FAST_FORWARD_KEY) && previous.is_none() {
+        *previous = Some(time.relative_speed());
+        time.set_relative_speed(FAST_FORWARD_SPEED);
+    } else if keyboard.just_released(FAST_FORWARD_KEY)
+        && let Some(previous) = previous.take()
+    {
+        // Whatever changed the speed in the meantime wins.
+        if time.relative_speed() == FAST_FORWARD_SPEED {
+            time.set_relative_speed(previous);
         }
     }
 }
-
-fn update_score(scores: Res<Score>, mut text: Single<&mut Text>) {
-    text.0 = format!("{} - {}", scores.0, scores.1);
-}
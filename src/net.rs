@@ -0,0 +1,429 @@
+//! Two-player LAN mode. The host runs the authoritative simulation and streams snapshots to the
+//! client, which only sends its paddle input back. Messages are newline-delimited RON over TCP.
+
+use crate::{
+    Simulation,
+    ball::{BALL_SIZE, Ball, BallSize, ExtraBall, Velocity},
+    interpolation::{Interpolated, store_physical_translation},
+    paddle::{Paddle, PaddleController, PaddleIntent, read_paddle_input},
+    score::{Score, ScoreChanged},
+    settings::cli_arg,
+    ui::TEXT_PADDING,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+const RECONNECT_INTERVAL: f32 = 1.;
+const STATUS_TEXT_SIZE: f32 = 24.;
+
+#[derive(Clone, Copy, Debug)]
+pub enum NetMode {
+    Host(SocketAddr),
+    Client(SocketAddr),
+}
+
+impl NetMode {
+    pub fn from_args() -> Option<Self> {
+        cli_arg("--host")
+            .map(NetMode::Host)
+            .or_else(|| cli_arg("--connect").map(NetMode::Client))
+    }
+}
+
+/// The host plays the right paddle, the client the left one.
+pub struct NetPlugin(pub NetMode);
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Peer>()
+            .add_systems(Startup, spawn_status_text);
+
+        match self.0 {
+            NetMode::Host(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .and_then(|listener| listener.set_nonblocking(true).map(|()| listener));
+                match listener {
+                    Ok(listener) => {
+                        info!("Hosting on {addr}");
+                        app.insert_resource(Host(listener));
+                    }
+                    Err(err) => {
+                        error!("Failed to host on {addr}: {err}");
+                        return;
+                    }
+                }
+                app.init_resource::<RemoteIntent>()
+                    .add_systems(Startup, hand_left_paddle_to_peer.after(crate::setup::setup))
+                    .add_systems(Update, (accept_peer, pause_while_disconnected).chain())
+                    .add_systems(
+                        FixedUpdate,
+                        (receive_input, apply_remote_intent)
+                            .chain()
+                            .after(read_paddle_input)
+                            .before(Simulation),
+                    )
+                    .add_systems(
+                        FixedPostUpdate,
+                        send_snapshot.after(store_physical_translation),
+                    );
+            }
+            NetMode::Client(addr) => {
+                app.insert_resource(Client {
+                    addr,
+                    retry: Timer::from_seconds(RECONNECT_INTERVAL, TimerMode::Repeating),
+                })
+                .configure_sets(FixedUpdate, Simulation.run_if(|| false))
+                .add_systems(Update, connect_to_host)
+                .add_systems(
+                    FixedUpdate,
+                    (receive_snapshots, send_input.after(read_paddle_input)),
+                );
+            }
+        }
+        app.add_systems(Update, update_status_text);
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct Host(TcpListener);
+
+#[derive(Resource)]
+pub(crate) struct Client {
+    addr: SocketAddr,
+    retry: Timer,
+}
+
+#[derive(Resource, Default)]
+struct Peer(Option<Connection>);
+
+#[derive(Resource, Default)]
+struct RemoteIntent(f32);
+
+#[derive(Component)]
+struct NetStatusText;
+
+#[derive(Serialize, Deserialize)]
+enum ClientMessage {
+    Input(f32),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    ball_translation: [f32; 2],
+    ball_velocity: [f32; 2],
+    ball_size: f32,
+    paddles: [f32; 2],
+    score: (u32, u32),
+    /// Balls from multiball pickups, which the client spawns and despawns to match.
+    extra_balls: Vec<ExtraBallState>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExtraBallState {
+    translation: [f32; 2],
+    velocity: [f32; 2],
+}
+
+pub(crate) struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    pub(crate) fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    pub(crate) fn send(&mut self, message: &impl Serialize) -> io::Result<()> {
+        let line = ron::to_string(message).map_err(io::Error::other)?;
+        self.outgoing.extend_from_slice(line.as_bytes());
+        self.outgoing.push(b'\n');
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn receive<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(read) => self.incoming.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut messages = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            match std::str::from_utf8(&line)
+                .ok()
+                .and_then(|line| ron::from_str(line.trim()).ok())
+            {
+                Some(message) => messages.push(message),
+                None => warn!("Dropping malformed message from peer"),
+            }
+        }
+        Ok(messages)
+    }
+}
+
+impl Peer {
+    fn drop_on_error<T>(&mut self, result: io::Result<T>) -> Option<T> {
+        result
+            .inspect_err(|err| {
+                warn!("Peer disconnected: {err}");
+                self.0 = None;
+            })
+            .ok()
+    }
+}
+
+fn spawn_status_text(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: STATUS_TEXT_SIZE,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: TEXT_PADDING,
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        NetStatusText,
+    ));
+}
+
+fn update_status_text(
+    mut text: Single<&mut Text, With<NetStatusText>>,
+    peer: Res<Peer>,
+    host: Option<Res<Host>>,
+    client: Option<Res<Client>>,
+) {
+    let status = if peer.0.is_some() {
+        String::new()
+    } else if let Some(addr) = host.and_then(|host| host.0.local_addr().ok()) {
+        format!("Waiting for opponent on {addr}")
+    } else if let Some(client) = client {
+        format!("Connecting to {}", client.addr)
+    } else {
+        String::new()
+    };
+    if text.0 != status {
+        text.0 = status;
+    }
+}
+
+fn hand_left_paddle_to_peer(paddles: Query<(&Transform, &mut Paddle)>) {
+    for (transform, mut paddle) in paddles {
+        if transform.translation.x < 0. {
+            paddle.controller = PaddleController::Remote;
+        }
+    }
+}
+
+fn accept_peer(host: Res<Host>, mut peer: ResMut<Peer>) {
+    if peer.0.is_some() {
+        return;
+    }
+    match host.0.accept() {
+        Ok((stream, addr)) => match Connection::new(stream) {
+            Ok(connection) => {
+                info!("Opponent connected from {addr}");
+                peer.0 = Some(connection);
+            }
+            Err(err) => warn!("Failed to set up connection from {addr}: {err}"),
+        },
+        Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+        Err(err) => warn!("Failed to accept opponent: {err}"),
+    }
+}
+
+fn pause_while_disconnected(peer: Res<Peer>, mut time: ResMut<Time<Virtual>>) {
+    if peer.0.is_none() {
+        time.pause();
+    } else if peer.is_changed() {
+        time.unpause();
+    }
+}
+
+fn receive_input(mut peer: ResMut<Peer>, mut remote: ResMut<RemoteIntent>) {
+    let Some(connection) = &mut peer.0 else {
+        return;
+    };
+    let result = connection.receive::<ClientMessage>();
+    for message in peer.drop_on_error(result).unwrap_or_default() {
+        match message {
+            ClientMessage::Input(intent) => remote.0 = intent,
+        }
+    }
+}
+
+fn apply_remote_intent(paddles: Query<(&Paddle, &mut PaddleIntent)>, remote: Res<RemoteIntent>) {
+    for (paddle, mut intent) in paddles {
+        if paddle.controller == PaddleController::Remote {
+            **intent = remote.0;
+        }
+    }
+}
+
+fn send_snapshot(
+    mut peer: ResMut<Peer>,
+    ball: Single<(&Transform, &Velocity, &BallSize), (With<Ball>, Without<ExtraBall>)>,
+    extra_balls: Query<(&Transform, &Velocity), With<ExtraBall>>,
+    paddles: Query<&Transform, (With<Paddle>, Without<Ball>)>,
+    score: Res<Score>,
+) {
+    let Some(connection) = &mut peer.0 else {
+        return;
+    };
+    let (ball_transform, ball_velocity, ball_size) = *ball;
+    let mut snapshot = Snapshot {
+        ball_translation: ball_transform.translation.xy().to_array(),
+        ball_velocity: ball_velocity.to_array(),
+        ball_size: **ball_size,
+        paddles: [0.; 2],
+        score: (score.0, score.1),
+        extra_balls: extra_balls
+            .iter()
+            .map(|(transform, velocity)| ExtraBallState {
+                translation: transform.translation.xy().to_array(),
+                velocity: velocity.to_array(),
+            })
+            .collect(),
+    };
+    for transform in &paddles {
+        let side = usize::from(transform.translation.x > 0.);
+        snapshot.paddles[side] = transform.translation.y;
+    }
+    let result = connection.send(&snapshot);
+    peer.drop_on_error(result);
+}
+
+fn connect_to_host(mut client: ResMut<Client>, mut peer: ResMut<Peer>, time: Res<Time>) {
+    if peer.0.is_some() || !client.retry.tick(time.delta()).just_finished() {
+        return;
+    }
+    match TcpStream::connect_timeout(&client.addr, CONNECT_TIMEOUT).and_then(Connection::new) {
+        Ok(connection) => {
+            info!("Connected to {}", client.addr);
+            peer.0 = Some(connection);
+        }
+        Err(err) => debug!("Failed to connect to {}: {err}", client.addr),
+    }
+}
+
+fn send_input(mut peer: ResMut<Peer>, paddles: Query<(&Transform, &PaddleIntent), With<Paddle>>) {
+    let Some(connection) = &mut peer.0 else {
+        return;
+    };
+    let Some((_, intent)) = paddles
+        .iter()
+        .find(|(transform, _)| transform.translation.x < 0.)
+    else {
+        return;
+    };
+    let result = connection.send(&ClientMessage::Input(**intent));
+    peer.drop_on_error(result);
+}
+
+fn receive_snapshots(
+    mut commands: Commands,
+    mut peer: ResMut<Peer>,
+    ball: Single<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut Interpolated,
+            &mut BallSize,
+            &Mesh2d,
+            &MeshMaterial2d<ColorMaterial>,
+        ),
+        (With<Ball>, Without<ExtraBall>),
+    >,
+    extra_balls: Query<(Entity, &mut Transform, &mut Velocity), (With<ExtraBall>, Without<Paddle>)>,
+    paddles: Query<&mut Transform, (With<Paddle>, Without<Ball>)>,
+    mut score: ResMut<Score>,
+    mut score_changed: EventWriter<ScoreChanged>,
+) {
+    let Some(connection) = &mut peer.0 else {
+        return;
+    };
+    let result = connection.receive::<Snapshot>();
+    let Some(snapshot) = peer
+        .drop_on_error(result)
+        .and_then(|snapshots| snapshots.into_iter().last())
+    else {
+        return;
+    };
+
+    let (
+        mut ball_transform,
+        mut ball_velocity,
+        mut ball_interpolated,
+        mut ball_size,
+        ball_mesh,
+        ball_material,
+    ) = ball.into_inner();
+    ball_transform.translation = Vec2::from(snapshot.ball_translation).extend(0.);
+    **ball_size = snapshot.ball_size;
+    **ball_velocity = Vec2::from(snapshot.ball_velocity);
+    for mut transform in paddles {
+        let side = usize::from(transform.translation.x > 0.);
+        transform.translation.y = snapshot.paddles[side];
+    }
+
+    let mut states = snapshot.extra_balls.iter();
+    for (entity, mut transform, mut velocity) in extra_balls {
+        match states.next() {
+            Some(state) => {
+                transform.translation = Vec2::from(state.translation).extend(0.);
+                **velocity = Vec2::from(state.velocity);
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+    for state in states {
+        let position = Vec2::from(state.translation).extend(0.);
+        commands.spawn((
+            ball_mesh.clone(),
+            ball_material.clone(),
+            Transform::from_translation(position).with_scale(Vec2::splat(BALL_SIZE).extend(1.)),
+            Ball,
+            ExtraBall,
+            Velocity(Vec2::from(state.velocity)),
+            Interpolated::new(position),
+        ));
+    }
+    if (score.0, score.1) != snapshot.score {
+        (score.0, score.1) = snapshot.score;
+        score_changed.write(ScoreChanged);
+        ball_interpolated.snap(ball_transform.translation);
+    }
+}
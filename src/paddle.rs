@@ -0,0 +1,1320 @@
+//! Paddles and everything that steers them: keyboard, gamepad and mouse input, and the AI.
+
+use crate::{
+    Phase,
+    ball::{
+        BALL_SIZE, BALL_SPEED, Ball, BallCollision, BallSize, CollisionKind, ExtraBall, Velocity,
+    },
+    pickups::{ActiveEffects, PaddleEffect},
+    score::Score,
+    serve::GameRng,
+    ui::{ControlsHint, HINT_DURATION, enter_player_name},
+    wall::{TopologyMode, WALL_OFFSET, WALL_WIDTH},
+};
+use bevy::{
+    input::gamepad::GamepadConnectionEvent,
+    prelude::*,
+    reflect::{DynamicEnum, DynamicVariant, TypeInfo, Typed, VariantInfo},
+    window::PrimaryWindow,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::FRAC_PI_4, str::FromStr};
+
+const PADDLE_SPEED: f32 = 200.;
+pub(crate) const PADDLE_OFFSET: f32 = 200.;
+pub(crate) const PADDLE_SIZE: Vec2 = Vec2::new(10., 50.);
+/// Extra paddle speed of a comeback boost at its strongest, as a fraction of `PADDLE_SPEED`.
+pub(crate) const DEFAULT_COMEBACK_BOOST: f32 = 0.3;
+pub(crate) const MAX_COMEBACK_BOOST: f32 = 1.;
+
+const AI_PREDICTION_HORIZON: f32 = 3.;
+const AI_DEADZONE: f32 = 4.;
+/// Distance from its target within which the AI slows down, so it settles instead of overshooting.
+/// Has to be at least as far as a paddle moves in one fixed step.
+const AI_SLOWDOWN_DISTANCE: f32 = 30.;
+
+const GAMEPAD_DEADZONE: f32 = 0.2;
+/// Largest stick deadzone accepted from the settings, leaving some of the stick's travel usable.
+pub(crate) const MAX_GAMEPAD_DEADZONE: f32 = 0.9;
+/// How far the cursor has to be from a mouse paddle for it to move at full speed.
+const MOUSE_FULL_SPEED_DISTANCE: f32 = 40.;
+
+/// Moving the paddles, whether a player, the AI or a replay decides where to.
+pub struct PaddlePlugin;
+
+impl Plugin for PaddlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                (read_paddle_input, ai_move_paddles).in_set(Phase::Input),
+                move_paddles.in_set(Phase::Move),
+                carry_held_ball.in_set(Phase::Carry),
+                catch_ball.in_set(Phase::Respond),
+            ),
+        );
+    }
+}
+
+/// Reading the players' devices between fixed steps, so no press is missed. Only added with a
+/// window.
+pub struct PaddleInputPlugin;
+
+impl Plugin for PaddleInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                (switch_input_sources, buffer_paddle_input).chain(),
+                apply_key_layouts
+                    .run_if(resource_changed::<KeyLayouts>)
+                    .after(enter_player_name),
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum PaddleController {
+    /// A player at this machine, using the paddle's [`InputSource`].
+    Local,
+    Ai,
+    #[cfg(feature = "net")]
+    Remote,
+}
+
+#[derive(Component, Clone)]
+#[require(PaddleIntent, CatchIntent, BufferedInput, InputSource, ActiveEffects)]
+pub(crate) struct Paddle {
+    pub(crate) up: KeyCode,
+    pub(crate) down: KeyCode,
+    pub(crate) catch: KeyCode,
+    pub(crate) controller: PaddleController,
+}
+
+/// Preset keys for a paddle, picked per side in the settings menu.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum KeyLayout {
+    Wasd,
+    Arrows,
+    Ijkl,
+    Numpad,
+}
+
+impl KeyLayout {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            KeyLayout::Wasd => KeyLayout::Arrows,
+            KeyLayout::Arrows => KeyLayout::Ijkl,
+            KeyLayout::Ijkl => KeyLayout::Numpad,
+            KeyLayout::Numpad => KeyLayout::Wasd,
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            KeyLayout::Wasd => "WASD",
+            KeyLayout::Arrows => "Arrows",
+            KeyLayout::Ijkl => "IJKL",
+            KeyLayout::Numpad => "Numpad",
+        }
+    }
+
+    /// Up, down and catch keys for the paddle on the side `x` has the sign of. Catching uses the
+    /// key pointing toward the center.
+    pub(crate) fn keys(self, x: f32) -> [KeyCode; 3] {
+        let [up, down, left, right] = match self {
+            KeyLayout::Wasd => [KeyCode::KeyW, KeyCode::KeyS, KeyCode::KeyA, KeyCode::KeyD],
+            KeyLayout::Arrows => [
+                KeyCode::ArrowUp,
+                KeyCode::ArrowDown,
+                KeyCode::ArrowLeft,
+                KeyCode::ArrowRight,
+            ],
+            KeyLayout::Ijkl => [KeyCode::KeyI, KeyCode::KeyK, KeyCode::KeyJ, KeyCode::KeyL],
+            KeyLayout::Numpad => [
+                KeyCode::Numpad8,
+                KeyCode::Numpad5,
+                KeyCode::Numpad4,
+                KeyCode::Numpad6,
+            ],
+        };
+        [up, down, if x < 0. { right } else { left }]
+    }
+}
+
+/// Up, down and catch keys picked one by one in the settings file rather than from a preset, by
+/// their names like `KeyW` or `ArrowUp`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "[String; 3]", into = "[String; 3]")]
+pub(crate) struct KeyBindings([KeyCode; 3]);
+
+impl TryFrom<[String; 3]> for KeyBindings {
+    type Error = String;
+
+    fn try_from(names: [String; 3]) -> Result<Self, Self::Error> {
+        // Reflection panics on a variant the enum doesn't have, so names are checked first.
+        let TypeInfo::Enum(info) = KeyCode::type_info() else {
+            unreachable!("KeyCode is an enum");
+        };
+        let mut keys = [KeyCode::KeyW; 3];
+        for (key, name) in keys.iter_mut().zip(names) {
+            if !matches!(info.variant(&name), Some(VariantInfo::Unit(_))) {
+                return Err(format!("unknown key {name}"));
+            }
+            *key = KeyCode::from_reflect(&DynamicEnum::new(name.clone(), DynamicVariant::Unit))
+                .ok_or_else(|| format!("unknown key {name}"))?;
+        }
+        Ok(Self(keys))
+    }
+}
+
+impl From<KeyBindings> for [String; 3] {
+    fn from(bindings: KeyBindings) -> Self {
+        bindings.0.map(|key| format!("{key:?}"))
+    }
+}
+
+#[derive(Resource, Clone, Copy)]
+pub(crate) struct KeyLayouts {
+    pub(crate) left: KeyLayout,
+    pub(crate) right: KeyLayout,
+    /// Keys from the settings file, used instead of the preset on their side.
+    pub(crate) left_custom: Option<KeyBindings>,
+    pub(crate) right_custom: Option<KeyBindings>,
+}
+
+impl KeyLayouts {
+    /// Up, down and catch keys of the paddle on the side `x` has the sign of.
+    pub(crate) fn keys(self, x: f32) -> [KeyCode; 3] {
+        let (layout, custom) = self.side(x);
+        custom.map_or_else(|| layout.keys(x), |custom| custom.0)
+    }
+
+    pub(crate) fn label(self, x: f32) -> &'static str {
+        match self.side(x) {
+            (_, Some(_)) => "Custom",
+            (layout, None) => layout.label(),
+        }
+    }
+
+    /// Moves a side on to the next preset. Custom keys go back to the preset they replaced first.
+    pub(crate) fn cycle(&mut self, x: f32) {
+        let (layout, custom) = if x < 0. {
+            (&mut self.left, &mut self.left_custom)
+        } else {
+            (&mut self.right, &mut self.right_custom)
+        };
+        if custom.take().is_none() {
+            *layout = layout.next();
+        }
+    }
+
+    fn side(self, x: f32) -> (KeyLayout, Option<KeyBindings>) {
+        if x < 0. {
+            (self.left, self.left_custom)
+        } else {
+            (self.right, self.right_custom)
+        }
+    }
+
+    /// Keys both sides are bound to, which would move both paddles at once.
+    pub(crate) fn conflicts(self) -> Vec<KeyCode> {
+        let right = self.keys(1.);
+        self.keys(-1.)
+            .into_iter()
+            .filter(|key| right.contains(key))
+            .collect()
+    }
+}
+
+impl Default for KeyLayouts {
+    fn default() -> Self {
+        Self {
+            left: KeyLayout::Wasd,
+            right: KeyLayout::Arrows,
+            left_custom: None,
+            right_custom: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum AiMode {
+    /// Follows the ball's current height.
+    Chase,
+    /// Heads for where the ball will cross the paddle, accounting for wall bounces.
+    #[default]
+    Predict,
+}
+
+impl FromStr for AiMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chase" => Ok(AiMode::Chase),
+            "predict" => Ok(AiMode::Predict),
+            _ => Err(format!("unknown AI mode '{s}', expected chase or predict")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum AiSides {
+    #[default]
+    None,
+    Left,
+    Right,
+    Both,
+}
+
+impl AiSides {
+    pub(crate) fn controls(self, x: f32) -> bool {
+        match self {
+            AiSides::None => false,
+            AiSides::Left => x < 0.,
+            AiSides::Right => x > 0.,
+            AiSides::Both => true,
+        }
+    }
+}
+
+impl FromStr for AiSides {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(AiSides::None),
+            "left" => Ok(AiSides::Left),
+            "right" => Ok(AiSides::Right),
+            "both" => Ok(AiSides::Both),
+            _ => Err(format!(
+                "unknown side '{s}', expected none, left, right or both"
+            )),
+        }
+    }
+}
+
+/// Presets for `--ai-mode` and `--ai-noise`, which still override them, plus a reaction time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum AiDifficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl AiDifficulty {
+    pub(crate) fn mode(self) -> AiMode {
+        match self {
+            AiDifficulty::Easy => AiMode::Chase,
+            AiDifficulty::Medium | AiDifficulty::Hard => AiMode::Predict,
+        }
+    }
+
+    pub(crate) fn noise(self) -> f32 {
+        match self {
+            AiDifficulty::Easy => 40.,
+            AiDifficulty::Medium => 20.,
+            AiDifficulty::Hard => 0.,
+        }
+    }
+
+    /// Seconds the AI takes to start moving once the ball heads its way.
+    pub(crate) fn reaction(self) -> f32 {
+        match self {
+            AiDifficulty::Easy => 0.35,
+            AiDifficulty::Medium => 0.15,
+            AiDifficulty::Hard => 0.,
+        }
+    }
+}
+
+impl FromStr for AiDifficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(AiDifficulty::Easy),
+            "medium" => Ok(AiDifficulty::Medium),
+            "hard" => Ok(AiDifficulty::Hard),
+            _ => Err(format!(
+                "unknown difficulty '{s}', expected easy, medium or hard"
+            )),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct AiConfig {
+    pub(crate) sides: AiSides,
+    pub(crate) mode: AiMode,
+    /// Largest error, in pixels, the AI makes when picking where to meet the ball.
+    pub(crate) noise: f32,
+    /// Seconds before the AI reacts to the ball turning toward it.
+    pub(crate) reaction: f32,
+}
+
+#[derive(Component, Clone)]
+pub(crate) struct Ai {
+    pub(crate) mode: AiMode,
+    pub(crate) noise: f32,
+    pub(crate) error: f32,
+    pub(crate) approaching: bool,
+    /// Runs from the moment the ball starts approaching; the paddle holds still until it finishes.
+    pub(crate) reaction: Timer,
+}
+
+impl Ai {
+    pub(crate) fn new(config: &AiConfig) -> Self {
+        Self {
+            mode: config.mode,
+            noise: config.noise.abs(),
+            error: 0.,
+            approaching: false,
+            reaction: Timer::from_seconds(config.reaction.max(0.), TimerMode::Once),
+        }
+    }
+}
+
+/// Movement requested for a paddle this step, from -1 (full speed down) to 1 (full speed up).
+#[derive(Component, Clone, Default, Deref, DerefMut)]
+pub(crate) struct PaddleIntent(pub(crate) f32);
+
+/// Top speed of a paddle, before any comeback boost.
+#[derive(Resource, Deref)]
+pub(crate) struct PaddleSpeed(pub(crate) f32);
+
+impl Default for PaddleSpeed {
+    fn default() -> Self {
+        Self(PADDLE_SPEED)
+    }
+}
+
+/// How a gamepad stick's tilt maps to paddle movement.
+#[derive(Resource)]
+pub(crate) struct StickResponse {
+    /// Tilt, from 0 to [`MAX_GAMEPAD_DEADZONE`], below which the stick counts as centered.
+    pub(crate) deadzone: f32,
+    pub(crate) curve: StickCurve,
+}
+
+impl StickResponse {
+    /// Rescales the tilt past the deadzone to the full -1 to 1 range, then applies the curve.
+    pub(crate) fn apply(&self, tilt: f32) -> f32 {
+        let deadzone = self.deadzone.clamp(0., MAX_GAMEPAD_DEADZONE);
+        let past_deadzone = (tilt.abs() - deadzone).max(0.) / (1. - deadzone);
+        let response = match self.curve {
+            StickCurve::Linear => past_deadzone,
+            StickCurve::Squared => past_deadzone * past_deadzone,
+        };
+        response.min(1.) * tilt.signum()
+    }
+}
+
+impl Default for StickResponse {
+    fn default() -> Self {
+        Self {
+            deadzone: GAMEPAD_DEADZONE,
+            curve: StickCurve::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum StickCurve {
+    #[default]
+    Linear,
+    /// Finer control near the center at the cost of a less direct feel.
+    Squared,
+}
+
+/// Device a local paddle is driven by. A side switches to whichever device its player last used.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum InputSource {
+    #[default]
+    Keyboard,
+    Gamepad(Entity),
+    /// Follows the cursor's height.
+    Mouse,
+}
+
+/// Paddle keys seen since the last fixed step, so a tap that starts and ends between two steps
+/// still moves the paddle.
+#[derive(Component, Clone, Default)]
+pub(crate) struct BufferedInput {
+    pub(crate) up: bool,
+    pub(crate) down: bool,
+    pub(crate) catch: bool,
+    /// Latest cursor height, kept across steps.
+    pub(crate) mouse_y: Option<f32>,
+}
+
+/// Whether the paddle wants to catch the ball, see [`CatchConfig`].
+#[derive(Component, Clone, Default, Deref, DerefMut)]
+pub(crate) struct CatchIntent(pub(crate) bool);
+
+/// Variant where a paddle holding its catch key grabs the ball and throws it on release, angled by
+/// the paddle's movement and sped up by how long it was held. Catch input isn't sent over the
+/// network or recorded in replays, so it is only meant for local matches.
+#[derive(Resource)]
+pub(crate) struct CatchConfig {
+    pub(crate) enabled: bool,
+    pub(crate) max_hold: f32,
+    pub(crate) max_angle: f32,
+    /// Extra throw speed, as a fraction of [`BALL_SPEED`], per second held.
+    pub(crate) power_per_second: f32,
+}
+
+impl Default for CatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_hold: 1.5,
+            max_angle: FRAC_PI_4,
+            power_per_second: 0.5,
+        }
+    }
+}
+
+/// The ball while caught by `paddle`, riding along at `offset` from its center.
+#[derive(Component, Clone)]
+pub(crate) struct Held {
+    pub(crate) paddle: Entity,
+    pub(crate) offset: f32,
+    pub(crate) timer: Timer,
+    /// Caught by a [`PaddleEffect::Sticky`] paddle, so held for the whole hold whatever the catch
+    /// key does.
+    pub(crate) sticky: bool,
+}
+
+/// Rubber band for lopsided games: a player behind by at least `margin` points gets a faster
+/// paddle, up to `max` extra speed at twice the margin.
+#[derive(Resource)]
+pub(crate) struct ComebackBoost {
+    /// `None` turns it off.
+    pub(crate) margin: Option<u32>,
+    pub(crate) max: f32,
+}
+
+impl ComebackBoost {
+    fn multiplier(&self, deficit: u32) -> f32 {
+        match self.margin {
+            Some(margin) if deficit >= margin => {
+                let behind = (deficit - margin + 1) as f32 / margin as f32;
+                1. + self.max * behind.min(1.)
+            }
+            _ => 1.,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct PaddleConfig {
+    /// Distance of each paddle's center from the center of the arena.
+    pub(crate) offset: f32,
+    /// Left and right paddle lengths. A longer paddle is easier to play, so uneven lengths work as
+    /// a handicap.
+    pub(crate) heights: [f32; 2],
+}
+
+impl PaddleConfig {
+    pub(crate) fn with_offset(offset: f32) -> Self {
+        Self {
+            offset,
+            heights: [PADDLE_SIZE.y; 2],
+        }
+    }
+
+    pub(crate) fn size(&self, x: f32) -> Vec2 {
+        Vec2::new(PADDLE_SIZE.x, self.heights[usize::from(x > 0.)])
+    }
+
+    pub(crate) fn validate_height(height: f32) -> Result<f32, String> {
+        let min = BALL_SIZE;
+        let max = 2. * WALL_OFFSET - WALL_WIDTH;
+        if (min..=max).contains(&height) {
+            Ok(height)
+        } else {
+            Err(format!("height {height} must be between {min} and {max}"))
+        }
+    }
+
+    pub(crate) fn validate(self) -> Result<Self, String> {
+        let min = BALL_SIZE;
+        let max = WALL_OFFSET - WALL_WIDTH / 2. - PADDLE_SIZE.x / 2.;
+        if (min..max).contains(&self.offset) {
+            Ok(self)
+        } else {
+            Err(format!(
+                "offset {} must be between {min} and {max}",
+                self.offset
+            ))
+        }
+    }
+}
+
+impl Default for PaddleConfig {
+    fn default() -> Self {
+        Self::with_offset(PADDLE_OFFSET)
+    }
+}
+
+/// Hands a side to whichever device its player just used. Gamepads go to the first side not
+/// already on one as soon as they connect or are first used, the mouse to the side of the arena it
+/// clicked in. A side whose gamepad disconnects falls back to the keyboard.
+fn switch_input_sources(
+    paddles: Query<(&Transform, &Paddle, &mut InputSource)>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    stick: Res<StickResponse>,
+    mut connections: EventReader<GamepadConnectionEvent>,
+    mut hint: ResMut<ControlsHint>,
+) {
+    let mut paddles: Vec<_> = paddles
+        .into_iter()
+        .filter(|(_, paddle, _)| paddle.controller == PaddleController::Local)
+        .collect();
+    paddles.sort_by(|(a, ..), (b, ..)| a.translation.x.total_cmp(&b.translation.x));
+    let mut switched = false;
+    let mut switch = |source: &mut InputSource, to: InputSource| {
+        if *source != to {
+            *source = to;
+            switched = true;
+        }
+    };
+
+    let mut connected = Vec::new();
+    for connection in connections.read() {
+        if connection.connected() {
+            connected.push(connection.gamepad);
+            continue;
+        }
+        for (.., source) in &mut paddles {
+            if **source == InputSource::Gamepad(connection.gamepad) {
+                switch(source, InputSource::Keyboard);
+            }
+        }
+    }
+
+    for (_, paddle, source) in &mut paddles {
+        if keyboard.any_just_pressed([paddle.up, paddle.down, paddle.catch]) {
+            switch(source, InputSource::Keyboard);
+        }
+    }
+
+    for (gamepad, state) in &gamepads {
+        let used = connected.contains(&gamepad)
+            || state.get_just_pressed().next().is_some()
+            || stick.apply(state.left_stick().y) != 0.;
+        let assigned = paddles
+            .iter()
+            .any(|(.., source)| **source == InputSource::Gamepad(gamepad));
+        if !used || assigned {
+            continue;
+        }
+        if let Some((.., source)) = paddles
+            .iter_mut()
+            .find(|(.., source)| !matches!(**source, InputSource::Gamepad(_)))
+        {
+            switch(source, InputSource::Gamepad(gamepad));
+        }
+    }
+
+    if let Some(window) = window
+        && let Some(cursor) = window
+            .cursor_position()
+            .filter(|_| mouse.just_pressed(MouseButton::Left))
+    {
+        let on_left = cursor.x < window.width() / 2.;
+        if let Some((.., source)) = paddles
+            .iter_mut()
+            .find(|(transform, ..)| (transform.translation.x < 0.) == on_left)
+        {
+            switch(source, InputSource::Mouse);
+        }
+    }
+
+    if switched {
+        hint.shown = true;
+        hint.auto_hide = Some(Timer::from_seconds(HINT_DURATION, TimerMode::Once));
+    }
+}
+
+fn buffer_paddle_input(
+    query: Query<(&Paddle, &InputSource, &mut BufferedInput)>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    camera: Option<Single<(&Camera, &GlobalTransform)>>,
+) {
+    let seen = |key| keyboard.pressed(key) || keyboard.just_pressed(key);
+    let cursor = window
+        .zip(camera)
+        .and_then(|(window, camera)| {
+            let (camera, transform) = *camera;
+            camera
+                .viewport_to_world_2d(transform, window.cursor_position()?)
+                .ok()
+        })
+        .map(|cursor| cursor.y);
+    for (paddle, source, mut buffered) in query {
+        match source {
+            InputSource::Keyboard => {
+                buffered.up |= seen(paddle.up);
+                buffered.down |= seen(paddle.down);
+                buffered.catch |= seen(paddle.catch);
+            }
+            InputSource::Mouse => {
+                buffered.catch |=
+                    mouse.pressed(MouseButton::Left) || mouse.just_pressed(MouseButton::Left);
+                if cursor.is_some() {
+                    buffered.mouse_y = cursor;
+                }
+            }
+            // Sticks are analog, so they are simply sampled at each fixed step.
+            InputSource::Gamepad(_) => {}
+        }
+    }
+}
+
+pub(crate) fn read_paddle_input(
+    query: Query<(
+        &Transform,
+        &Paddle,
+        &InputSource,
+        &mut BufferedInput,
+        &mut PaddleIntent,
+        &mut CatchIntent,
+    )>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    stick: Res<StickResponse>,
+    catch: Res<CatchConfig>,
+) {
+    for (transform, paddle, source, mut buffered, mut intent, mut catching) in query {
+        let mouse_y = buffered.mouse_y;
+        let buffered = std::mem::replace(
+            &mut *buffered,
+            BufferedInput {
+                mouse_y,
+                ..default()
+            },
+        );
+        if paddle.controller != PaddleController::Local {
+            continue;
+        }
+        let (direction, wants_catch) = match *source {
+            InputSource::Keyboard => {
+                let mut direction = 0.;
+                if buffered.up || keyboard.pressed(paddle.up) {
+                    direction += 1.;
+                }
+                if buffered.down || keyboard.pressed(paddle.down) {
+                    direction -= 1.;
+                }
+                (direction, buffered.catch || keyboard.pressed(paddle.catch))
+            }
+            InputSource::Gamepad(gamepad) => match gamepads.get(gamepad) {
+                Ok(gamepad) => {
+                    let mut direction = stick.apply(gamepad.left_stick().y);
+                    if gamepad.pressed(GamepadButton::DPadUp) {
+                        direction += 1.;
+                    }
+                    if gamepad.pressed(GamepadButton::DPadDown) {
+                        direction -= 1.;
+                    }
+                    (direction, gamepad.pressed(GamepadButton::South))
+                }
+                Err(_) => (0., false),
+            },
+            InputSource::Mouse => {
+                let direction = mouse_y.map_or(0., |y| {
+                    (y - transform.translation.y) / MOUSE_FULL_SPEED_DISTANCE
+                });
+                (direction, buffered.catch)
+            }
+        };
+        **intent = direction.clamp(-1., 1.);
+        **catching = catch.enabled && wants_catch;
+    }
+}
+
+/// Folds a height that overshot `-bound..=bound` back into range, as repeated wall bounces would.
+fn reflect_into_bounds(y: f32, bound: f32) -> f32 {
+    if bound <= 0. {
+        return 0.;
+    }
+    let period = 4. * bound;
+    let phase = (y + bound).rem_euclid(period);
+    if phase <= 2. * bound {
+        phase - bound
+    } else {
+        3. * bound - phase
+    }
+}
+
+/// Height at which a ball at `position` moving at `velocity` reaches `target_x`, with its center
+/// kept within `-bound..=bound` vertically. `None` if the ball is moving away or won't get there
+/// within `horizon` seconds.
+fn predict_intercept_y(
+    position: Vec2,
+    velocity: Vec2,
+    target_x: f32,
+    bound: f32,
+    topology: TopologyMode,
+    horizon: f32,
+) -> Option<f32> {
+    if velocity.x == 0. {
+        return None;
+    }
+    let time = (target_x - position.x) / velocity.x;
+    if !(0. ..=horizon).contains(&time) {
+        return None;
+    }
+    let y = position.y + velocity.y * time;
+    Some(match topology {
+        TopologyMode::Bounce => reflect_into_bounds(y, bound),
+        TopologyMode::Wrap => (y + WALL_OFFSET).rem_euclid(2. * WALL_OFFSET) - WALL_OFFSET,
+    })
+}
+
+/// Corners of the path a ball at `position` moving at `velocity` takes to `target_x`: where it
+/// starts, each bounce off the `-bound..=bound` band it is kept in, and where it gets to `target_x`
+/// or makes its last allowed bounce. Pass an infinite `bound` for an arena without walls.
+pub(crate) fn predict_path(
+    mut position: Vec2,
+    mut velocity: Vec2,
+    target_x: f32,
+    bound: f32,
+    max_bounces: usize,
+) -> Vec<Vec2> {
+    let mut path = vec![position];
+    if velocity.x == 0. || (target_x - position.x) * velocity.x < 0. {
+        return path;
+    }
+    for _ in 0..=max_bounces {
+        let to_target = (target_x - position.x) / velocity.x;
+        let to_wall = if velocity.y == 0. {
+            f32::INFINITY
+        } else {
+            ((bound * velocity.y.signum() - position.y) / velocity.y).max(0.)
+        };
+        if to_target <= to_wall {
+            path.push(position + velocity * to_target);
+            break;
+        }
+        position += velocity * to_wall;
+        velocity.y = -velocity.y;
+        path.push(position);
+    }
+    path
+}
+
+/// Moves AI paddles toward whichever ball reaches them first, or the first ball while none is
+/// coming their way.
+pub(crate) fn ai_move_paddles(
+    paddles: Query<(&Transform, &mut Ai, &mut PaddleIntent, &ActiveEffects)>,
+    balls: Query<(&Transform, &Velocity, &BallSize, Has<ExtraBall>), With<Ball>>,
+    topology: Res<TopologyMode>,
+    mut rng: ResMut<GameRng>,
+    time: Res<Time>,
+) {
+    for (transform, mut ai, mut intent, effects) in paddles {
+        if effects.frozen() {
+            continue;
+        }
+        let paddle_x = transform.translation.x;
+        let arrival = |(ball_transform, ball_velocity, ..): &(&Transform, &Velocity, _, _)| {
+            (paddle_x - ball_transform.translation.x) / ball_velocity.x
+        };
+        let incoming = balls
+            .iter()
+            .filter(|(ball_transform, ball_velocity, ..)| {
+                (paddle_x - ball_transform.translation.x) * ball_velocity.x > 0.
+            })
+            .min_by(|a, b| arrival(a).total_cmp(&arrival(b)));
+        let Some((ball_transform, ball_velocity, ball_size, _)) =
+            incoming.or_else(|| balls.iter().find(|(.., extra)| !extra))
+        else {
+            continue;
+        };
+        let ball_position = ball_transform.translation.xy();
+        let bound = WALL_OFFSET - WALL_WIDTH / 2. - **ball_size / 2.;
+        let approaching = incoming.is_some();
+        if approaching && !ai.approaching {
+            ai.error = if ai.noise > 0. {
+                rng.random_range(-ai.noise..=ai.noise)
+            } else {
+                0.
+            };
+            ai.reaction.reset();
+        }
+        ai.approaching = approaching;
+        if approaching && !ai.reaction.tick(time.delta()).finished() {
+            **intent = 0.;
+            continue;
+        }
+
+        let target_y = match ai.mode {
+            AiMode::Chase => ball_position.y,
+            AiMode::Predict => predict_intercept_y(
+                ball_position,
+                **ball_velocity,
+                paddle_x,
+                bound,
+                *topology,
+                AI_PREDICTION_HORIZON,
+            )
+            .unwrap_or(0.),
+        } + ai.error;
+
+        let distance = target_y - transform.translation.y;
+        **intent = if distance.abs() < AI_DEADZONE {
+            0.
+        } else {
+            (distance / AI_SLOWDOWN_DISTANCE).clamp(-1., 1.)
+        };
+    }
+}
+
+pub(crate) fn paddle_side(transform: &Transform) -> usize {
+    usize::from(transform.translation.x > 0.)
+}
+
+fn move_paddles(
+    query: Query<(&mut Transform, &PaddleIntent, &ActiveEffects)>,
+    speed: Res<PaddleSpeed>,
+    boost: Res<ComebackBoost>,
+    scores: Res<Score>,
+    time: Res<Time>,
+) {
+    for (mut transform, intent, effects) in query {
+        if effects.frozen() {
+            continue;
+        }
+        let (own, other) = match paddle_side(&transform) {
+            0 => (scores.0, scores.1),
+            _ => (scores.1, scores.0),
+        };
+        let speed = **speed * boost.multiplier(other.saturating_sub(own));
+        let bound = WALL_OFFSET - WALL_WIDTH / 2. - transform.scale.y / 2.;
+        transform.translation.y = (transform.translation.y
+            + speed * intent.clamp(-1., 1.) * time.delta_secs())
+        .clamp(-bound, bound);
+    }
+}
+
+fn catch_ball(
+    mut commands: Commands,
+    mut collisions: EventReader<BallCollision>,
+    mut balls: Query<(&Transform, &mut Velocity), (With<Ball>, Without<Held>)>,
+    mut paddles: Query<
+        (Entity, &Transform, &CatchIntent, &mut ActiveEffects),
+        (With<Paddle>, Without<Ball>),
+    >,
+    config: Res<CatchConfig>,
+) {
+    for collision in collisions.read() {
+        if collision.kind != CollisionKind::Paddle {
+            continue;
+        }
+        let Ok((transform, mut velocity)) = balls.get_mut(collision.ball) else {
+            continue;
+        };
+        let Some((paddle, paddle_transform, _, mut effects)) = paddles
+            .iter_mut()
+            .filter(|(_, _, catching, effects)| ***catching || effects.sticky())
+            .min_by(|(_, a, ..), (_, b, ..)| {
+                let distance = |t: &Transform| t.translation.xy().distance(collision.position);
+                distance(a).total_cmp(&distance(b))
+            })
+        else {
+            continue;
+        };
+        if paddle_transform
+            .translation
+            .xy()
+            .distance(collision.position)
+            > paddle_transform.scale.xy().length()
+        {
+            continue;
+        }
+        let half_height = paddle_transform.scale.y / 2.;
+        let sticky = effects.sticky();
+        commands.entity(collision.ball).insert(Held {
+            paddle,
+            offset: (transform.translation.y - paddle_transform.translation.y)
+                .clamp(-half_height, half_height),
+            timer: Timer::from_seconds(config.max_hold, TimerMode::Once),
+            sticky,
+        });
+        effects.end(PaddleEffect::Sticky);
+        **velocity = Vec2::ZERO;
+    }
+}
+
+/// Keeps a caught ball against its paddle's face, then throws it once the catch key is released or
+/// the hold runs out. A sticky catch always lasts the whole hold.
+fn carry_held_ball(
+    mut commands: Commands,
+    balls: Query<(Entity, &mut Transform, &mut Velocity, &mut Held, &BallSize), With<Ball>>,
+    paddles: Query<(&Transform, &PaddleIntent, &CatchIntent), Without<Ball>>,
+    config: Res<CatchConfig>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut velocity, mut held, size) in balls {
+        let Ok((paddle_transform, intent, catching)) = paddles.get(held.paddle) else {
+            commands.entity(entity).remove::<Held>();
+            continue;
+        };
+        let toward_center = -paddle_transform.translation.x.signum();
+        transform.translation.x =
+            paddle_transform.translation.x + toward_center * (PADDLE_SIZE.x + **size) / 2.;
+        transform.translation.y = paddle_transform.translation.y + held.offset;
+        if (**catching || held.sticky) && !held.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        let angle = intent.clamp(-1., 1.) * config.max_angle;
+        let power = 1. + held.timer.elapsed_secs() * config.power_per_second;
+        **velocity = Vec2::new(angle.cos() * toward_center, angle.sin()) * BALL_SPEED * power;
+        commands.entity(entity).remove::<Held>();
+    }
+}
+
+pub(crate) fn key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::ArrowUp => "Up".to_string(),
+        KeyCode::ArrowDown => "Down".to_string(),
+        KeyCode::ArrowLeft => "Left".to_string(),
+        KeyCode::ArrowRight => "Right".to_string(),
+        key => {
+            let label = format!("{key:?}");
+            label
+                .strip_prefix("Key")
+                .or_else(|| label.strip_prefix("Digit"))
+                .unwrap_or(&label)
+                .to_string()
+        }
+    }
+}
+
+pub(crate) fn apply_key_layouts(
+    paddles: Query<(&Transform, &mut Paddle)>,
+    layouts: Res<KeyLayouts>,
+) {
+    let conflicts = layouts.conflicts();
+    if !conflicts.is_empty() {
+        let keys: Vec<_> = conflicts.into_iter().map(key_label).collect();
+        warn!("Both paddles are bound to {}", keys.join(", "));
+    }
+    for (transform, mut paddle) in paddles {
+        let x = transform.translation.x;
+        [paddle.up, paddle.down, paddle.catch] = layouts.keys(x);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, STEP};
+
+    /// A left paddle with WASD keys, under the fixed steps that read and apply its input.
+    fn paddle_app(held: &[KeyCode]) -> (App, Entity) {
+        let mut app = testing::app();
+        let mut keyboard = ButtonInput::<KeyCode>::default();
+        for &key in held {
+            keyboard.press(key);
+        }
+        app.insert_resource(keyboard)
+            .insert_resource(Score(0, 0))
+            .insert_resource(ComebackBoost {
+                margin: None,
+                max: DEFAULT_COMEBACK_BOOST,
+            })
+            .init_resource::<PaddleSpeed>()
+            .init_resource::<StickResponse>()
+            .init_resource::<CatchConfig>()
+            .add_systems(FixedUpdate, (read_paddle_input, move_paddles).chain());
+        let [up, down, catch] = KeyLayout::Wasd.keys(-PADDLE_OFFSET);
+        let paddle = app
+            .world_mut()
+            .spawn((
+                Transform::from_xyz(-PADDLE_OFFSET, 0., 0.).with_scale(PADDLE_SIZE.extend(1.)),
+                Paddle {
+                    up,
+                    down,
+                    catch,
+                    controller: PaddleController::Local,
+                },
+            ))
+            .id();
+        (app, paddle)
+    }
+
+    fn height(app: &App, paddle: Entity) -> f32 {
+        app.world().get::<Transform>(paddle).unwrap().translation.y
+    }
+
+    #[test]
+    fn held_up_moves_at_paddle_speed() {
+        let (mut app, paddle) = paddle_app(&[KeyCode::KeyW]);
+        app.update();
+        assert_eq!(height(&app, paddle), PADDLE_SPEED * STEP);
+        app.update();
+        assert_eq!(height(&app, paddle), 2. * PADDLE_SPEED * STEP);
+    }
+
+    /// Holds up for longer than any paddle needs to cross the arena.
+    fn top_height(length: f32) -> f32 {
+        let (mut app, paddle) = paddle_app(&[KeyCode::KeyW]);
+        app.world_mut()
+            .get_mut::<Transform>(paddle)
+            .unwrap()
+            .scale
+            .y = length;
+        for _ in 0..(2. * WALL_OFFSET / (PADDLE_SPEED * STEP)) as usize + 1 {
+            app.update();
+        }
+        height(&app, paddle)
+    }
+
+    #[test]
+    fn paddles_stop_at_the_wall_by_their_length() {
+        let edge = WALL_OFFSET - WALL_WIDTH / 2.;
+        assert_eq!(top_height(PADDLE_SIZE.y), edge - PADDLE_SIZE.y / 2.);
+        assert_eq!(top_height(120.), edge - 60.);
+        assert_eq!(top_height(BALL_SIZE), edge - BALL_SIZE / 2.);
+    }
+
+    #[test]
+    fn held_up_and_down_cancel_out() {
+        let (mut app, paddle) = paddle_app(&[KeyCode::KeyW, KeyCode::KeyS]);
+        app.update();
+        assert_eq!(height(&app, paddle), 0.);
+    }
+
+    #[test]
+    fn comeback_boost_only_speeds_up_a_player_behind_by_the_margin() {
+        let step_speed = |score: Score| {
+            let (mut app, paddle) = paddle_app(&[KeyCode::KeyW]);
+            app.insert_resource(score).insert_resource(ComebackBoost {
+                margin: Some(3),
+                max: 0.3,
+            });
+            app.update();
+            height(&app, paddle) / (PADDLE_SPEED * STEP)
+        };
+        assert_eq!(step_speed(Score(0, 2)), 1.);
+        assert_eq!(step_speed(Score(5, 0)), 1.);
+        assert!((step_speed(Score(0, 3)) - 1.1).abs() < 1e-5);
+        assert!((step_speed(Score(1, 6)) - 1.3).abs() < 1e-5);
+        assert!((step_speed(Score(0, 10)) - 1.3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn a_tap_between_steps_still_moves_the_paddle() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let (mut app, paddle) = paddle_app(&[]);
+        app.init_resource::<ButtonInput<MouseButton>>();
+        let set_w = |app: &mut App, pressed: bool| {
+            let mut keyboard = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            if pressed {
+                keyboard.press(KeyCode::KeyW);
+            } else {
+                keyboard.release(KeyCode::KeyW);
+            }
+        };
+        set_w(&mut app, true);
+        app.world_mut()
+            .run_system_once(buffer_paddle_input)
+            .unwrap();
+        set_w(&mut app, false);
+        app.update();
+        assert_eq!(height(&app, paddle), PADDLE_SPEED * STEP);
+
+        // The tap only counts once.
+        app.update();
+        assert_eq!(height(&app, paddle), PADDLE_SPEED * STEP);
+    }
+
+    #[test]
+    fn key_layouts_map_each_side_and_flag_shared_keys() {
+        let mut layouts = KeyLayouts {
+            left: KeyLayout::Ijkl,
+            right: KeyLayout::Numpad,
+            left_custom: None,
+            right_custom: None,
+        };
+        assert_eq!(
+            layouts.keys(-1.),
+            [KeyCode::KeyI, KeyCode::KeyK, KeyCode::KeyL]
+        );
+        assert_eq!(
+            layouts.keys(1.),
+            [KeyCode::Numpad8, KeyCode::Numpad5, KeyCode::Numpad4]
+        );
+        assert!(layouts.conflicts().is_empty());
+
+        let custom = KeyBindings::try_from(["KeyW", "KeyK", "Space"].map(String::from)).unwrap();
+        layouts.right_custom = Some(custom);
+        assert_eq!(layouts.label(1.), "Custom");
+        assert_eq!(layouts.conflicts(), [KeyCode::KeyK]);
+        for name in ["Nope", "Unidentified"] {
+            let names = ["KeyW", name, "Space"].map(String::from);
+            assert_eq!(
+                KeyBindings::try_from(names),
+                Err(format!("unknown key {name}"))
+            );
+        }
+
+        // Cycling drops the custom keys before moving on from the preset.
+        layouts.cycle(1.);
+        assert_eq!(layouts.label(1.), "Numpad");
+        layouts.cycle(1.);
+        assert_eq!(layouts.label(1.), "WASD");
+    }
+
+    #[test]
+    fn stick_tilt_is_rescaled_past_the_deadzone() {
+        let linear = StickResponse {
+            deadzone: 0.2,
+            curve: StickCurve::Linear,
+        };
+        assert_eq!(linear.apply(0.15), 0.);
+        assert_eq!(linear.apply(-0.2), 0.);
+        assert!((linear.apply(0.6) - 0.5).abs() < 1e-6);
+        assert!((linear.apply(-0.6) + 0.5).abs() < 1e-6);
+        assert_eq!(linear.apply(1.), 1.);
+
+        let squared = StickResponse {
+            curve: StickCurve::Squared,
+            ..linear
+        };
+        assert!((squared.apply(0.6) - 0.25).abs() < 1e-6);
+        assert_eq!(squared.apply(-1.), -1.);
+
+        // A deadzone past the cap would swallow the whole stick, or divide by zero.
+        let capped = StickResponse {
+            deadzone: 1.,
+            curve: StickCurve::Linear,
+        };
+        assert_eq!(capped.apply(1.), 1.);
+        assert!((capped.apply(0.95) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn intercepts_straight_and_off_one_bounce() {
+        let predict = |position: Vec2, velocity: Vec2| {
+            predict_intercept_y(position, velocity, 200., 100., TopologyMode::Bounce, 10.)
+        };
+        assert_eq!(predict(Vec2::ZERO, Vec2::new(100., 25.)), Some(50.));
+        // Up 150 would end 50 past the top, so the bounce brings it back down to 50.
+        assert_eq!(predict(Vec2::ZERO, Vec2::new(100., 75.)), Some(50.));
+        assert_eq!(predict(Vec2::ZERO, Vec2::new(100., -75.)), Some(-50.));
+        // Moving away, or too far off to bother predicting.
+        assert_eq!(predict(Vec2::ZERO, Vec2::new(-100., 0.)), None);
+        assert_eq!(predict(Vec2::ZERO, Vec2::new(10., 0.)), None);
+    }
+
+    #[test]
+    fn ai_slows_down_into_its_target_without_overshooting() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let mut app = testing::app();
+        app.insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .insert_resource(Score(0, 0))
+            .insert_resource(ComebackBoost {
+                margin: None,
+                max: DEFAULT_COMEBACK_BOOST,
+            })
+            .init_resource::<PaddleSpeed>()
+            .init_resource::<TopologyMode>()
+            .add_systems(FixedUpdate, (ai_move_paddles, move_paddles).chain());
+        let config = AiConfig {
+            sides: AiSides::Right,
+            mode: AiMode::Chase,
+            noise: 0.,
+            reaction: 0.,
+        };
+        let paddle = app
+            .world_mut()
+            .spawn((
+                Transform::from_xyz(PADDLE_OFFSET, 0., 0.),
+                Ai::new(&config),
+                PaddleIntent::default(),
+                ActiveEffects::default(),
+            ))
+            .id();
+        let target = 150.;
+        app.world_mut().spawn((
+            Transform::from_xyz(0., target, 0.),
+            Ball,
+            Velocity(Vec2::ZERO),
+        ));
+
+        let mut last_step = f32::INFINITY;
+        let mut previous = 0.;
+        for _ in 0..128 {
+            app.update();
+            let y = height(&app, paddle);
+            assert!(y <= target, "overshot to {y}");
+            let step = y - previous;
+            assert!(step >= 0.);
+            if target - previous < AI_SLOWDOWN_DISTANCE {
+                assert!(step <= last_step, "sped up close to the target");
+            }
+            (last_step, previous) = (step, y);
+        }
+        assert!(target - previous < AI_DEADZONE);
+    }
+
+    #[test]
+    fn ai_follows_the_ball_coming_its_way() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let mut app = testing::app();
+        app.insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .init_resource::<TopologyMode>()
+            .add_systems(FixedUpdate, ai_move_paddles);
+        let config = AiConfig {
+            sides: AiSides::Right,
+            mode: AiMode::Chase,
+            noise: 0.,
+            reaction: 0.,
+        };
+        let paddle = app
+            .world_mut()
+            .spawn((
+                Transform::from_xyz(PADDLE_OFFSET, 0., 0.),
+                Ai::new(&config),
+                PaddleIntent::default(),
+                ActiveEffects::default(),
+            ))
+            .id();
+        let mut spawn_ball = |y: f32, velocity_x: f32| {
+            app.world_mut()
+                .spawn((
+                    Transform::from_xyz(0., y, 0.),
+                    Ball,
+                    Velocity(Vec2::new(velocity_x, 0.)),
+                ))
+                .id()
+        };
+        spawn_ball(100., -BALL_SPEED);
+        let extra = spawn_ball(-100., BALL_SPEED);
+        app.world_mut().entity_mut(extra).insert(ExtraBall);
+        app.update();
+        assert_eq!(**app.world().get::<PaddleIntent>(paddle).unwrap(), -1.);
+
+        // With nothing coming its way, it goes back to the first ball.
+        app.world_mut().get_mut::<Velocity>(extra).unwrap().x = -BALL_SPEED;
+        app.update();
+        assert_eq!(**app.world().get::<PaddleIntent>(paddle).unwrap(), 1.);
+    }
+}
@@ -0,0 +1,584 @@
+//! Pickups, and the other random events that change the game for a while: ball size modifiers
+//! and speed bursts.
+
+use crate::{
+    Phase,
+    ball::{
+        BALL_SIZE, Ball, BallCollision, BallSize, Collider, CollisionKind, ExtraBall, Velocity,
+        resize_ball,
+    },
+    interpolation::Interpolated,
+    paddle::{Paddle, PaddleConfig},
+    serve::GameRng,
+    wall::{WALL_LENGTH, WALL_OFFSET, WALL_WIDTH},
+};
+use bevy::{
+    math::bounding::{BoundingCircle, IntersectsVolume},
+    prelude::*,
+};
+use rand::Rng;
+use std::{f32::consts::PI, mem, time::Duration};
+
+const PICKUP_SIZE: f32 = 16.;
+const PICKUP_LIFETIME: f32 = 8.;
+const FROZEN_COLOR: Color = Color::srgb(0.4, 0.7, 1.);
+const SHIELD_COLOR: Color = Color::srgba(0.3, 1., 0.6, 0.6);
+const SHIELD_INSET: f32 = WALL_WIDTH * 1.5;
+const SHIELD_DURATION: f32 = 10.;
+pub(crate) const DOUBLE_POINTS_COLOR: Color = Color::srgb(1., 0.85, 0.2);
+const DOUBLE_POINTS_DURATION: f32 = 12.;
+const GROW_COLOR: Color = Color::srgb(0.5, 1., 0.4);
+const SHRINK_COLOR: Color = Color::srgb(1., 0.4, 0.8);
+/// Paddle length while grown or shrunk, as a factor of its usual length.
+const GROWN_FACTOR: f32 = 1.5;
+const SHRUNK_FACTOR: f32 = 0.6;
+const RESIZE_DURATION: f32 = 8.;
+const SLOW_COLOR: Color = Color::srgb(0.6, 0.5, 1.);
+/// Fraction of its speed a ball slowed by a pickup keeps.
+const SLOW_FACTOR: f32 = 0.5;
+const STICKY_COLOR: Color = Color::srgb(1., 0.6, 0.2);
+const MULTIBALL_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+/// Pickups, ball size modifiers and speed bursts, each off unless its flag is given.
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                spawn_size_modifiers
+                    .before(resize_ball)
+                    .in_set(Phase::Resize),
+                (start_speed_bursts, end_speed_bursts).in_set(Phase::Burst),
+                (
+                    spawn_pickups,
+                    collect_pickups,
+                    expire_paddle_effects.after(collect_pickups),
+                    expire_shields,
+                    expire_double_points,
+                )
+                    .in_set(Phase::Pickups),
+            ),
+        );
+    }
+}
+
+/// Temporarily scales the ball, and with it the collision radius, by `scale`.
+#[derive(Component, Clone)]
+pub(crate) struct BallSizeModifier {
+    pub(crate) scale: f32,
+    pub(crate) timer: Timer,
+}
+
+#[derive(Resource, Clone)]
+pub(crate) struct BallSizeModifiers {
+    pub(crate) enabled: bool,
+    pub(crate) every: Timer,
+    pub(crate) duration: f32,
+    pub(crate) big: f32,
+    pub(crate) small: f32,
+}
+
+impl Default for BallSizeModifiers {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            every: Timer::from_seconds(12., TimerMode::Repeating),
+            duration: 5.,
+            big: 2.,
+            small: 0.5,
+        }
+    }
+}
+
+/// Random events where the ball speeds up for a while, announced by a banner shortly before.
+#[derive(Resource, Clone)]
+pub(crate) struct SpeedBursts {
+    pub(crate) enabled: bool,
+    /// Average time between bursts. Each gap is randomized by up to half of this either way.
+    pub(crate) every: f32,
+    pub(crate) next: Timer,
+    pub(crate) warning: f32,
+    pub(crate) duration: f32,
+    pub(crate) scale: f32,
+}
+
+impl Default for SpeedBursts {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            every: 20.,
+            next: Timer::from_seconds(20., TimerMode::Once),
+            warning: 1.5,
+            duration: 4.,
+            scale: 1.6,
+        }
+    }
+}
+
+/// Scales how far the ball moves each step, leaving its velocity alone so paddle hits and the
+/// rest of the physics behave as usual.
+#[derive(Component, Clone)]
+pub(crate) struct SpeedBurst {
+    pub(crate) warning: Timer,
+    timer: Timer,
+    scale: f32,
+}
+
+impl SpeedBurst {
+    pub(crate) fn scale(&self) -> f32 {
+        if self.warning.finished() {
+            self.scale
+        } else {
+            1.
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PickupKind {
+    /// Freezes the paddle of the side the ball is heading toward.
+    Freeze,
+    /// Guards the goal of the side the ball is heading away from.
+    Shield,
+    /// Makes the next point of the side the ball is heading away from count twice.
+    DoublePoints,
+    /// Lengthens the paddle of the side the ball is heading away from for a while.
+    Grow,
+    /// Shortens the paddle of the side the ball is heading toward for a while.
+    Shrink,
+    /// Slows the ball down until a paddle next returns it.
+    Slow,
+    /// Makes the paddle of the side the ball is heading toward catch the ball when it next hits
+    /// it, throwing it once the hold runs out.
+    Sticky,
+    /// Splits off another ball heading the same way, mirrored vertically, that leaves play at the
+    /// first goal it reaches.
+    Multiball,
+}
+
+impl PickupKind {
+    const ALL: [PickupKind; 8] = [
+        PickupKind::Freeze,
+        PickupKind::Shield,
+        PickupKind::DoublePoints,
+        PickupKind::Grow,
+        PickupKind::Shrink,
+        PickupKind::Slow,
+        PickupKind::Sticky,
+        PickupKind::Multiball,
+    ];
+
+    fn color(self) -> Color {
+        match self {
+            PickupKind::Freeze => FROZEN_COLOR,
+            PickupKind::Shield => SHIELD_COLOR,
+            PickupKind::DoublePoints => DOUBLE_POINTS_COLOR,
+            PickupKind::Grow => GROW_COLOR,
+            PickupKind::Shrink => SHRINK_COLOR,
+            PickupKind::Slow => SLOW_COLOR,
+            PickupKind::Sticky => STICKY_COLOR,
+            PickupKind::Multiball => MULTIBALL_COLOR,
+        }
+    }
+}
+
+/// Bounces the ball back from in front of a goal, disappearing once used up or timed out.
+#[derive(Component, Clone)]
+pub(crate) struct Shield {
+    pub(crate) uses: u32,
+    lifetime: Timer,
+}
+
+/// Collected when the ball passes over it, despawning on its own after a while.
+#[derive(Component, Clone)]
+pub(crate) struct Pickup {
+    kind: PickupKind,
+    lifetime: Timer,
+}
+
+#[derive(Resource, Clone)]
+pub(crate) struct PickupConfig {
+    pub(crate) enabled: bool,
+    pub(crate) every: Timer,
+    pub(crate) freeze_duration: f32,
+}
+
+impl Default for PickupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            every: Timer::from_seconds(10., TimerMode::Repeating),
+            freeze_duration: 1.5,
+        }
+    }
+}
+
+/// The next point `player` scores counts twice. Used up by any point, or lost when the timer
+/// runs out.
+#[derive(Resource, Clone)]
+pub(crate) struct DoublePoints {
+    pub(crate) player: usize,
+    timer: Timer,
+}
+
+impl DoublePoints {
+    pub(crate) fn new(player: usize) -> Self {
+        Self {
+            player,
+            timer: Timer::from_seconds(DOUBLE_POINTS_DURATION, TimerMode::Once),
+        }
+    }
+}
+
+/// What a pickup does to a paddle while it lasts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum PaddleEffect {
+    /// Can't move, and goes back to `color` afterwards. Collisions are unaffected.
+    Frozen { color: Color },
+    /// Grown or shrunk, back to its usual length afterwards.
+    Resized,
+    /// Catches the ball the next time it hits it, even without the catch key or
+    /// [`CatchConfig`](crate::paddle::CatchConfig) enabled. Lasts until then rather than for a set
+    /// time.
+    Sticky,
+}
+
+/// Every effect pickups have on a paddle, each with the timer it ends on, if any. At most one of
+/// each kind: starting one again restarts it.
+#[derive(Component, Clone, Default)]
+pub(crate) struct ActiveEffects(Vec<(PaddleEffect, Option<Timer>)>);
+
+impl ActiveEffects {
+    pub(crate) fn frozen(&self) -> bool {
+        self.0
+            .iter()
+            .any(|(effect, _)| matches!(effect, PaddleEffect::Frozen { .. }))
+    }
+
+    pub(crate) fn sticky(&self) -> bool {
+        self.0
+            .iter()
+            .any(|(effect, _)| *effect == PaddleEffect::Sticky)
+    }
+
+    /// Starts `effect` for `duration` seconds, or until it is ended with `None`.
+    fn start(&mut self, effect: PaddleEffect, duration: Option<f32>) {
+        self.end(effect);
+        let timer = duration.map(|duration| Timer::from_seconds(duration, TimerMode::Once));
+        self.0.push((effect, timer));
+    }
+
+    /// Ends the effect of the same kind as `effect`, whatever its data.
+    pub(crate) fn end(&mut self, effect: PaddleEffect) {
+        self.0
+            .retain(|(active, _)| mem::discriminant(active) != mem::discriminant(&effect));
+    }
+
+    /// Advances every timer, removing and returning the effects that ran out.
+    fn tick(&mut self, delta: Duration) -> Vec<PaddleEffect> {
+        let mut expired = Vec::new();
+        self.0.retain_mut(|(effect, timer)| {
+            let finished = timer
+                .as_mut()
+                .is_some_and(|timer| timer.tick(delta).finished());
+            if finished {
+                expired.push(*effect);
+            }
+            !finished
+        });
+        expired
+    }
+}
+
+fn start_speed_bursts(
+    mut commands: Commands,
+    mut bursts: ResMut<SpeedBursts>,
+    mut rng: ResMut<GameRng>,
+    ball: Single<Entity, (With<Ball>, Without<SpeedBurst>, Without<ExtraBall>)>,
+    time: Res<Time>,
+) {
+    if !bursts.enabled || !bursts.next.tick(time.delta()).finished() {
+        return;
+    }
+    let gap = bursts.every * rng.random_range(0.5..=1.5);
+    bursts.next = Timer::from_seconds(gap, TimerMode::Once);
+    commands.entity(*ball).insert(SpeedBurst {
+        warning: Timer::from_seconds(bursts.warning, TimerMode::Once),
+        timer: Timer::from_seconds(bursts.duration, TimerMode::Once),
+        scale: bursts.scale,
+    });
+}
+
+fn end_speed_bursts(
+    mut commands: Commands,
+    mut collisions: EventReader<BallCollision>,
+    balls: Query<(Entity, &mut SpeedBurst), With<Ball>>,
+    time: Res<Time>,
+) {
+    let scored = collisions
+        .read()
+        .any(|collision| collision.kind == CollisionKind::Goal);
+    for (entity, mut burst) in balls {
+        if burst.warning.finished() {
+            burst.timer.tick(time.delta());
+        } else {
+            burst.warning.tick(time.delta());
+        }
+        if scored || burst.timer.finished() {
+            commands.entity(entity).remove::<SpeedBurst>();
+        }
+    }
+}
+
+fn spawn_size_modifiers(
+    mut commands: Commands,
+    mut modifiers: ResMut<BallSizeModifiers>,
+    mut rng: ResMut<GameRng>,
+    ball: Single<Entity, (With<Ball>, Without<BallSizeModifier>, Without<ExtraBall>)>,
+    time: Res<Time>,
+) {
+    if !modifiers.enabled || !modifiers.every.tick(time.delta()).just_finished() {
+        return;
+    }
+    let scale = if rng.random() {
+        modifiers.big
+    } else {
+        modifiers.small
+    };
+    commands.entity(*ball).insert(BallSizeModifier {
+        scale,
+        timer: Timer::from_seconds(modifiers.duration, TimerMode::Once),
+    });
+}
+
+fn spawn_pickups(
+    mut commands: Commands,
+    mut config: ResMut<PickupConfig>,
+    mut rng: ResMut<GameRng>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    paddles: Res<PaddleConfig>,
+    time: Res<Time>,
+) {
+    if !config.enabled || !config.every.tick(time.delta()).just_finished() {
+        return;
+    }
+    let kind = PickupKind::ALL[rng.random_range(0..PickupKind::ALL.len())];
+    let x_range = paddles.offset / 2.;
+    let y_range = WALL_OFFSET - WALL_WIDTH - PICKUP_SIZE;
+    let position = Vec2::new(
+        rng.random_range(-x_range..=x_range),
+        rng.random_range(-y_range..=y_range),
+    );
+    commands.spawn((
+        Mesh2d(meshes.add(Rectangle::default())),
+        MeshMaterial2d(materials.add(kind.color())),
+        Transform::from_translation(position.extend(0.))
+            .with_rotation(Quat::from_rotation_z(PI / 4.))
+            .with_scale(Vec2::splat(PICKUP_SIZE).extend(1.)),
+        Pickup {
+            kind,
+            lifetime: Timer::from_seconds(PICKUP_LIFETIME, TimerMode::Once),
+        },
+    ));
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    mut pickups: Query<(Entity, &Transform, &mut Pickup), Without<Paddle>>,
+    mut balls: Query<
+        (
+            &Transform,
+            &mut Velocity,
+            &BallSize,
+            &Mesh2d,
+            &MeshMaterial2d<ColorMaterial>,
+        ),
+        (With<Ball>, Without<Paddle>),
+    >,
+    mut paddles: Query<
+        (
+            &mut Transform,
+            &MeshMaterial2d<ColorMaterial>,
+            &mut ActiveEffects,
+        ),
+        With<Paddle>,
+    >,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<PickupConfig>,
+    paddle_config: Res<PaddleConfig>,
+    time: Res<Time>,
+) {
+    for (entity, transform, mut pickup) in &mut pickups {
+        if pickup.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let pickup_circle = BoundingCircle::new(transform.translation.xy(), PICKUP_SIZE / 2.);
+        let collector = balls.iter_mut().find(|(ball_transform, _, size, ..)| {
+            BoundingCircle::new(ball_transform.translation.xy(), ***size / 2.)
+                .intersects(&pickup_circle)
+        });
+        let Some((ball_transform, mut ball_velocity, _, ball_mesh, ball_material)) = collector
+        else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+
+        // Paddles by whether the ball is heading toward them.
+        let heading_toward = |paddle_transform: &Transform| {
+            (paddle_transform.translation.x - ball_transform.translation.x) * ball_velocity.x > 0.
+        };
+        match pickup.kind {
+            PickupKind::Freeze => {
+                let target = paddles.iter_mut().find(|(paddle_transform, _, effects)| {
+                    !effects.frozen() && heading_toward(paddle_transform)
+                });
+                if let Some((_, material, mut effects)) = target {
+                    let mut color = Color::WHITE;
+                    if let Some(material) = materials.get_mut(&material.0) {
+                        color = material.color;
+                        material.color = FROZEN_COLOR;
+                    }
+                    effects.start(PaddleEffect::Frozen { color }, Some(config.freeze_duration));
+                }
+            }
+            PickupKind::Shield => {
+                let x = -ball_velocity.x.signum() * (WALL_OFFSET - SHIELD_INSET);
+                commands.spawn((
+                    Mesh2d(meshes.add(Rectangle::default())),
+                    MeshMaterial2d(materials.add(SHIELD_COLOR)),
+                    Transform::from_xyz(x, 0., 0.)
+                        .with_scale(Vec2::new(WALL_WIDTH / 2., WALL_LENGTH).extend(1.)),
+                    Shield {
+                        uses: 1,
+                        lifetime: Timer::from_seconds(SHIELD_DURATION, TimerMode::Once),
+                    },
+                    Collider,
+                ));
+            }
+            PickupKind::DoublePoints => {
+                commands.insert_resource(DoublePoints::new(usize::from(ball_velocity.x < 0.)));
+            }
+            PickupKind::Grow | PickupKind::Shrink => {
+                let (toward, factor) = if pickup.kind == PickupKind::Grow {
+                    (false, GROWN_FACTOR)
+                } else {
+                    (true, SHRUNK_FACTOR)
+                };
+                let target = paddles
+                    .iter_mut()
+                    .find(|(paddle_transform, ..)| heading_toward(paddle_transform) == toward);
+                if let Some((mut paddle_transform, _, mut effects)) = target {
+                    paddle_transform.scale.y =
+                        paddle_config.size(paddle_transform.translation.x).y * factor;
+                    effects.start(PaddleEffect::Resized, Some(RESIZE_DURATION));
+                }
+            }
+            PickupKind::Slow => **ball_velocity *= SLOW_FACTOR,
+            PickupKind::Sticky => {
+                let target = paddles
+                    .iter_mut()
+                    .find(|(paddle_transform, ..)| heading_toward(paddle_transform));
+                if let Some((.., mut effects)) = target {
+                    effects.start(PaddleEffect::Sticky, None);
+                }
+            }
+            PickupKind::Multiball => {
+                let position = ball_transform.translation;
+                commands.spawn((
+                    ball_mesh.clone(),
+                    ball_material.clone(),
+                    Transform::from_translation(position)
+                        .with_scale(Vec2::splat(BALL_SIZE).extend(1.)),
+                    Ball,
+                    ExtraBall,
+                    Velocity(Vec2::new(ball_velocity.x, -ball_velocity.y)),
+                    Interpolated::new(position),
+                ));
+            }
+        }
+    }
+}
+
+fn expire_shields(mut commands: Commands, shields: Query<(Entity, &mut Shield)>, time: Res<Time>) {
+    for (entity, mut shield) in shields {
+        if shield.uses == 0 || shield.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn expire_double_points(
+    mut commands: Commands,
+    double: Option<ResMut<DoublePoints>>,
+    time: Res<Time>,
+) {
+    if let Some(mut double) = double
+        && double.timer.tick(time.delta()).finished()
+    {
+        commands.remove_resource::<DoublePoints>();
+    }
+}
+
+/// Undoes each paddle effect whose timer runs out.
+fn expire_paddle_effects(
+    paddles: Query<(
+        &mut ActiveEffects,
+        &mut Transform,
+        &MeshMaterial2d<ColorMaterial>,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<PaddleConfig>,
+    time: Res<Time>,
+) {
+    for (mut effects, mut transform, material) in paddles {
+        for effect in effects.tick(time.delta()) {
+            match effect {
+                PaddleEffect::Frozen { color } => {
+                    if let Some(material) = materials.get_mut(&material.0) {
+                        material.color = color;
+                    }
+                }
+                PaddleEffect::Resized => {
+                    transform.scale.y = config.size(transform.translation.x).y;
+                }
+                PaddleEffect::Sticky => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paddle_effects_end_on_their_own_timers() {
+        let mut effects = ActiveEffects::default();
+        let color = Color::WHITE;
+        effects.start(PaddleEffect::Frozen { color }, Some(1.));
+        effects.start(PaddleEffect::Resized, Some(2.));
+        effects.start(PaddleEffect::Sticky, None);
+        assert!(effects.frozen() && effects.sticky());
+
+        let expired = effects.tick(Duration::from_secs_f32(1.5));
+        assert_eq!(expired, [PaddleEffect::Frozen { color }]);
+        assert!(!effects.frozen());
+
+        // Starting one again restarts it rather than stacking a second.
+        effects.start(PaddleEffect::Resized, Some(2.));
+        assert!(effects.tick(Duration::from_secs_f32(1.5)).is_empty());
+        assert_eq!(
+            effects.tick(Duration::from_secs_f32(1.)),
+            [PaddleEffect::Resized]
+        );
+
+        // Sticky only ends once the paddle catches the ball.
+        assert!(effects.sticky());
+        effects.end(PaddleEffect::Sticky);
+        assert!(!effects.sticky());
+    }
+}
@@ -0,0 +1,186 @@
+//! Solo practice: the aim trainer's targets and the ball machine.
+
+use crate::{
+    Phase,
+    ball::{BALL_SPEED, Ball, BallSize, Collider, Velocity},
+    effects::GoalFlash,
+    score::ScoreChanged,
+    serve::GameRng,
+    ui::on_off,
+    wall::{WALL_OFFSET, WALL_WIDTH},
+};
+use bevy::{
+    math::bounding::{Aabb2d, BoundingCircle, IntersectsVolume},
+    prelude::*,
+};
+use rand::Rng;
+
+pub(crate) const TARGET_SIZE: Vec2 = Vec2::new(30., 60.);
+pub(crate) const TARGET_COLOR: Color = Color::srgba(1., 0.8, 0.2, 0.5);
+
+pub(crate) const BALL_MACHINE_ANGLES: [f32; 5] = [-30., -15., 0., 15., 30.];
+pub(crate) const BALL_MACHINE_SPEEDS: [f32; 4] = [150., 200., 300., 400.];
+
+/// The aim trainer's targets and the ball machine's wall.
+pub struct PracticePlugin;
+
+impl Plugin for PracticePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BallMachineFeed>()
+            .add_systems(FixedUpdate, hit_targets.in_set(Phase::Targets))
+            .add_systems(
+                Update,
+                sync_ball_machine.run_if(resource_changed::<BallMachineFeed>),
+            );
+    }
+}
+
+/// Aim trainer: a single paddle on the left returns the ball at targets on the right, which is
+/// walled off instead of being a goal.
+#[derive(Resource, Clone)]
+pub(crate) struct Practice {
+    pub(crate) enabled: bool,
+    pub(crate) hits: u32,
+}
+
+#[derive(Component, Clone)]
+pub(crate) struct Target;
+
+/// A side wall that sends the ball back instead of conceding a point.
+#[derive(Component)]
+pub(crate) struct Backboard;
+
+/// Feed of the ball machine, which replaces the right goal with a wall that returns every ball at
+/// the same angle and speed. It is set from its own menu, so replays don't capture it.
+#[derive(Resource)]
+pub(crate) struct BallMachineFeed {
+    pub(crate) enabled: bool,
+    /// Above the horizontal, so positive angles feed the ball upward.
+    pub(crate) angle_degrees: f32,
+    pub(crate) speed: f32,
+}
+
+impl BallMachineFeed {
+    fn machine(&self) -> BallMachine {
+        BallMachine {
+            angle: self.angle_degrees.to_radians(),
+            speed: self.speed,
+        }
+    }
+
+    pub(crate) fn labels(&self) -> Vec<String> {
+        vec![
+            format!("Ball machine: {}", on_off(self.enabled)),
+            format!("Feed angle: {:.0}°", self.angle_degrees),
+            format!("Feed speed: {:.0}", self.speed),
+            "Back".to_string(),
+        ]
+    }
+}
+
+impl Default for BallMachineFeed {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            angle_degrees: 0.,
+            speed: BALL_SPEED,
+        }
+    }
+}
+
+/// Wall that sends the ball back toward the center at a fixed `angle`, in radians above the
+/// horizontal, and `speed`.
+#[derive(Component)]
+pub(crate) struct BallMachine {
+    angle: f32,
+    speed: f32,
+}
+
+impl BallMachine {
+    pub(crate) fn feed(&self, toward: f32) -> Vec2 {
+        Vec2::new(toward * self.angle.cos(), self.angle.sin()) * self.speed
+    }
+}
+
+pub(crate) fn random_target_position(rng: &mut impl Rng) -> Vec2 {
+    let max = Vec2::splat(WALL_OFFSET - WALL_WIDTH) - TARGET_SIZE / 2.;
+    Vec2::new(
+        rng.random_range(0. ..=max.x),
+        rng.random_range(-max.y..=max.y),
+    )
+}
+
+fn hit_targets(
+    mut practice: ResMut<Practice>,
+    mut targets: Query<&mut Transform, (With<Target>, Without<Ball>)>,
+    balls: Query<(&Transform, &Velocity, &BallSize), With<Ball>>,
+    mut rng: ResMut<GameRng>,
+    mut score_changed: EventWriter<ScoreChanged>,
+) {
+    if !practice.enabled {
+        return;
+    }
+    for (ball_transform, ball_velocity, ball_size) in &balls {
+        if ball_velocity.x <= 0. {
+            continue;
+        }
+        let bounding_circle =
+            BoundingCircle::new(ball_transform.translation.xy(), **ball_size / 2.);
+        for mut transform in &mut targets {
+            let bounding_box = Aabb2d::new(transform.translation.xy(), TARGET_SIZE / 2.);
+            if bounding_circle.intersects(&bounding_box) {
+                practice.hits += 1;
+                score_changed.write(ScoreChanged);
+                transform.translation = random_target_position(&mut **rng).extend(-1.);
+            }
+        }
+    }
+}
+
+/// Turns the right side wall into the ball machine, or back into a goal marker.
+fn sync_ball_machine(
+    mut commands: Commands,
+    feed: Res<BallMachineFeed>,
+    walls: Query<(Entity, &Transform, Has<Backboard>), With<GoalFlash>>,
+) {
+    let Some((wall, _, is_backboard)) = walls
+        .iter()
+        .find(|(_, transform, _)| transform.translation.x > 0.)
+    else {
+        return;
+    };
+    if feed.enabled {
+        commands.entity(wall).insert((feed.machine(), Collider));
+    } else {
+        commands.entity(wall).remove::<BallMachine>();
+        if !is_backboard {
+            commands.entity(wall).remove::<Collider>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_machine_feeds_at_its_set_angle_and_speed() {
+        let feed = BallMachineFeed {
+            enabled: true,
+            angle_degrees: 30.,
+            speed: 300.,
+        };
+        let velocity = feed.machine().feed(-1.);
+        assert!((velocity.length() - 300.).abs() < 1e-3);
+        assert!((velocity.y - 150.).abs() < 1e-3);
+        assert!(velocity.x < 0.);
+
+        let downward = BallMachineFeed {
+            angle_degrees: -30.,
+            ..feed
+        };
+        let velocity = downward.machine().feed(1.);
+        assert!(velocity.x > 0.);
+        assert!((velocity.y + 150.).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,905 @@
+//! Recording and playing back replays, the instant replay after each point, and stepping through
+//! the game one fixed step at a time.
+
+use crate::{
+    FIXED_TIMESTEP_HZ, Phase, Simulation,
+    ball::{
+        Ball, BallCollision, CollisionKind, ExtraBall, Rally, StallWatchdog, detect_collisions,
+    },
+    interpolation::{Interpolated, interpolate_translation, store_physical_translation},
+    paddle::{Paddle, PaddleIntent, paddle_side},
+    pickups::{BallSizeModifiers, DoublePoints, Pickup, PickupConfig, Shield, SpeedBursts},
+    practice::{Practice, Target},
+    score::{MatchClock, Score, ScoreChanged, ScoreDecay, score_goals},
+    serve::{GameRng, GameSeed},
+    settings::{PendingSaves, cli_arg, cli_flag},
+    ui::SettingsMenu,
+};
+use bevy::{
+    app::FixedMain,
+    ecs::{
+        component::{ComponentCloneBehavior, ComponentId},
+        entity_disabling::Disabled,
+    },
+    prelude::*,
+    render::view::screenshot::{Screenshot, save_to_disk},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Fixed steps kept for the instant replay, two seconds' worth.
+const INSTANT_REPLAY_STEPS: usize = 128;
+const INSTANT_REPLAY_SPEED: f32 = 0.35;
+
+const STEP_MODE_KEY: KeyCode = KeyCode::F4;
+const STEP_KEY: KeyCode = KeyCode::Period;
+
+pub(crate) const BEST_RALLY_PATH: &str = "best-rally.ron";
+/// Playback speed until a replay reaches its highlight.
+const REPLAY_SKIP_SPEED: f32 = 12.;
+const REPLAY_PAUSE_KEY: KeyCode = KeyCode::Space;
+const REPLAY_STEP_KEY: KeyCode = KeyCode::Period;
+const REPLAY_STEP_BACK_KEY: KeyCode = KeyCode::Comma;
+const REPLAY_SLOWER_KEY: KeyCode = KeyCode::Minus;
+const REPLAY_FASTER_KEY: KeyCode = KeyCode::Equal;
+const REPLAY_SPEEDS: [f32; 5] = [0.25, 0.5, 1., 2., 4.];
+/// Fixed steps between the snapshots a replay saves as it plays, so stepping back never has to
+/// play more than this many steps again.
+const REPLAY_SNAPSHOT_INTERVAL: usize = 64;
+/// Just under the seed in the top right corner.
+pub(crate) const REPLAY_STATUS_TOP: Val = Val::Px(40.);
+
+/// Recording the paddles' intents and playing them back, which headless replay checks need too.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (record_replay_inputs, play_replay_inputs).in_set(Phase::Replay),
+        );
+    }
+}
+
+/// Watching and saving replays, the instant replay and step mode. Only added with a window.
+pub struct ReplayControlsPlugin;
+
+impl Plugin for ReplayControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BestRally>()
+            .init_resource::<ReplayScrubber>()
+            .init_resource::<ReplaySnapshots>()
+            .add_systems(FixedPreUpdate, snapshot_replay)
+            .add_systems(
+                FixedUpdate,
+                (
+                    capture_instant_replay_frame
+                        .after(detect_collisions)
+                        .before(score_goals),
+                    track_best_rally.in_set(Simulation).after(Phase::Limit),
+                ),
+            )
+            .add_systems(
+                FixedPostUpdate,
+                export_replay_frame.after(store_physical_translation),
+            )
+            .add_systems(
+                Update,
+                (
+                    play_instant_replay.after(interpolate_translation),
+                    (scrub_replay, step_paused_replay, update_replay_status).chain(),
+                    (toggle_step_mode, step_simulation)
+                        .chain()
+                        .run_if(resource_exists::<StepMode>),
+                ),
+            );
+    }
+}
+
+/// Playback controls while watching a replay: pause, single steps either way while paused, and a
+/// speed picked from [`REPLAY_SPEEDS`].
+#[derive(Resource)]
+struct ReplayScrubber {
+    speed: usize,
+    /// Set for one frame to run a single fixed step while paused.
+    step: bool,
+    /// Set for one frame to go back a single fixed step while paused.
+    step_back: bool,
+}
+
+impl Default for ReplayScrubber {
+    fn default() -> Self {
+        Self {
+            speed: REPLAY_SPEEDS
+                .iter()
+                .position(|&speed| speed == 1.)
+                .unwrap_or(0),
+            step: false,
+            step_back: false,
+        }
+    }
+}
+
+/// Entities the simulation changes, which a [`ReplaySnapshot`] saves.
+type Simulated = Or<(
+    With<Ball>,
+    With<Paddle>,
+    With<Pickup>,
+    With<Shield>,
+    With<Target>,
+)>;
+
+/// Puts a resource back the way it was when saved, removing it if it didn't exist then.
+type RestoreResource = Box<dyn Fn(&mut World) + Send + Sync>;
+
+/// Resources the simulation changes, which a [`ReplaySnapshot`] saves.
+const SIMULATED_RESOURCES: [fn(&World) -> RestoreResource; 11] = [
+    save_resource::<Score>,
+    save_resource::<GameRng>,
+    save_resource::<Rally>,
+    save_resource::<StallWatchdog>,
+    save_resource::<MatchClock>,
+    save_resource::<ScoreDecay>,
+    save_resource::<DoublePoints>,
+    save_resource::<PickupConfig>,
+    save_resource::<BallSizeModifiers>,
+    save_resource::<SpeedBursts>,
+    save_resource::<Practice>,
+];
+
+fn save_resource<R: Resource + Clone>(world: &World) -> RestoreResource {
+    let saved = world.get_resource::<R>().cloned();
+    Box::new(move |world| match &saved {
+        Some(resource) => world.insert_resource(resource.clone()),
+        None => {
+            world.remove_resource::<R>();
+        }
+    })
+}
+
+/// The game at the start of replay step `tick`. Entities are kept as disabled clones, which no
+/// query sees, each paired with the entity it was cloned from.
+struct ReplaySnapshot {
+    tick: usize,
+    entities: Vec<(Entity, Entity)>,
+    resources: Vec<RestoreResource>,
+}
+
+/// Taken every [`REPLAY_SNAPSHOT_INTERVAL`] steps while a replay plays. Playback is
+/// deterministic, so going back to a step restores the latest snapshot before it and plays
+/// forward from there.
+#[derive(Resource, Default)]
+struct ReplaySnapshots(Vec<ReplaySnapshot>);
+
+#[derive(Component)]
+pub(crate) struct ReplayStatusText;
+
+/// Debugging aid that starts the game paused and runs one fixed step per press of [`STEP_KEY`].
+/// [`STEP_MODE_KEY`] switches between stepping and running normally.
+#[derive(Resource)]
+pub(crate) struct StepMode {
+    pub(crate) stepping: bool,
+    /// Set for one frame to run a single fixed step.
+    pub(crate) step: bool,
+}
+
+/// Share code plus both paddles' intents (left, right) for every simulated fixed step.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Replay {
+    code: String,
+    inputs: Vec<[f32; 2]>,
+    /// State after the last step, checked by `--verify` to catch changes to gameplay.
+    #[serde(default)]
+    outcome: Option<ReplayOutcome>,
+    /// Step the interesting part starts on. Playback races through the steps before it.
+    #[serde(default)]
+    highlight: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct ReplayOutcome {
+    score: (u32, u32),
+    ball: [f32; 2],
+    paddles: [f32; 2],
+}
+
+impl ReplayOutcome {
+    /// Takes physics translations, which differ from rendered ones outside the fixed steps.
+    fn capture(score: &Score, ball: Vec3, paddles: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut outcome = Self {
+            score: (score.0, score.1),
+            ball: ball.xy().to_array(),
+            paddles: [0.; 2],
+        };
+        for translation in paddles {
+            outcome.paddles[usize::from(translation.x > 0.)] = translation.y;
+        }
+        outcome
+    }
+}
+
+/// Ball and paddle heights (left, right) after one fixed step.
+pub(crate) struct ReplayFrame {
+    ball: Vec2,
+    paddles: [f32; 2],
+}
+
+/// Slow-motion replay of the last couple of seconds before each point. It pauses the game while
+/// it plays, so the simulation and any recorded replay are unaffected.
+#[derive(Resource)]
+pub(crate) struct InstantReplay {
+    pub(crate) enabled: bool,
+    /// The latest [`INSTANT_REPLAY_STEPS`] steps, oldest first.
+    pub(crate) recent: VecDeque<ReplayFrame>,
+    /// Frames being played back and how far into them, in fixed steps.
+    pub(crate) playback: Option<(Vec<ReplayFrame>, f32)>,
+}
+
+impl Default for InstantReplay {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            recent: VecDeque::with_capacity(INSTANT_REPLAY_STEPS),
+            playback: None,
+        }
+    }
+}
+
+/// The session's inputs so far and the longest rally in them, which can be saved as a replay from
+/// the settings menu. Replays always run from the share code, so a rally's replay has to include
+/// every step before it, highlighting where the rally starts.
+#[derive(Resource, Default)]
+pub(crate) struct BestRally {
+    inputs: Vec<[f32; 2]>,
+    /// Step the current rally started on.
+    rally_start: usize,
+    /// Paddle hits in the current rally.
+    hits: u32,
+    pub(crate) best: Option<(u32, Replay)>,
+}
+
+impl BestRally {
+    /// Starts the session's inputs over for a rematch, which plays out from the share code
+    /// again. The best rally so far keeps its own inputs.
+    pub(crate) fn restart(&mut self) {
+        self.inputs.clear();
+        self.rally_start = 0;
+        self.hits = 0;
+    }
+}
+
+/// What to do with a replay's outcome once headless playback finishes.
+pub(crate) enum ReplayCheck {
+    /// Fail unless the outcome matches the recorded one.
+    Verify,
+    /// Overwrite the recorded outcome, for when gameplay changed on purpose.
+    Bless,
+}
+
+impl Replay {
+    fn load(path: &Path) -> Result<(GameSeed, Replay), String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let replay: Replay = ron::from_str(&contents).map_err(|err| err.to_string())?;
+        let seed = replay.code.parse().map_err(|err| format!("{err}"))?;
+        Ok((seed, replay))
+    }
+}
+
+#[derive(Resource)]
+pub(crate) enum ReplayMode {
+    Off,
+    Recording {
+        path: PathBuf,
+        replay: Replay,
+    },
+    Playing {
+        path: PathBuf,
+        seed: GameSeed,
+        replay: Replay,
+        tick: usize,
+        /// Directory each simulated step is saved to as a numbered PNG.
+        export: Option<PathBuf>,
+        check: Option<ReplayCheck>,
+    },
+}
+
+impl ReplayMode {
+    pub(crate) fn from_args() -> Self {
+        if let Some(path) = cli_arg::<PathBuf>("--replay") {
+            let (seed, replay) = match Replay::load(&path) {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    eprintln!("Ignoring --replay {}: {err}", path.display());
+                    return Self::Off;
+                }
+            };
+            let export = cli_arg::<PathBuf>("--export-frames").filter(|dir| {
+                fs::create_dir_all(dir)
+                    .inspect_err(|err| {
+                        eprintln!("Ignoring --export-frames {}: {err}", dir.display())
+                    })
+                    .is_ok()
+            });
+            let check = if cli_flag("--bless") {
+                Some(ReplayCheck::Bless)
+            } else if cli_flag("--verify") {
+                Some(ReplayCheck::Verify)
+            } else {
+                None
+            };
+            return Self::Playing {
+                path,
+                seed,
+                replay,
+                tick: 0,
+                export,
+                check,
+            };
+        }
+        match cli_arg::<PathBuf>("--record") {
+            Some(path) => Self::Recording {
+                path,
+                replay: Replay {
+                    code: String::new(),
+                    inputs: Vec::new(),
+                    outcome: None,
+                    highlight: None,
+                },
+            },
+            None => Self::Off,
+        }
+    }
+}
+
+fn record_replay_inputs(
+    mut replay_mode: ResMut<ReplayMode>,
+    paddles: Query<(&Transform, &PaddleIntent)>,
+    seed: Res<GameSeed>,
+) {
+    let ReplayMode::Recording { replay, .. } = &mut *replay_mode else {
+        return;
+    };
+    if replay.code.is_empty() {
+        replay.code = seed.to_string();
+    }
+    let mut inputs = [0.; 2];
+    for (transform, intent) in &paddles {
+        inputs[paddle_side(transform)] = **intent;
+    }
+    replay.inputs.push(inputs);
+}
+
+fn scrub_replay(
+    mut scrubber: ResMut<ReplayScrubber>,
+    replay_mode: Res<ReplayMode>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let ReplayMode::Playing { export: None, .. } = *replay_mode else {
+        return;
+    };
+    if keyboard.just_pressed(REPLAY_PAUSE_KEY) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+    scrubber.step = time.is_paused() && keyboard.just_pressed(REPLAY_STEP_KEY);
+    scrubber.step_back = time.is_paused() && keyboard.just_pressed(REPLAY_STEP_BACK_KEY);
+
+    let speed = scrubber.speed;
+    if keyboard.just_pressed(REPLAY_SLOWER_KEY) {
+        scrubber.speed = speed.saturating_sub(1);
+    } else if keyboard.just_pressed(REPLAY_FASTER_KEY) {
+        scrubber.speed = (speed + 1).min(REPLAY_SPEEDS.len() - 1);
+    }
+    if scrubber.speed != speed {
+        time.set_relative_speed(REPLAY_SPEEDS[scrubber.speed]);
+    }
+}
+
+/// Runs one fixed step by hand, the way the fixed main loop would, or goes back one, while the
+/// replay is paused.
+fn step_paused_replay(world: &mut World) {
+    if std::mem::take(&mut world.resource_mut::<ReplayScrubber>().step) {
+        run_fixed_step(world);
+    }
+    if std::mem::take(&mut world.resource_mut::<ReplayScrubber>().step_back)
+        && let ReplayMode::Playing { tick, .. } = *world.resource::<ReplayMode>()
+        && tick > 0
+    {
+        seek_replay(world, tick - 1);
+    }
+}
+
+/// Saves a snapshot at the start of every [`REPLAY_SNAPSHOT_INTERVAL`]th step the replay hasn't
+/// reached before.
+fn snapshot_replay(world: &mut World) {
+    let ReplayMode::Playing {
+        tick, export: None, ..
+    } = *world.resource::<ReplayMode>()
+    else {
+        return;
+    };
+    if tick != world.resource::<ReplaySnapshots>().0.len() * REPLAY_SNAPSHOT_INTERVAL {
+        return;
+    }
+    let live: Vec<Entity> = world
+        .query_filtered::<Entity, Simulated>()
+        .iter(world)
+        .collect();
+    let entities = live
+        .into_iter()
+        .map(|entity| {
+            let saved = world.entity_mut(entity).clone_and_spawn();
+            world.entity_mut(saved).insert(Disabled);
+            (entity, saved)
+        })
+        .collect();
+    let resources = SIMULATED_RESOURCES.iter().map(|save| save(world)).collect();
+    world
+        .resource_mut::<ReplaySnapshots>()
+        .0
+        .push(ReplaySnapshot {
+            tick,
+            entities,
+            resources,
+        });
+}
+
+/// Puts the game back the way `snapshot` saved it. Entities despawned since are spawned again,
+/// and the snapshot keeps track of the new ones for the next restore.
+fn restore_snapshot(world: &mut World, snapshot: &mut ReplaySnapshot) {
+    let live: Vec<Entity> = world
+        .query_filtered::<Entity, Simulated>()
+        .iter(world)
+        .collect();
+    for entity in live {
+        if !snapshot
+            .entities
+            .iter()
+            .any(|&(saved_from, _)| saved_from == entity)
+        {
+            world.despawn(entity);
+        }
+    }
+    for (entity, saved) in &mut snapshot.entities {
+        if world.get_entity(*entity).is_err() {
+            *entity = world.spawn_empty().id();
+        }
+        // Cloning only adds components, so any added since, like a ball being held, go first.
+        // Components that are never cloned, like the render world's, stay.
+        let kept: Vec<ComponentId> = world.entity(*saved).archetype().components().collect();
+        let added: Vec<ComponentId> = world
+            .entity(*entity)
+            .archetype()
+            .components()
+            .filter(|id| {
+                !kept.contains(id)
+                    && world.components().get_info(*id).is_some_and(|info| {
+                        matches!(info.clone_behavior(), ComponentCloneBehavior::Custom(_))
+                    })
+            })
+            .collect();
+        let mut restored = world.entity_mut(*entity);
+        for id in added {
+            restored.remove_by_id(id);
+        }
+        world.entity_mut(*saved).clone_with(*entity, |builder| {
+            builder.deny::<Disabled>();
+        });
+    }
+    for restore in &snapshot.resources {
+        restore(world);
+    }
+}
+
+/// Goes back to step `target` of the replay from the latest snapshot before it.
+fn seek_replay(world: &mut World, target: usize) {
+    let start = world.resource_scope(|world, mut snapshots: Mut<ReplaySnapshots>| {
+        let snapshot = snapshots
+            .0
+            .iter_mut()
+            .rev()
+            .find(|snapshot| snapshot.tick <= target)?;
+        restore_snapshot(world, snapshot);
+        Some(snapshot.tick)
+    });
+    let Some(start) = start else {
+        return;
+    };
+    if let ReplayMode::Playing { tick, .. } = &mut *world.resource_mut::<ReplayMode>() {
+        *tick = start;
+    }
+    for _ in start..target {
+        run_fixed_step(world);
+    }
+    // The steps played again were already seen, so only the score needs showing again.
+    world.resource_mut::<Events<BallCollision>>().clear();
+    world.send_event(ScoreChanged);
+}
+
+fn toggle_step_mode(
+    mut step_mode: ResMut<StepMode>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if keyboard.just_pressed(STEP_MODE_KEY) {
+        step_mode.stepping = !step_mode.stepping;
+        if step_mode.stepping {
+            time.pause();
+        } else {
+            time.unpause();
+        }
+    }
+    step_mode.step = step_mode.stepping && keyboard.just_pressed(STEP_KEY);
+}
+
+fn step_simulation(world: &mut World) {
+    if std::mem::take(&mut world.resource_mut::<StepMode>().step) {
+        run_fixed_step(world);
+    }
+}
+
+/// Runs one fixed step right away, whether or not virtual time is paused.
+fn run_fixed_step(world: &mut World) {
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    world.run_schedule(FixedMain);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+fn update_replay_status(
+    mut text: Single<&mut Text, With<ReplayStatusText>>,
+    replay_mode: Res<ReplayMode>,
+    scrubber: Res<ReplayScrubber>,
+    time: Res<Time<Virtual>>,
+) {
+    let status = match &*replay_mode {
+        ReplayMode::Playing { replay, tick, .. } => {
+            let state = if time.is_paused() {
+                "paused".to_string()
+            } else {
+                format!("{}x", REPLAY_SPEEDS[scrubber.speed])
+            };
+            format!("Tick {tick} / {} ({state})", replay.inputs.len())
+        }
+        _ => String::new(),
+    };
+    if text.0 != status {
+        text.0 = status;
+    }
+}
+
+fn play_replay_inputs(
+    mut replay_mode: ResMut<ReplayMode>,
+    paddles: Query<(&Transform, &mut PaddleIntent)>,
+    ball: Single<&Transform, (With<Ball>, Without<ExtraBall>)>,
+    score: Res<Score>,
+    mut exit: EventWriter<AppExit>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let ReplayMode::Playing {
+        path,
+        replay,
+        tick,
+        export,
+        check,
+        ..
+    } = &mut *replay_mode
+    else {
+        return;
+    };
+    let Some(inputs) = replay.inputs.get(*tick) else {
+        info!("Replay finished after {tick} steps");
+        let outcome = ReplayOutcome::capture(
+            &score,
+            ball.translation,
+            paddles.iter().map(|(transform, _)| transform.translation),
+        );
+        match check {
+            Some(ReplayCheck::Verify) if replay.outcome.as_ref() == Some(&outcome) => {
+                println!("{}: ok", path.display());
+                exit.write(AppExit::Success);
+            }
+            Some(ReplayCheck::Verify) => {
+                eprintln!(
+                    "{}: expected {:?}, got {outcome:?}",
+                    path.display(),
+                    replay.outcome
+                );
+                exit.write(AppExit::error());
+            }
+            Some(ReplayCheck::Bless) => {
+                replay.outcome = Some(outcome);
+                let written = ron::to_string(&*replay)
+                    .map_err(std::io::Error::other)
+                    .and_then(|contents| fs::write(&*path, contents));
+                match written {
+                    Ok(()) => {
+                        println!("{}: blessed", path.display());
+                        exit.write(AppExit::Success);
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to write {}: {err}", path.display());
+                        exit.write(AppExit::error());
+                    }
+                }
+            }
+            None if export.is_some() => {
+                exit.write(AppExit::Success);
+            }
+            None => {}
+        }
+        *replay_mode = ReplayMode::Off;
+        return;
+    };
+    if let Some(start) = replay.highlight.filter(|_| export.is_none()) {
+        if *tick == 0 && start > 0 {
+            time.set_relative_speed(REPLAY_SKIP_SPEED);
+        } else if *tick == start {
+            time.set_relative_speed(1.);
+        }
+    }
+    for (transform, mut intent) in paddles {
+        **intent = inputs[paddle_side(transform)];
+    }
+    *tick += 1;
+}
+
+/// Runs between moving the ball and scoring, so the frames leading to a point end on the ball
+/// reaching the goal rather than on the serve.
+fn capture_instant_replay_frame(
+    mut instant_replay: ResMut<InstantReplay>,
+    ball: Single<&Transform, (With<Ball>, Without<ExtraBall>)>,
+    paddles: Query<&Transform, (With<Paddle>, Without<Ball>)>,
+) {
+    if !instant_replay.enabled {
+        return;
+    }
+    let mut frame = ReplayFrame {
+        ball: ball.translation.xy(),
+        paddles: [0.; 2],
+    };
+    for transform in &paddles {
+        frame.paddles[paddle_side(transform)] = transform.translation.y;
+    }
+    if instant_replay.recent.len() == INSTANT_REPLAY_STEPS {
+        instant_replay.recent.pop_front();
+    }
+    instant_replay.recent.push_back(frame);
+}
+
+/// Starts an instant replay when a point is scored and drives the ball and paddles through it in
+/// real time, since the game is paused meanwhile. Runs after interpolation so it has the last word
+/// on their translations.
+fn play_instant_replay(
+    mut instant_replay: ResMut<InstantReplay>,
+    mut collisions: EventReader<BallCollision>,
+    mut ball: Single<&mut Transform, (With<Ball>, Without<ExtraBall>)>,
+    paddles: Query<&mut Transform, (With<Paddle>, Without<Ball>)>,
+    menu: Single<&Visibility, With<SettingsMenu>>,
+    mut time: ResMut<Time<Virtual>>,
+    real_time: Res<Time<Real>>,
+) {
+    let scored = collisions
+        .read()
+        .any(|collision| collision.kind == CollisionKind::Goal);
+    if scored
+        && instant_replay.enabled
+        && instant_replay.playback.is_none()
+        && !time.is_paused()
+        && !instant_replay.recent.is_empty()
+    {
+        let frames = instant_replay.recent.drain(..).collect();
+        instant_replay.playback = Some((frames, 0.));
+        time.pause();
+    }
+
+    let Some((frames, position)) = &mut instant_replay.playback else {
+        return;
+    };
+    *position += real_time.delta_secs() * FIXED_TIMESTEP_HZ as f32 * INSTANT_REPLAY_SPEED;
+    // Unpausing from the menu skips the rest of the replay.
+    let Some(frame) = frames.get(*position as usize).filter(|_| time.is_paused()) else {
+        instant_replay.playback = None;
+        if **menu == Visibility::Hidden {
+            time.unpause();
+        }
+        return;
+    };
+    ball.translation = frame.ball.extend(0.);
+    for mut transform in paddles {
+        transform.translation.y = frame.paddles[paddle_side(&transform)];
+    }
+}
+
+fn track_best_rally(
+    mut rally: ResMut<BestRally>,
+    mut collisions: EventReader<BallCollision>,
+    replay_mode: Res<ReplayMode>,
+    paddles: Query<(&Transform, &PaddleIntent)>,
+    seed: Res<GameSeed>,
+) {
+    if matches!(*replay_mode, ReplayMode::Playing { .. }) {
+        collisions.clear();
+        return;
+    }
+    let mut inputs = [0.; 2];
+    for (transform, intent) in &paddles {
+        inputs[paddle_side(transform)] = **intent;
+    }
+    rally.inputs.push(inputs);
+
+    for collision in collisions.read() {
+        match collision.kind {
+            CollisionKind::Paddle => rally.hits += 1,
+            CollisionKind::Goal => {
+                let best_hits = rally.best.as_ref().map_or(0, |(hits, _)| *hits);
+                if rally.hits > best_hits {
+                    let replay = Replay {
+                        code: seed.to_string(),
+                        inputs: rally.inputs.clone(),
+                        outcome: None,
+                        highlight: Some(rally.rally_start),
+                    };
+                    rally.best = Some((rally.hits, replay));
+                }
+                rally.hits = 0;
+                rally.rally_start = rally.inputs.len();
+            }
+            CollisionKind::Wall => {}
+        }
+    }
+}
+
+fn export_replay_frame(mut commands: Commands, replay_mode: Res<ReplayMode>) {
+    if let ReplayMode::Playing {
+        tick,
+        export: Some(dir),
+        ..
+    } = &*replay_mode
+    {
+        let path = dir.join(format!("frame_{tick:06}.png"));
+        commands
+            .spawn(Screenshot::primary_window())
+            .observe(save_to_disk(path));
+    }
+}
+
+pub(crate) fn save_replay(
+    mut exits: EventReader<AppExit>,
+    mut replay_mode: ResMut<ReplayMode>,
+    ball: Single<&Interpolated, (With<Ball>, Without<ExtraBall>)>,
+    paddles: Query<&Interpolated, With<Paddle>>,
+    score: Res<Score>,
+    mut saves: ResMut<PendingSaves>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    let ReplayMode::Recording { path, replay } = &mut *replay_mode else {
+        return;
+    };
+    replay.outcome = Some(ReplayOutcome::capture(
+        &score,
+        ball.current,
+        paddles.iter().map(|interpolated| interpolated.current),
+    ));
+    match ron::to_string(replay) {
+        Ok(contents) => saves.queue(path.clone(), contents),
+        Err(err) => error!("Failed to serialize replay: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        serve::{ServeDirection, Serving},
+        testing,
+    };
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    /// Stands in for the simulation: the ball moves a pixel a step and the right score is a fresh
+    /// random draw, while a second ball joins on step 3, the first one starts serving on step 5
+    /// and the second one leaves on step 67.
+    fn play_step(
+        mut commands: Commands,
+        mut replay_mode: ResMut<ReplayMode>,
+        mut score: ResMut<Score>,
+        mut rng: ResMut<GameRng>,
+        ball: Single<(Entity, &mut Transform), (With<Ball>, Without<ExtraBall>)>,
+        extra_balls: Query<Entity, With<ExtraBall>>,
+    ) {
+        let ReplayMode::Playing { tick, .. } = &mut *replay_mode else {
+            return;
+        };
+        let (entity, mut transform) = ball.into_inner();
+        transform.translation.x += 1.;
+        score.1 = rng.random_range(0..1000);
+        match *tick {
+            3 => {
+                commands.spawn((Transform::from_xyz(0., 50., 0.), Ball, ExtraBall));
+            }
+            5 => {
+                commands.entity(entity).insert(Serving);
+            }
+            67 => extra_balls
+                .iter()
+                .for_each(|extra| commands.entity(extra).despawn()),
+            _ => {}
+        }
+        *tick += 1;
+    }
+
+    /// The step reached, where the first ball is, how many extra balls there are, whether the
+    /// first ball is serving and the score.
+    fn state(app: &mut App) -> (usize, f32, usize, bool, (u32, u32)) {
+        let world = app.world_mut();
+        let ReplayMode::Playing { tick, .. } = *world.resource::<ReplayMode>() else {
+            panic!("the replay finished");
+        };
+        let (x, serving) = world
+            .query_filtered::<(&Transform, Has<Serving>), Without<ExtraBall>>()
+            .single(world)
+            .map(|(transform, serving)| (transform.translation.x, serving))
+            .unwrap();
+        let extra_balls = world
+            .query_filtered::<(), With<ExtraBall>>()
+            .iter(world)
+            .count();
+        let score = world.resource::<Score>();
+        (tick, x, extra_balls, serving, (score.0, score.1))
+    }
+
+    #[test]
+    fn stepping_back_plays_forward_from_the_last_snapshot() {
+        let mut app = testing::app();
+        app.add_event::<BallCollision>()
+            .add_event::<ScoreChanged>()
+            .insert_resource(Score(0, 0))
+            .insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .insert_resource(ReplayMode::Playing {
+                path: PathBuf::new(),
+                seed: GameSeed {
+                    seed: 0,
+                    serve: ServeDirection::Right,
+                },
+                replay: Replay {
+                    code: String::new(),
+                    inputs: Vec::new(),
+                    outcome: None,
+                    highlight: None,
+                },
+                tick: 0,
+                export: None,
+                check: None,
+            })
+            .init_resource::<ReplaySnapshots>()
+            .add_systems(FixedPreUpdate, snapshot_replay)
+            .add_systems(FixedUpdate, play_step);
+        app.world_mut().spawn((Transform::default(), Ball));
+
+        let mut states = vec![state(&mut app)];
+        for _ in 0..70 {
+            app.update();
+            states.push(state(&mut app));
+        }
+        assert_eq!(app.world().resource::<ReplaySnapshots>().0.len(), 2);
+
+        for target in [69, 66, 64, 10, 4, 2] {
+            seek_replay(app.world_mut(), target);
+            assert_eq!(state(&mut app), states[target], "back to step {target}");
+        }
+        // Playing on from there goes the same way as the first time.
+        app.update();
+        assert_eq!(state(&mut app), states[3]);
+    }
+}
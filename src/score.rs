@@ -0,0 +1,974 @@
+//! Scoring, the match limit and clock, and the history and ratings finished matches go into.
+
+use crate::{
+    Phase,
+    ball::{
+        Ball, BallCollision, BallSize, CollisionKind, ExtraBall, Rally, Velocity, segment_entry,
+    },
+    interpolation::Interpolated,
+    pickups::DoublePoints,
+    practice::Practice,
+    replay::ReplayMode,
+    serve::{GameRng, GameSeed, SERVICE_LINE, ServeConfig, ServeDirection, Serving},
+    settings::PendingSaves,
+    wall::Goal,
+};
+use bevy::{
+    math::bounding::{Aabb2d, BoundingCircle, IntersectsVolume},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fs,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HISTORY_PATH: &str = "history.ron";
+/// Matches kept in [`HISTORY_PATH`]; older ones are dropped.
+const HISTORY_LENGTH: usize = 100;
+/// Matches listed in the history screen.
+const HISTORY_SHOWN: usize = 8;
+
+const RATINGS_PATH: &str = "ratings.ron";
+const BASE_RATING: f32 = 1000.;
+pub(crate) const DEFAULT_K_FACTOR: f32 = 32.;
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ScoreChanged>()
+            .init_resource::<MatchClock>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    score_goals.in_set(Phase::Score),
+                    reset_volleys.in_set(Phase::Respond),
+                    end_celebrations.in_set(Phase::Celebrate),
+                    decay_scores.in_set(Phase::Decay),
+                    check_match_limit.in_set(Phase::Limit),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Clone)]
+pub(crate) struct Score(pub(crate) u32, pub(crate) u32);
+
+#[derive(Event)]
+pub(crate) struct ScoreChanged;
+
+/// Pressure mode: while enabled, both scores tick down by one every timer period.
+#[derive(Resource, Clone)]
+pub(crate) struct ScoreDecay {
+    pub(crate) enabled: bool,
+    pub(crate) timer: Timer,
+}
+
+impl ScoreDecay {
+    pub(crate) fn every(seconds: f32) -> Self {
+        Self {
+            enabled: true,
+            timer: Timer::from_seconds(seconds, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Default for ScoreDecay {
+    fn default() -> Self {
+        let mut decay = Self::every(5.);
+        decay.enabled = false;
+        decay
+    }
+}
+
+/// When a match is over. Endless matches last until the window is closed.
+#[derive(Resource, Clone, Copy, Default)]
+pub(crate) enum MatchLimit {
+    #[default]
+    Endless,
+    /// First to `points`, with a lead of at least `win_by`.
+    Score { points: u32, win_by: u32 },
+    /// Highest score when the clock runs out.
+    Time { seconds: f32, tie: TieBreak },
+}
+
+impl MatchLimit {
+    /// Whether these scores end a first to match, which takes enough points and a wide enough lead.
+    pub(crate) fn won_at(self, scores: (u32, u32)) -> bool {
+        match self {
+            MatchLimit::Score { points, win_by } => {
+                scores.0.max(scores.1) >= points && scores.0.abs_diff(scores.1) >= win_by
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What a time limit match tied when the clock runs out ends in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum TieBreak {
+    /// Sudden death: the next point wins.
+    #[default]
+    Overtime,
+    Draw,
+}
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overtime" => Ok(TieBreak::Overtime),
+            "draw" => Ok(TieBreak::Draw),
+            _ => Err(format!(
+                "unknown tie break '{s}', expected overtime or draw"
+            )),
+        }
+    }
+}
+
+/// Game time played so far, which stops along with the simulation once the match is over.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct MatchClock {
+    pub(crate) elapsed: f32,
+    pub(crate) over: bool,
+}
+
+/// One match in [`HISTORY_PATH`], which holds a record per line so it can be read back even if a
+/// line gets corrupted.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct MatchRecord {
+    /// Names as typed, empty if the player didn't set one.
+    pub(crate) left: String,
+    pub(crate) right: String,
+    pub(crate) score: (u32, u32),
+    pub(crate) seconds: f32,
+    /// When the match ended, in seconds since the Unix epoch.
+    pub(crate) finished_at: u64,
+    /// Closed before reaching the match limit, so nobody won it.
+    #[serde(default)]
+    pub(crate) abandoned: bool,
+}
+
+impl MatchRecord {
+    fn winner(&self) -> Option<&str> {
+        if self.abandoned {
+            return None;
+        }
+        match self.score.0.cmp(&self.score.1) {
+            Ordering::Greater => Some(self.left.as_str()),
+            Ordering::Less => Some(self.right.as_str()),
+            Ordering::Equal => None,
+        }
+    }
+
+    pub(crate) fn summary(&self) -> String {
+        let left = PlayerNames::or_default(&self.left, "Player 1");
+        let right = PlayerNames::or_default(&self.right, "Player 2");
+        let result = match self.score.0.cmp(&self.score.1) {
+            _ if self.abandoned => "Abandoned".to_string(),
+            Ordering::Greater => format!("{left} won"),
+            Ordering::Less => format!("{right} won"),
+            Ordering::Equal => "Draw".to_string(),
+        };
+        let seconds = self.seconds as u64;
+        format!(
+            "{}  {left} {} - {} {right}  {result} in {}:{:02}",
+            format_date(self.finished_at),
+            self.score.0,
+            self.score.1,
+            seconds / 60,
+            seconds % 60,
+        )
+    }
+}
+
+/// Elo rating of every named player, updated after each match they finish.
+#[derive(Resource)]
+pub(crate) struct Ratings {
+    pub(crate) players: BTreeMap<String, f32>,
+    /// Most a single match can move a rating.
+    pub(crate) k_factor: f32,
+}
+
+impl Ratings {
+    pub(crate) fn load(k_factor: f32) -> Self {
+        let players = match fs::read_to_string(RATINGS_PATH) {
+            Ok(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+                eprintln!("Ignoring {RATINGS_PATH}: {err}");
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self { players, k_factor }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> f32 {
+        self.players.get(name).copied().unwrap_or(BASE_RATING)
+    }
+
+    /// Moves both ratings toward the `result` of `left` against `right`, further the more
+    /// unexpected it was.
+    fn record(&mut self, left: &str, right: &str, result: Ordering) {
+        let (left_rating, right_rating) = (self.get(left), self.get(right));
+        let expected = 1. / (1. + 10f32.powf((right_rating - left_rating) / 400.));
+        let actual = match result {
+            Ordering::Greater => 1.,
+            Ordering::Equal => 0.5,
+            Ordering::Less => 0.,
+        };
+        let change = self.k_factor * (actual - expected);
+        self.players.insert(left.to_string(), left_rating + change);
+        self.players
+            .insert(right.to_string(), right_rating - change);
+    }
+}
+
+/// Past matches, oldest first.
+#[derive(Resource, Default)]
+pub(crate) struct MatchHistory(pub(crate) Vec<MatchRecord>);
+
+impl MatchHistory {
+    pub(crate) fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(HISTORY_PATH) else {
+            return Self::default();
+        };
+        let records = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                ron::from_str(line)
+                    .inspect_err(|err| eprintln!("Ignoring a match in {HISTORY_PATH}: {err}"))
+                    .ok()
+            })
+            .collect();
+        Self(records)
+    }
+
+    pub(crate) fn push(&mut self, record: MatchRecord) {
+        self.0.push(record);
+        let excess = self.0.len().saturating_sub(HISTORY_LENGTH);
+        self.0.drain(..excess);
+    }
+
+    fn to_lines(&self) -> ron::Result<String> {
+        let mut contents = String::new();
+        for record in &self.0 {
+            contents += &ron::to_string(record)?;
+            contents.push('\n');
+        }
+        Ok(contents)
+    }
+
+    /// The most recent matches, followed by the win counts of named players.
+    pub(crate) fn summary(&self) -> String {
+        if self.0.is_empty() {
+            return "No matches played yet".to_string();
+        }
+        let mut lines = vec!["Recent matches".to_string()];
+        lines.extend(
+            self.0
+                .iter()
+                .rev()
+                .take(HISTORY_SHOWN)
+                .map(MatchRecord::summary),
+        );
+
+        let mut wins = HashMap::<&str, u32>::new();
+        for winner in self.0.iter().filter_map(MatchRecord::winner) {
+            let winner = winner.trim();
+            if !winner.is_empty() {
+                *wins.entry(winner).or_default() += 1;
+            }
+        }
+        if !wins.is_empty() {
+            let mut wins: Vec<_> = wins.into_iter().collect();
+            wins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+            let wins: Vec<_> = wins
+                .into_iter()
+                .map(|(name, count)| format!("{name} {count}"))
+                .collect();
+            lines.push(format!("Wins: {}", wins.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Squash-like variant where the ball bounces off a back wall once before a point is scored
+/// there, giving the defender a second chance to return it.
+#[derive(Resource)]
+pub(crate) struct VolleyRule {
+    pub(crate) enabled: bool,
+}
+
+/// Which goal, by the player it credits, the ball has bounced off since a paddle last hit it.
+#[derive(Component, Clone, Default)]
+pub(crate) struct Volley {
+    bounced_off: Option<usize>,
+}
+
+/// Pause after each goal while its effects play, before the ball is served. Skipping it with
+/// [`SKIP_CELEBRATION_KEY`](crate::effects::SKIP_CELEBRATION_KEY) is not recorded, so replays of
+/// games where that happened will not play back the same.
+#[derive(Resource, Default)]
+pub(crate) struct Celebration {
+    pub(crate) duration: f32,
+    /// Off in lockstep games, where only one peer would skip.
+    pub(crate) skippable: bool,
+}
+
+/// The ball while it waits at the center for the celebration to end.
+#[derive(Component, Clone)]
+pub(crate) struct Celebrating(pub(crate) Timer);
+
+/// Names shown next to the scores. Empty names fall back to "Player 1" and "Player 2".
+#[derive(Resource)]
+pub(crate) struct PlayerNames {
+    pub(crate) left: String,
+    pub(crate) right: String,
+}
+
+impl PlayerNames {
+    pub(crate) fn left(&self) -> &str {
+        Self::or_default(&self.left, "Player 1")
+    }
+
+    pub(crate) fn right(&self) -> &str {
+        Self::or_default(&self.right, "Player 2")
+    }
+
+    fn or_default<'a>(name: &'a str, default: &'a str) -> &'a str {
+        let name = name.trim();
+        if name.is_empty() { default } else { name }
+    }
+}
+
+/// Formats seconds since the Unix epoch as a UTC `YYYY-MM-DD` date.
+fn format_date(timestamp: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, with eras starting on March 1st.
+    let days = timestamp / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+pub(crate) fn score_goals(
+    mut commands: Commands,
+    mut scores: ResMut<Score>,
+    mut score_changed: EventWriter<ScoreChanged>,
+    mut collisions: EventWriter<BallCollision>,
+    mut rng: ResMut<GameRng>,
+    serve: Res<ServeConfig>,
+    seed: Res<GameSeed>,
+    limit: Res<MatchLimit>,
+    volley_rule: Res<VolleyRule>,
+    double: Option<Res<DoublePoints>>,
+    celebration: Res<Celebration>,
+    balls: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Interpolated,
+            &BallSize,
+            &mut Volley,
+            Has<ExtraBall>,
+            Has<Serving>,
+        ),
+        (With<Ball>, Without<Goal>),
+    >,
+    goals: Query<(&Transform, &Goal)>,
+) {
+    let mut double = double.map(|double| double.player);
+    'balls: for (
+        entity,
+        mut transform,
+        mut velocity,
+        mut interpolated,
+        size,
+        mut volley,
+        extra,
+        serving,
+    ) in balls
+    {
+        let bounding_circle = BoundingCircle::new(transform.translation.xy(), **size / 2.);
+        let start = interpolated.previous.xy();
+        // The player credited with a point, and the goal it was scored in unless it was a fault.
+        let mut point = None;
+        for (goal_transform, goal) in &goals {
+            let center = goal_transform.translation.xy();
+            let half_size = goal_transform.scale.xy() / 2.;
+            let bounding_box = Aabb2d::new(center, half_size);
+            let outward = goal_transform.translation.x.signum();
+            if velocity.x * outward <= 0. {
+                continue;
+            }
+            // Swept like the other colliders, so a fast ball can't pass through the goal line.
+            let entry = segment_entry(
+                start,
+                bounding_circle.center,
+                &Aabb2d::new(center, half_size + **size / 2.),
+            )
+            .or_else(|| bounding_circle.intersects(&bounding_box).then_some(1.));
+            let Some(entry) = entry else {
+                continue;
+            };
+            let position = bounding_box.closest_point(start.lerp(bounding_circle.center, entry));
+            let normal = Vec2::new(-outward, 0.);
+
+            if volley_rule.enabled && volley.bounced_off != Some(goal.player) {
+                volley.bounced_off = Some(goal.player);
+                velocity.x = -velocity.x;
+                collisions.write(BallCollision {
+                    ball: entity,
+                    position,
+                    normal,
+                    kind: CollisionKind::Wall,
+                });
+                continue 'balls;
+            }
+
+            point = Some((
+                goal.player,
+                Some(BallCollision {
+                    ball: entity,
+                    position,
+                    normal,
+                    kind: CollisionKind::Goal,
+                }),
+            ));
+            break;
+        }
+        let side = velocity.x.signum();
+        let (from, to) = (start.x * side, bounding_circle.center.x * side);
+        if point.is_none() && serving && from < SERVICE_LINE && to >= SERVICE_LINE {
+            commands.entity(entity).remove::<Serving>();
+            let crossing = start.lerp(bounding_circle.center, (SERVICE_LINE - from) / (to - from));
+            if serve
+                .fault_window()
+                .is_some_and(|zone| crossing.y.abs() > zone / 2.)
+            {
+                point = Some((usize::from(side > 0.), None));
+            }
+        }
+        let Some((player, goal)) = point else {
+            continue;
+        };
+
+        volley.bounced_off = None;
+        // Taken rather than read, as the removal only lands after every ball has been checked.
+        let doubled = double.take();
+        if doubled.is_some() {
+            commands.remove_resource::<DoublePoints>();
+        }
+        let points = if doubled == Some(player) { 2 } else { 1 };
+        let conceding = if player == 0 {
+            scores.0 += points;
+            ServeDirection::Right
+        } else {
+            scores.1 += points;
+            ServeDirection::Left
+        };
+        if let Some(goal) = goal {
+            collisions.write(goal);
+        }
+        score_changed.write(ScoreChanged);
+        // An extra ball scores like any other, but only the first is served again.
+        if extra {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        commands.insert_resource(Rally::default());
+        transform.translation = Vec3::ZERO;
+        interpolated.snap(Vec3::ZERO);
+        **velocity = serve.after_point(conceding, seed.serve, &scores, &limit, &mut **rng);
+        commands.entity(entity).insert(Serving);
+        if celebration.duration > 0. {
+            commands
+                .entity(entity)
+                .insert(Celebrating(Timer::from_seconds(
+                    celebration.duration,
+                    TimerMode::Once,
+                )));
+        }
+    }
+}
+
+fn decay_scores(
+    mut decay: ResMut<ScoreDecay>,
+    mut scores: ResMut<Score>,
+    mut score_changed: EventWriter<ScoreChanged>,
+    time: Res<Time>,
+) {
+    if !decay.enabled || !decay.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    if scores.0 == 0 && scores.1 == 0 {
+        return;
+    }
+    scores.0 = scores.0.saturating_sub(1);
+    scores.1 = scores.1.saturating_sub(1);
+    score_changed.write(ScoreChanged);
+}
+
+/// Ends the match once its limit is reached. Practice matches never end.
+pub(crate) fn check_match_limit(
+    mut clock: ResMut<MatchClock>,
+    limit: Res<MatchLimit>,
+    scores: Res<Score>,
+    practice: Res<Practice>,
+    time: Res<Time>,
+) {
+    clock.elapsed += time.delta_secs();
+    if practice.enabled {
+        return;
+    }
+    clock.over = match *limit {
+        MatchLimit::Endless => false,
+        MatchLimit::Score { .. } => limit.won_at((scores.0, scores.1)),
+        MatchLimit::Time { seconds, tie } => {
+            clock.elapsed >= seconds && (scores.0 != scores.1 || tie == TieBreak::Draw)
+        }
+    };
+}
+
+/// Adds the match to the history when the game is closed, as abandoned if it hadn't reached the
+/// match limit.
+pub(crate) fn record_match(
+    mut exits: EventReader<AppExit>,
+    mut history: ResMut<MatchHistory>,
+    mut ratings: ResMut<Ratings>,
+    mut saves: ResMut<PendingSaves>,
+    replay_mode: Res<ReplayMode>,
+    practice: Res<Practice>,
+    score: Res<Score>,
+    names: Res<PlayerNames>,
+    clock: Res<MatchClock>,
+) {
+    if exits.read().next().is_none()
+        || matches!(*replay_mode, ReplayMode::Playing { .. })
+        || practice.enabled
+    {
+        return;
+    }
+    save_match(
+        &mut history,
+        &mut ratings,
+        &mut saves,
+        &score,
+        &names,
+        clock.elapsed,
+        clock.over,
+    );
+}
+
+/// Adds a match to the history and the players' ratings, unless nobody scored. An unfinished one
+/// is recorded as abandoned and leaves the ratings alone.
+pub(crate) fn save_match(
+    history: &mut MatchHistory,
+    ratings: &mut Ratings,
+    saves: &mut PendingSaves,
+    score: &Score,
+    names: &PlayerNames,
+    seconds: f32,
+    finished: bool,
+) {
+    if (score.0, score.1) == (0, 0) {
+        return;
+    }
+    let finished_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    history.push(MatchRecord {
+        left: names.left.trim().to_string(),
+        right: names.right.trim().to_string(),
+        score: (score.0, score.1),
+        seconds,
+        finished_at,
+        abandoned: !finished,
+    });
+    match history.to_lines() {
+        Ok(contents) => saves.queue(HISTORY_PATH, contents),
+        Err(err) => error!("Failed to serialize match history: {err}"),
+    }
+
+    // Unnamed players would all share the default names' ratings.
+    let (left, right) = (names.left.trim(), names.right.trim());
+    if !finished || left.is_empty() || right.is_empty() || left == right {
+        return;
+    }
+    ratings.record(left, right, score.0.cmp(&score.1));
+    match ron::to_string(&ratings.players) {
+        Ok(contents) => saves.queue(RATINGS_PATH, contents),
+        Err(err) => error!("Failed to serialize ratings: {err}"),
+    }
+}
+
+fn end_celebrations(
+    mut commands: Commands,
+    balls: Query<(Entity, &mut Celebrating)>,
+    time: Res<Time>,
+) {
+    for (entity, mut celebrating) in balls {
+        if celebrating.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Celebrating>();
+        }
+    }
+}
+
+fn reset_volleys(
+    mut commands: Commands,
+    mut collisions: EventReader<BallCollision>,
+    mut volleys: Query<(&mut Volley, Has<Serving>), With<Ball>>,
+) {
+    for collision in collisions.read() {
+        if collision.kind == CollisionKind::Paddle
+            && let Ok((mut volley, serving)) = volleys.get_mut(collision.ball)
+        {
+            volley.bounced_off = None;
+            // A returned serve is in play, wherever it went.
+            if serving {
+                commands.entity(collision.ball).remove::<Serving>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ball::BALL_SPEED,
+        testing::{self, STEP},
+        wall::{WALL_LENGTH, WALL_OFFSET, WALL_WIDTH},
+    };
+    use rand::{SeedableRng, rngs::StdRng};
+
+    /// An app scoring goals under `serve`, with no goals in the arena.
+    fn referee_app(serve: ServeConfig) -> App {
+        let mut app = testing::app();
+        app.add_event::<ScoreChanged>()
+            .add_event::<BallCollision>()
+            .insert_resource(Score(0, 0))
+            .insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .insert_resource(serve)
+            .insert_resource(GameSeed {
+                seed: 0,
+                serve: ServeDirection::Right,
+            })
+            .insert_resource(VolleyRule { enabled: false })
+            .init_resource::<MatchLimit>()
+            .init_resource::<Celebration>()
+            .add_systems(FixedUpdate, score_goals);
+        app
+    }
+
+    /// Spawns a ball served to the right that has just crossed the service line at `y`.
+    fn serve_across(app: &mut App, y: f32) -> Entity {
+        let before = Vec3::new(SERVICE_LINE - 4., y, 0.);
+        app.world_mut()
+            .spawn((
+                Ball,
+                Transform::from_xyz(SERVICE_LINE + 4., y, 0.),
+                Velocity(Vec2::new(BALL_SPEED, 0.)),
+                Interpolated::new(before),
+                Serving,
+            ))
+            .id()
+    }
+
+    /// The goals behind both side walls, as the arena has them.
+    fn spawn_goals(app: &mut App) {
+        for (x, player) in [(-WALL_OFFSET, 1), (WALL_OFFSET, 0)] {
+            app.world_mut().spawn((
+                Transform::from_xyz(x, 0., 0.)
+                    .with_scale(Vec2::new(WALL_WIDTH, WALL_LENGTH).extend(1.)),
+                Goal { player },
+            ));
+        }
+    }
+
+    /// Spawns a ball that has just flown into the goal on the `side` of its sign.
+    fn shoot(app: &mut App, side: f32) -> Entity {
+        let ball = app.world_mut().spawn(Ball).id();
+        shoot_again(app, ball, side);
+        ball
+    }
+
+    fn shoot_again(app: &mut App, ball: Entity, side: f32) {
+        let before = Vec3::new(side * (WALL_OFFSET - 20.), 0., 0.);
+        app.world_mut().entity_mut(ball).insert((
+            Transform::from_xyz(side * WALL_OFFSET, 0., 0.),
+            Velocity(Vec2::new(side * BALL_SPEED, 0.)),
+            Interpolated::new(before),
+        ));
+    }
+
+    fn score(app: &App) -> (u32, u32) {
+        let score = app.world().resource::<Score>();
+        (score.0, score.1)
+    }
+
+    /// Serves straight across, so the zone is never widened to the cone.
+    fn faulting(zone: f32) -> ServeConfig {
+        ServeConfig {
+            fault_zone: Some(zone),
+            ..ServeConfig::with_cone_degrees(0.)
+        }
+    }
+
+    #[test]
+    fn serve_inside_the_zone_is_in_play() {
+        let mut app = referee_app(faulting(100.));
+        let ball = serve_across(&mut app, 40.);
+        app.update();
+        let score = app.world().resource::<Score>();
+        assert_eq!((score.0, score.1), (0, 0));
+        assert!(!app.world().entity(ball).contains::<Serving>());
+        let translation = app.world().get::<Transform>(ball).unwrap().translation;
+        assert_eq!(translation.x, SERVICE_LINE + 4.);
+    }
+
+    #[test]
+    fn serve_outside_the_zone_is_a_point_for_the_receiver() {
+        let mut app = referee_app(faulting(100.));
+        let ball = serve_across(&mut app, 60.);
+        app.update();
+        let score = app.world().resource::<Score>();
+        assert_eq!((score.0, score.1), (0, 1));
+        // Served again from the center, and checked again.
+        let translation = app.world().get::<Transform>(ball).unwrap().translation;
+        assert_eq!(translation, Vec3::ZERO);
+        assert!(app.world().entity(ball).contains::<Serving>());
+    }
+
+    #[test]
+    fn zones_narrower_than_the_cone_are_widened_to_it() {
+        let serve = ServeConfig {
+            fault_zone: Some(100.),
+            ..ServeConfig::with_cone_degrees(30.)
+        };
+        let spread = serve.spread();
+        assert!(spread > 120.);
+        let mut app = referee_app(serve);
+        serve_across(&mut app, spread / 2. - 1.);
+        app.update();
+        assert_eq!(score(&app), (0, 0));
+    }
+
+    #[test]
+    fn serves_never_fault_without_a_zone() {
+        let mut app = referee_app(ServeConfig::default());
+        serve_across(&mut app, 200.);
+        app.update();
+        let score = app.world().resource::<Score>();
+        assert_eq!((score.0, score.1), (0, 0));
+    }
+
+    #[test]
+    fn each_goal_scores_for_its_own_player() {
+        let mut app = referee_app(ServeConfig::default());
+        spawn_goals(&mut app);
+        let ball = shoot(&mut app, 1.);
+        app.update();
+        assert_eq!(score(&app), (1, 0));
+        let translation = app.world().get::<Transform>(ball).unwrap().translation;
+        assert_eq!(translation, Vec3::ZERO);
+
+        app.world_mut().entity_mut(ball).despawn();
+        shoot(&mut app, -1.);
+        app.update();
+        assert_eq!(score(&app), (1, 1));
+    }
+
+    #[test]
+    fn double_points_only_count_for_their_player_and_last_one_point() {
+        let mut app = referee_app(ServeConfig::default());
+        spawn_goals(&mut app);
+        app.insert_resource(DoublePoints::new(1));
+        let ball = shoot(&mut app, 1.);
+        app.update();
+        assert_eq!(score(&app), (1, 0));
+        assert!(!app.world().contains_resource::<DoublePoints>());
+
+        app.insert_resource(DoublePoints::new(1));
+        shoot_again(&mut app, ball, -1.);
+        app.update();
+        assert_eq!(score(&app), (1, 2));
+        assert!(!app.world().contains_resource::<DoublePoints>());
+
+        // Only the first of two balls scoring in the same step gets it.
+        app.insert_resource(DoublePoints::new(1));
+        shoot_again(&mut app, ball, -1.);
+        shoot(&mut app, -1.);
+        app.update();
+        assert_eq!(score(&app), (1, 5));
+        assert!(!app.world().contains_resource::<DoublePoints>());
+    }
+
+    #[test]
+    fn volleys_bounce_off_the_back_wall_once_per_return() {
+        let mut app = referee_app(ServeConfig::default());
+        app.insert_resource(VolleyRule { enabled: true })
+            .add_systems(FixedUpdate, reset_volleys.before(score_goals));
+        spawn_goals(&mut app);
+        let ball = shoot(&mut app, 1.);
+        app.update();
+        assert_eq!(score(&app), (0, 0));
+        assert!(app.world().get::<Velocity>(ball).unwrap().x < 0.);
+
+        // Returned by a paddle, so it gets another bounce.
+        app.world_mut().send_event(BallCollision {
+            ball,
+            position: Vec2::ZERO,
+            normal: Vec2::X,
+            kind: CollisionKind::Paddle,
+        });
+        shoot_again(&mut app, ball, 1.);
+        app.update();
+        assert_eq!(score(&app), (0, 0));
+
+        // Straight back in without a return scores.
+        shoot_again(&mut app, ball, 1.);
+        app.update();
+        assert_eq!(score(&app), (1, 0));
+    }
+
+    #[test]
+    fn first_to_matches_need_the_points_and_the_lead() {
+        let limit = MatchLimit::Score {
+            points: 11,
+            win_by: 2,
+        };
+        assert!(!limit.won_at((10, 9)));
+        assert!(limit.won_at((11, 9)));
+        assert!(!limit.won_at((11, 10)));
+        assert!(!limit.won_at((12, 11)));
+        assert!(limit.won_at((11, 13)));
+        assert!(!MatchLimit::Endless.won_at((100, 0)));
+    }
+
+    #[test]
+    fn timed_matches_end_on_the_clock_unless_tied_into_overtime() {
+        let over = |tie: TieBreak, score: Score| {
+            let mut app = testing::app();
+            app.insert_resource(MatchLimit::Time {
+                seconds: 4. * STEP,
+                tie,
+            })
+            .init_resource::<MatchClock>()
+            .insert_resource(score)
+            .insert_resource(Practice {
+                enabled: false,
+                hits: 0,
+            })
+            .add_systems(FixedUpdate, check_match_limit);
+            let mut ends = Vec::new();
+            for _ in 0..5 {
+                app.update();
+                ends.push(app.world().resource::<MatchClock>().over);
+            }
+            ends
+        };
+        let on_time = [false, false, false, true, true];
+        assert_eq!(over(TieBreak::Overtime, Score(2, 1)), on_time);
+        assert_eq!(over(TieBreak::Draw, Score(1, 1)), on_time);
+        assert_eq!(over(TieBreak::Overtime, Score(1, 1)), [false; 5]);
+    }
+
+    #[test]
+    fn scores_decay_every_period_down_to_zero() {
+        let mut app = testing::app();
+        app.add_event::<ScoreChanged>()
+            .insert_resource(Score(2, 1))
+            .insert_resource(ScoreDecay::every(4. * STEP))
+            .add_systems(FixedUpdate, decay_scores);
+        let mut decays = Vec::new();
+        for _ in 0..12 {
+            app.update();
+            let score = app.world().resource::<Score>();
+            let changed = app.world().resource::<Events<ScoreChanged>>();
+            decays.push((score.0, score.1, !changed.is_empty()));
+            app.world_mut()
+                .resource_mut::<Events<ScoreChanged>>()
+                .clear();
+        }
+        let after = |steps: usize| decays[steps - 1];
+        assert_eq!(after(3), (2, 1, false));
+        assert_eq!(after(4), (1, 0, true));
+        assert_eq!(after(7), (1, 0, false));
+        assert_eq!(after(8), (0, 0, true));
+        // Nothing left to take, so nothing changes.
+        assert_eq!(after(12), (0, 0, false));
+    }
+
+    #[test]
+    fn abandoned_matches_have_no_winner() {
+        let record = |abandoned| MatchRecord {
+            left: "Ann".to_string(),
+            right: "Bo".to_string(),
+            score: (3, 1),
+            seconds: 75.,
+            finished_at: 0,
+            abandoned,
+        };
+        assert_eq!(record(false).winner(), Some("Ann"));
+        assert!(record(false).summary().ends_with("Ann won in 1:15"));
+        assert_eq!(record(true).winner(), None);
+        assert!(record(true).summary().ends_with("Abandoned in 1:15"));
+        let history = MatchHistory(vec![record(true), record(false)]);
+        assert!(history.summary().ends_with("Wins: Ann 1"));
+    }
+
+    #[test]
+    fn only_finished_matches_change_the_ratings() {
+        let mut history = MatchHistory::default();
+        let mut ratings = Ratings {
+            players: BTreeMap::new(),
+            k_factor: 32.,
+        };
+        let mut saves = PendingSaves::default();
+        let names = PlayerNames {
+            left: "Ann".to_string(),
+            right: "Bo".to_string(),
+        };
+        save_match(
+            &mut history,
+            &mut ratings,
+            &mut saves,
+            &Score(2, 0),
+            &names,
+            30.,
+            false,
+        );
+        assert_eq!(history.0.len(), 1);
+        assert!(ratings.players.is_empty());
+        save_match(
+            &mut history,
+            &mut ratings,
+            &mut saves,
+            &Score(5, 3),
+            &names,
+            90.,
+            true,
+        );
+        assert_eq!(history.0.len(), 2);
+        assert_eq!(ratings.get("Ann"), BASE_RATING + 16.);
+        assert_eq!(ratings.get("Bo"), BASE_RATING - 16.);
+    }
+}
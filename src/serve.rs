@@ -0,0 +1,372 @@
+//! Serving the ball, and the seed that makes a game's serves reproducible.
+
+use crate::{
+    ball::BALL_SPEED,
+    score::{MatchLimit, Score},
+    settings::cli_arg,
+    wall::WALL_OFFSET,
+};
+use bevy::prelude::*;
+use rand::{Rng, rngs::StdRng};
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+pub(crate) const SERVE_CONE_DEGREES: f32 = 30.;
+const MAX_SERVE_CONE_DEGREES: f32 = 60.;
+pub(crate) const DEFAULT_SERVES_PER_TURN: u32 = 2;
+/// Distance from the center of the receiver's service line, where [`ServeConfig::fault_zone`] is
+/// checked.
+pub(crate) const SERVICE_LINE: f32 = WALL_OFFSET / 2.;
+
+#[derive(Resource, Clone, Deref, DerefMut)]
+pub(crate) struct GameRng(pub(crate) StdRng);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ServeDirection {
+    Left,
+    Right,
+}
+
+impl ServeDirection {
+    fn sign(self) -> f32 {
+        match self {
+            ServeDirection::Left => -1.,
+            ServeDirection::Right => 1.,
+        }
+    }
+
+    fn opposite(self) -> Self {
+        match self {
+            ServeDirection::Left => ServeDirection::Right,
+            ServeDirection::Right => ServeDirection::Left,
+        }
+    }
+
+    pub(crate) fn random(rng: &mut impl Rng) -> Self {
+        if rng.random() {
+            ServeDirection::Left
+        } else {
+            ServeDirection::Right
+        }
+    }
+
+    pub(crate) fn velocity(self, config: &ServeConfig, rng: &mut impl Rng) -> Vec2 {
+        let angle = rng.random_range(-config.cone..=config.cone);
+        let direction = Vec2::from_angle(angle);
+        Vec2::new(direction.x * self.sign(), direction.y) * config.speed
+    }
+}
+
+#[derive(Resource, Clone, Copy)]
+pub(crate) struct ServeConfig {
+    /// Half-angle in radians of the cone around the horizontal that serves are picked from.
+    pub(crate) cone: f32,
+    pub(crate) rule: ServeRule,
+    /// Serves to one side before switching under [`ServeRule::Alternating`].
+    pub(crate) serves_per_turn: u32,
+    /// Launch speed of a serve. Below the [`BallSpeed`](crate::ball::BallSpeed) it gives the receiver time to react, and the
+    /// first paddle hit brings the ball up to rally speed.
+    pub(crate) speed: f32,
+    /// Speed of a catch-up serve relative to a normal one.
+    pub(crate) catch_up_speed: f32,
+    /// Height of the window around the center serves have to cross the [`SERVICE_LINE`] in. A
+    /// serve outside it is a fault, and the point goes to the receiver. Never narrower than the
+    /// [`spread`](Self::spread), so a serve only faults when it's steered out, not by its angle.
+    pub(crate) fault_zone: Option<f32>,
+}
+
+impl ServeConfig {
+    pub(crate) fn with_cone_degrees(degrees: f32) -> Self {
+        Self {
+            cone: degrees.to_radians(),
+            rule: ServeRule::default(),
+            serves_per_turn: DEFAULT_SERVES_PER_TURN,
+            speed: BALL_SPEED,
+            catch_up_speed: 0.85,
+            fault_zone: None,
+        }
+    }
+
+    /// The serve after a point conceded by the `conceding` side, with `score` already updated.
+    /// `opening` is the direction of the first serve of the game.
+    pub(crate) fn after_point(
+        &self,
+        conceding: ServeDirection,
+        opening: ServeDirection,
+        score: &Score,
+        limit: &MatchLimit,
+        rng: &mut impl Rng,
+    ) -> Vec2 {
+        match self.rule {
+            ServeRule::Conceder => conceding.velocity(self, rng),
+            ServeRule::Alternating => {
+                // The score doubles as the serve counter, so it starts over with the game.
+                let served = score.0 + score.1;
+                let mut turn = served / self.serves_per_turn;
+                // At deuce, both one point from winning, the serve switches every point.
+                if let MatchLimit::Score { points, .. } = *limit {
+                    let deuce = 2 * points.saturating_sub(1);
+                    if served >= deuce {
+                        turn = deuce / self.serves_per_turn + (served - deuce);
+                    }
+                }
+                let direction = if turn.is_multiple_of(2) {
+                    opening
+                } else {
+                    opening.opposite()
+                };
+                direction.velocity(self, rng)
+            }
+            ServeRule::CatchUp => {
+                let trailing = match score.0.cmp(&score.1) {
+                    Ordering::Less => ServeDirection::Left,
+                    Ordering::Greater => ServeDirection::Right,
+                    Ordering::Equal => return ServeDirection::random(rng).velocity(self, rng),
+                };
+                trailing.velocity(self, rng) * self.catch_up_speed
+            }
+        }
+    }
+
+    /// Height the cone covers at the [`SERVICE_LINE`], where serves from the center cross it.
+    pub(crate) fn spread(&self) -> f32 {
+        2. * SERVICE_LINE * self.cone.tan()
+    }
+
+    /// The [`fault_zone`](Self::fault_zone), widened to the [`spread`](Self::spread) of a cone
+    /// that grew since it was set.
+    pub(crate) fn fault_window(&self) -> Option<f32> {
+        self.fault_zone.map(|zone| zone.max(self.spread()))
+    }
+
+    /// Serves too close to vertical would take ages to reach a paddle, so the cone is capped.
+    pub(crate) fn validate(self) -> Result<Self, String> {
+        let max = MAX_SERVE_CONE_DEGREES;
+        if (0. ..=max.to_radians()).contains(&self.cone) {
+            Ok(self)
+        } else {
+            Err(format!(
+                "cone {} must be between 0 and {max} degrees",
+                self.cone.to_degrees()
+            ))
+        }
+    }
+}
+
+/// On a ball that was just served, until it crosses the [`SERVICE_LINE`] or a paddle hits it.
+#[derive(Component, Clone)]
+pub(crate) struct Serving;
+
+/// Who the ball is served to after a point.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum ServeRule {
+    /// The player who just conceded, as in classic Pong.
+    #[default]
+    Conceder,
+    /// Whoever is behind, a little slower to keep games close. Ties are served at random.
+    CatchUp,
+    /// `serves_per_turn` serves in a row to each side whoever scores, as in table tennis.
+    Alternating,
+}
+
+impl FromStr for ServeRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "conceder" => Ok(ServeRule::Conceder),
+            "catch-up" => Ok(ServeRule::CatchUp),
+            "alternating" => Ok(ServeRule::Alternating),
+            _ => Err(format!(
+                "unknown serve rule '{s}', expected conceder, catch-up or alternating"
+            )),
+        }
+    }
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self::with_cone_degrees(SERVE_CONE_DEGREES)
+    }
+}
+
+/// Everything needed to reproduce a game, shareable as a short code such as `R0000BEEF`.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct GameSeed {
+    pub(crate) seed: u32,
+    pub(crate) serve: ServeDirection,
+}
+
+impl GameSeed {
+    fn random() -> Self {
+        Self {
+            seed: rand::random(),
+            serve: ServeDirection::random(&mut rand::rng()),
+        }
+    }
+
+    pub(crate) fn from_args() -> Self {
+        cli_arg("--seed").unwrap_or_else(Self::random)
+    }
+}
+
+impl fmt::Display for GameSeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let serve = match self.serve {
+            ServeDirection::Left => 'L',
+            ServeDirection::Right => 'R',
+        };
+        write!(f, "{serve}{:08X}", self.seed)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ShareCodeError {
+    Length(usize),
+    Serve(char),
+    Seed(String),
+}
+
+impl fmt::Display for ShareCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShareCodeError::Length(len) => write!(f, "expected 9 characters, got {len}"),
+            ShareCodeError::Serve(c) => write!(f, "serve must be L or R, got '{c}'"),
+            ShareCodeError::Seed(seed) => write!(f, "seed '{seed}' is not 8 hex digits"),
+        }
+    }
+}
+
+impl FromStr for GameSeed {
+    type Err = ShareCodeError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let code = code.trim();
+        let len = code.chars().count();
+        if len != 9 {
+            return Err(ShareCodeError::Length(len));
+        }
+        let mut chars = code.chars();
+        let serve = match chars.next() {
+            Some('L' | 'l') => ServeDirection::Left,
+            Some('R' | 'r') => ServeDirection::Right,
+            Some(c) => return Err(ShareCodeError::Serve(c)),
+            None => return Err(ShareCodeError::Length(0)),
+        };
+        let seed = chars.as_str();
+        if !seed.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ShareCodeError::Seed(seed.to_string()));
+        }
+        let seed =
+            u32::from_str_radix(seed, 16).map_err(|_| ShareCodeError::Seed(seed.to_string()))?;
+        Ok(Self { seed, serve })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Sides served to after each total of points from 0 to `points`, by the sign of the serve.
+    fn alternating_serves(serves_per_turn: u32, limit: MatchLimit, points: u32) -> Vec<f32> {
+        let config = ServeConfig {
+            rule: ServeRule::Alternating,
+            serves_per_turn,
+            ..default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..=points)
+            .map(|served| {
+                // Level as often as not, so a limit's deuce is reached. Who conceded is ignored.
+                let score = Score(served - served / 2, served / 2);
+                let velocity = config.after_point(
+                    ServeDirection::Left,
+                    ServeDirection::Right,
+                    &score,
+                    &limit,
+                    &mut rng,
+                );
+                velocity.x.signum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn serves_head_to_their_side_within_the_cone() {
+        let config = ServeConfig::with_cone_degrees(20.);
+        let mut rng = StdRng::seed_from_u64(0);
+        for direction in [ServeDirection::Left, ServeDirection::Right] {
+            for _ in 0..100 {
+                let velocity = direction.velocity(&config, &mut rng);
+                assert_eq!(velocity.x.signum(), direction.sign());
+                assert!((velocity.length() - config.speed).abs() < 1e-3);
+                let angle = (velocity.y / velocity.x.abs()).atan();
+                assert!(angle.abs() <= config.cone + 1e-6, "{velocity}");
+            }
+        }
+        assert!(
+            ServeConfig::with_cone_degrees(MAX_SERVE_CONE_DEGREES + 1.)
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn alternating_serves_switch_sides_every_turn() {
+        assert_eq!(
+            alternating_serves(2, MatchLimit::Endless, 6),
+            [1., 1., -1., -1., 1., 1., -1.]
+        );
+    }
+
+    #[test]
+    fn serves_switch_every_point_from_deuce() {
+        assert_eq!(
+            alternating_serves(3, MatchLimit::Endless, 6),
+            [1., 1., 1., -1., -1., -1., 1.]
+        );
+        // First to 4, so 3-3 after six points is deuce.
+        let limit = MatchLimit::Score {
+            points: 4,
+            win_by: 2,
+        };
+        assert_eq!(
+            alternating_serves(3, limit, 9),
+            [1., 1., 1., -1., -1., -1., 1., -1., 1., -1.]
+        );
+    }
+
+    #[test]
+    fn catch_up_serves_go_slower_to_whoever_is_behind() {
+        let config = ServeConfig {
+            rule: ServeRule::CatchUp,
+            ..default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut serve = |score: Score| {
+            // Who conceded doesn't matter, only the score does.
+            config.after_point(
+                ServeDirection::Right,
+                ServeDirection::Right,
+                &score,
+                &MatchLimit::Endless,
+                &mut rng,
+            )
+        };
+        let slower = config.speed * config.catch_up_speed;
+        let behind_left = serve(Score(2, 5));
+        assert!(behind_left.x < 0.);
+        assert!((behind_left.length() - slower).abs() < 1e-3);
+        let behind_right = serve(Score(5, 2));
+        assert!(behind_right.x > 0.);
+        assert!((behind_right.length() - slower).abs() < 1e-3);
+
+        let ties: Vec<_> = (0..20).map(|_| serve(Score(3, 3))).collect();
+        assert!(ties.iter().any(|velocity| velocity.x < 0.));
+        assert!(ties.iter().any(|velocity| velocity.x > 0.));
+        assert!(
+            ties.iter()
+                .all(|velocity| (velocity.length() - config.speed).abs() < 1e-3)
+        );
+    }
+}
@@ -0,0 +1,841 @@
+//! Settings from the command line and the settings file, and writing files back to disk.
+
+use crate::{
+    ball::{
+        AimedBounces, Assist, BALL_SPEED, BallPhysics, BallSpeed, CustomPhysics,
+        DEFAULT_RALLY_SPEEDUP_CAP, MAX_SKIN_WIDTH, PhysicsProfile, RallySpeedup, Restitution,
+        SkinWidth, SpeedFloor, SpinConfig, StallWatchdog,
+    },
+    effects::{
+        ArenaGrid, CameraZoom, EdgeGlow, EffectBudget, GRID_COLOR, MatchPointSlowMotion,
+        PreviewSides, ReduceMotion, ScreenShake, TrajectoryPreview,
+    },
+    interpolation::Interpolation,
+    paddle::{
+        AiConfig, AiDifficulty, AiSides, CatchConfig, ComebackBoost, DEFAULT_COMEBACK_BOOST,
+        KeyBindings, KeyLayout, KeyLayouts, MAX_COMEBACK_BOOST, MAX_GAMEPAD_DEADZONE, PADDLE_SIZE,
+        PaddleConfig, PaddleSpeed, StickCurve, StickResponse,
+    },
+    pickups::{BallSizeModifiers, PickupConfig, SpeedBursts},
+    practice::Practice,
+    replay::InstantReplay,
+    score::{
+        Celebration, DEFAULT_K_FACTOR, MatchHistory, MatchLimit, PlayerNames, Ratings, ScoreDecay,
+        VolleyRule,
+    },
+    serve::{DEFAULT_SERVES_PER_TURN, ServeConfig},
+    sound::MasterVolume,
+    theme::{Background, ThemeRegistry},
+    ui::{
+        FocusPause, MAX_SCORE_SIZE, MIN_SCORE_SIZE, ScoreAnchor, ScoreDisplay, ScoreLayout,
+        TEXT_SIZE,
+    },
+    wall::{BumperConfig, DampZoneConfig, MovableWalls, TopologyMode},
+};
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::{asset::LoadState, render::render_resource::TextureFormat, winit::WinitWindows};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, fs, path::PathBuf, str::FromStr};
+
+/// WCAG's minimum contrast for large graphics.
+const MIN_BACKGROUND_CONTRAST: f32 = 3.;
+
+pub(crate) const SETTINGS_PATH: &str = "settings.ron";
+
+/// Turns the command line flags and the settings file into the resources they configure. Flags win
+/// over the file, and out of range values fall back to the defaults with a warning.
+pub(crate) struct SettingsPlugin {
+    pub(crate) settings: SavedSettings,
+    /// Both paddles are played by the AI for `--simulate`.
+    pub(crate) simulated: bool,
+    /// Exported frames show exactly the physics of each step, so interpolation is off.
+    pub(crate) exporting: bool,
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = &self.settings;
+        let topology: TopologyMode = cli_arg("--topology").unwrap_or_default();
+        let gameplay = settings.gameplay.validated();
+        let paddle_speed = cli_arg("--paddle-speed")
+            .filter(|speed: &f32| {
+                let valid = *speed > 0.;
+                if !valid {
+                    eprintln!("Ignoring --paddle-speed {speed}: must be above 0");
+                }
+                valid
+            })
+            .map_or(PaddleSpeed(gameplay.paddle_speed), PaddleSpeed);
+        let paddles = PaddleConfig {
+            heights: ["--left-paddle-height", "--right-paddle-height"].map(|flag| {
+                cli_arg(flag)
+                    .and_then(|height| {
+                        PaddleConfig::validate_height(height)
+                            .inspect_err(|err| eprintln!("Ignoring {flag}: {err}"))
+                            .ok()
+                    })
+                    .unwrap_or(PADDLE_SIZE.y)
+            }),
+            ..cli_arg("--paddle-offset")
+                .map(PaddleConfig::with_offset)
+                .and_then(|config| {
+                    config
+                        .validate()
+                        .inspect_err(|err| eprintln!("Ignoring --paddle-offset: {err}"))
+                        .ok()
+                })
+                .unwrap_or_default()
+        };
+        let mut serve = ServeConfig {
+            rule: cli_arg("--serve-rule").unwrap_or_default(),
+            serves_per_turn: cli_arg("--serves-per-turn")
+                .filter(|&serves: &u32| {
+                    if serves == 0 {
+                        eprintln!("Ignoring --serves-per-turn 0: must be at least 1");
+                    }
+                    serves > 0
+                })
+                .unwrap_or(DEFAULT_SERVES_PER_TURN),
+            speed: cli_arg("--serve-speed")
+                .filter(|speed: &f32| {
+                    let max = gameplay.ball_speed;
+                    let valid = *speed > 0. && *speed <= max;
+                    if !valid {
+                        eprintln!(
+                            "Ignoring --serve-speed {speed}: must be above 0 and at most {max}"
+                        );
+                    }
+                    valid
+                })
+                .unwrap_or(gameplay.serve_speed),
+            ..cli_arg("--serve-cone")
+                .map(ServeConfig::with_cone_degrees)
+                .and_then(|config| {
+                    config
+                        .validate()
+                        .inspect_err(|err| eprintln!("Ignoring --serve-cone: {err}"))
+                        .ok()
+                })
+                .unwrap_or_default()
+        };
+        let pickups = PickupConfig {
+            enabled: cli_flag("--pickups"),
+            freeze_duration: cli_arg("--freeze-duration")
+                .filter(|seconds: &f32| *seconds > 0.)
+                .unwrap_or(PickupConfig::default().freeze_duration),
+            ..default()
+        };
+        let difficulty: Option<AiDifficulty> = cli_arg("--ai-difficulty");
+        let ai = AiConfig {
+            sides: if self.simulated {
+                AiSides::Both
+            } else {
+                cli_arg("--ai").unwrap_or_default()
+            },
+            mode: cli_arg("--ai-mode")
+                .or(difficulty.map(AiDifficulty::mode))
+                .unwrap_or_default(),
+            noise: cli_arg("--ai-noise")
+                .or(difficulty.map(AiDifficulty::noise))
+                .unwrap_or_default(),
+            reaction: difficulty.map_or(0., AiDifficulty::reaction),
+        };
+        let score_decay = cli_arg("--score-decay")
+            .filter(|seconds: &f32| *seconds > 0.)
+            .map_or_else(ScoreDecay::default, ScoreDecay::every);
+        let practice = Practice {
+            enabled: cli_flag("--practice"),
+            hits: 0,
+        };
+        let bumpers = BumperConfig {
+            enabled: cli_flag("--bumpers"),
+        };
+        let damp_zones = DampZoneConfig {
+            zones: cli_args("--damp-zone"),
+        };
+        let movable_walls = MovableWalls {
+            enabled: cli_flag("--movable-walls"),
+            ..default()
+        };
+        let mut restitution = cli_arg("--restitution")
+            .filter(|restitution: &f32| {
+                let valid = *restitution > 0. && *restitution <= 1.;
+                if !valid {
+                    eprintln!(
+                        "Ignoring --restitution {restitution}: must be above 0 and at most 1"
+                    );
+                }
+                valid
+            })
+            .map_or_else(Restitution::default, Restitution);
+        let skin = cli_arg("--skin-width")
+            .filter(|skin: &f32| {
+                let valid = (0. ..=MAX_SKIN_WIDTH).contains(skin);
+                if !valid {
+                    eprintln!(
+                        "Ignoring --skin-width {skin}: must be between 0 and {MAX_SKIN_WIDTH}"
+                    );
+                }
+                valid
+            })
+            .map_or_else(SkinWidth::default, SkinWidth);
+        let time_limit = cli_arg("--time-limit").filter(|seconds: &f32| {
+            let valid = *seconds > 0.;
+            if !valid {
+                eprintln!("Ignoring --time-limit {seconds}: must be above 0");
+            }
+            valid
+        });
+        let win_by = cli_arg("--win-by")
+            .filter(|&lead: &u32| {
+                if lead == 0 {
+                    eprintln!("Ignoring --win-by 0: must be at least 1");
+                }
+                lead > 0
+            })
+            .unwrap_or(gameplay.win_by);
+        let limit = match (
+            cli_arg("--first-to").filter(|&points: &u32| points > 0),
+            time_limit,
+        ) {
+            (Some(points), time_limit) => {
+                if time_limit.is_some() {
+                    eprintln!("Ignoring --time-limit: --first-to is also set");
+                }
+                MatchLimit::Score { points, win_by }
+            }
+            (None, Some(seconds)) => MatchLimit::Time {
+                seconds,
+                tie: cli_arg("--tie").unwrap_or_default(),
+            },
+            (None, None) => {
+                gameplay
+                    .first_to
+                    .map_or(MatchLimit::Endless, |points| MatchLimit::Score {
+                        points,
+                        win_by,
+                    })
+            }
+        };
+        let k_factor = cli_arg("--elo-k")
+            .filter(|k: &f32| {
+                let valid = *k > 0.;
+                if !valid {
+                    eprintln!("Ignoring --elo-k {k}: must be above 0");
+                }
+                valid
+            })
+            .unwrap_or(DEFAULT_K_FACTOR);
+        let mut speed_floor = cli_arg("--speed-floor")
+            .filter(|floor: &f32| {
+                let valid = (0. ..=BALL_SPEED).contains(floor);
+                if !valid {
+                    eprintln!("Ignoring --speed-floor {floor}: must be between 0 and {BALL_SPEED}");
+                }
+                valid
+            })
+            .map_or_else(SpeedFloor::default, SpeedFloor);
+        let comeback = ComebackBoost {
+            margin: cli_arg("--comeback-margin").filter(|&margin: &u32| {
+                if margin == 0 {
+                    eprintln!("Ignoring --comeback-margin 0: must be at least 1");
+                }
+                margin > 0
+            }),
+            max: cli_arg("--comeback-boost")
+                .filter(|boost: &f32| {
+                    let valid = (0. ..=MAX_COMEBACK_BOOST).contains(boost);
+                    if !valid {
+                        eprintln!(
+                            "Ignoring --comeback-boost {boost}: must be between 0 and {MAX_COMEBACK_BOOST}"
+                        );
+                    }
+                    valid
+                })
+                .unwrap_or(DEFAULT_COMEBACK_BOOST),
+        };
+        let rally_speedup = RallySpeedup {
+            per_hit: cli_arg("--rally-speedup")
+                .filter(|per_hit: &f32| {
+                    let valid = (0. ..=1.).contains(per_hit);
+                    if !valid {
+                        eprintln!("Ignoring --rally-speedup {per_hit}: must be between 0 and 1");
+                    }
+                    valid
+                })
+                .unwrap_or_default(),
+            cap: cli_arg("--rally-speedup-cap")
+                .filter(|cap: &f32| {
+                    let valid = *cap > 0.;
+                    if !valid {
+                        eprintln!("Ignoring --rally-speedup-cap {cap}: must be above 0");
+                    }
+                    valid
+                })
+                .unwrap_or(DEFAULT_RALLY_SPEEDUP_CAP),
+        };
+        let random_bounces = cli_flag("--random-bounces");
+        if random_bounces && cli_flag("--aimed-bounces") {
+            eprintln!("Ignoring --aimed-bounces: --random-bounces is also set");
+        }
+        let mut aimed = AimedBounces {
+            enabled: !random_bounces,
+        };
+        let profile: PhysicsProfile = cli_arg("--physics").unwrap_or(settings.physics);
+        let custom = BallPhysics {
+            serve_speed: serve.speed,
+            serve_cone: serve.cone,
+            restitution: restitution.0,
+            speed_floor: speed_floor.0,
+            aimed_bounces: aimed.enabled,
+        };
+        if let Some(physics) = profile.physics() {
+            for flag in [
+                "--serve-speed",
+                "--serve-cone",
+                "--restitution",
+                "--speed-floor",
+                "--aimed-bounces",
+                "--random-bounces",
+            ] {
+                if cli_flag(flag) {
+                    eprintln!(
+                        "Ignoring {flag}: the {} physics profile is set",
+                        profile.name()
+                    );
+                }
+            }
+            physics.apply(&mut serve, &mut restitution, &mut speed_floor, &mut aimed);
+        }
+        // Checked against the cone the profile settled on.
+        serve.fault_zone = cli_arg("--serve-fault-zone").filter(|zone: &f32| {
+            let spread = serve.spread();
+            let valid = *zone > 0. && *zone >= spread;
+            if !valid {
+                eprintln!(
+                    "Ignoring --serve-fault-zone {zone}: must be above 0 and at least {spread}, \
+                     the height the serve cone covers at the service line"
+                );
+            }
+            valid
+        });
+        let volley = VolleyRule {
+            enabled: cli_flag("--volley"),
+        };
+        let catch = CatchConfig {
+            enabled: cli_flag("--catch"),
+            ..default()
+        };
+        let spin = SpinConfig {
+            enabled: cli_flag("--spin"),
+            kick: cli_arg("--spin-kick")
+                .filter(|speed: &f32| {
+                    let valid = *speed >= 0.;
+                    if !valid {
+                        eprintln!("Ignoring --spin-kick {speed}: must be at least 0");
+                    }
+                    valid
+                })
+                .unwrap_or(SpinConfig::default().kick),
+            per_paddle_speed: cli_arg("--spin-curve")
+                .filter(|rate: &f32| {
+                    let valid = *rate >= 0.;
+                    if !valid {
+                        eprintln!("Ignoring --spin-curve {rate}: must be at least 0");
+                    }
+                    valid
+                })
+                .unwrap_or(SpinConfig::default().per_paddle_speed),
+            ..default()
+        };
+        let stall = StallWatchdog {
+            nudge: cli_arg("--stall-nudge")
+                .filter(|speed: &f32| *speed >= 0.)
+                .unwrap_or(StallWatchdog::default().nudge),
+            ..default()
+        };
+        let background = cli_arg::<Background>("--background").or_else(|| {
+            let hex = settings.background.as_deref()?;
+            hex.parse()
+                .inspect_err(|err| eprintln!("Ignoring background in {SETTINGS_PATH}: {err}"))
+                .ok()
+        });
+        if let Some(background) = background {
+            let contrast = background.contrast_with_white();
+            if contrast < MIN_BACKGROUND_CONTRAST {
+                eprintln!(
+                    "Background contrast against white is only {contrast:.1}:1, the ball and paddles may be hard to see"
+                );
+            }
+        }
+        let keys = KeyLayouts {
+            left: settings.left_keys,
+            right: settings.right_keys,
+            left_custom: settings.left_custom_keys,
+            right_custom: settings.right_custom_keys,
+        };
+        let size_modifiers = BallSizeModifiers {
+            enabled: cli_flag("--size-modifiers"),
+            ..default()
+        };
+        let speed_bursts = SpeedBursts {
+            enabled: cli_flag("--speed-bursts"),
+            every: cli_arg("--burst-every")
+                .filter(|seconds: &f32| *seconds > 0.)
+                .unwrap_or(SpeedBursts::default().every),
+            scale: cli_arg("--burst-scale")
+                .filter(|scale: &f32| *scale >= 1.)
+                .unwrap_or(SpeedBursts::default().scale),
+            ..default()
+        };
+
+        app.insert_resource(topology)
+            .insert_resource(score_decay)
+            .insert_resource(paddles)
+            .insert_resource(serve)
+            .insert_resource(size_modifiers)
+            .insert_resource(speed_bursts)
+            .insert_resource(catch)
+            .insert_resource(stall)
+            .insert_resource(spin)
+            .insert_resource(bumpers)
+            .insert_resource(damp_zones)
+            .insert_resource(movable_walls)
+            .insert_resource(practice)
+            .insert_resource(volley)
+            .insert_resource(restitution)
+            .insert_resource(skin)
+            .insert_resource(speed_floor)
+            .insert_resource(aimed)
+            .insert_resource(profile)
+            .insert_resource(SavedChoices {
+                physics: settings.physics,
+                background: settings.background.clone(),
+            })
+            .insert_resource(CustomPhysics(custom))
+            .insert_resource(rally_speedup)
+            .insert_resource(comeback)
+            .insert_resource(limit)
+            .insert_resource(
+                background.map_or_else(ClearColor::default, |background| ClearColor(background.0)),
+            )
+            .insert_resource(ai)
+            .insert_resource(pickups)
+            .insert_resource(Interpolation {
+                enabled: settings.interpolation && !self.exporting,
+                ..default()
+            })
+            .insert_resource(CameraZoom {
+                enabled: settings.camera_zoom,
+                ..default()
+            })
+            .insert_resource(FocusPause {
+                resume_on_focus: settings.resume_on_focus,
+            })
+            .insert_resource(Assist {
+                strength: settings.assist.clamp(0., 1.),
+                ..default()
+            })
+            .insert_resource(ScoreDisplay {
+                pips: settings.score_pips,
+                count: cli_arg("--pip-count")
+                    .filter(|count: &u32| *count > 0)
+                    .unwrap_or(ScoreDisplay::default().count),
+            })
+            .insert_resource(StickResponse {
+                deadzone: settings.stick_deadzone.clamp(0., MAX_GAMEPAD_DEADZONE),
+                curve: settings.stick_curve,
+            })
+            .insert_resource(ScoreLayout {
+                anchor: settings.score_anchor,
+                size: settings.score_size.clamp(MIN_SCORE_SIZE, MAX_SCORE_SIZE),
+                color: settings
+                    .score_color
+                    .as_deref()
+                    .and_then(|hex| {
+                        hex.parse::<Background>()
+                            .inspect_err(|err| {
+                                eprintln!("Ignoring score color in {SETTINGS_PATH}: {err}")
+                            })
+                            .ok()
+                    })
+                    .map_or(Color::WHITE, |color| color.0),
+            })
+            .insert_resource(EffectBudget(settings.effect_budget))
+            .insert_resource(TrajectoryPreview {
+                sides: settings.trajectory_preview,
+            })
+            .insert_resource(EdgeGlow {
+                intensity: settings.edge_glow.clamp(0., 1.),
+            })
+            .insert_resource(MasterVolume {
+                level: settings.volume.clamp(0., 1.),
+            })
+            .insert_resource(ScreenShake {
+                intensity: settings.screen_shake.clamp(0., 1.),
+            })
+            .insert_resource(MatchPointSlowMotion {
+                enabled: settings.match_point_slow_motion,
+            })
+            .insert_resource(ReduceMotion {
+                enabled: settings.reduce_motion,
+            })
+            .insert_resource(Celebration {
+                duration: settings.celebration.max(0.),
+                skippable: true,
+            })
+            .insert_resource(ArenaGrid {
+                cell: settings.grid_cell.max(0.),
+                color: settings
+                    .grid_color
+                    .as_deref()
+                    .and_then(|hex| {
+                        hex.parse::<Background>()
+                            .inspect_err(|err| {
+                                eprintln!("Ignoring grid color in {SETTINGS_PATH}: {err}")
+                            })
+                            .ok()
+                    })
+                    .map_or(GRID_COLOR, |color| color.0),
+            })
+            .insert_resource(InstantReplay {
+                enabled: settings.instant_replay,
+                ..default()
+            })
+            .insert_resource(keys)
+            .insert_resource(paddle_speed)
+            .insert_resource(BallSpeed(gameplay.ball_speed))
+            .insert_resource(settings.gameplay)
+            .insert_resource(PlayerNames {
+                left: settings.left_name.clone(),
+                right: settings.right_name.clone(),
+            })
+            .insert_resource(MatchHistory::load())
+            .insert_resource(Ratings::load(k_factor))
+            .insert_resource(ThemeRegistry::load(settings.theme.as_deref()))
+            .init_resource::<PendingSaves>();
+    }
+}
+
+/// Files waiting to be written to disk, keyed by path. Flushing drains the queue, so it is safe to
+/// flush more than once.
+#[derive(Resource, Default)]
+pub(crate) struct PendingSaves(HashMap<PathBuf, String>);
+
+impl PendingSaves {
+    pub(crate) fn queue(&mut self, path: impl Into<PathBuf>, contents: String) {
+        self.0.insert(path.into(), contents);
+    }
+
+    pub(crate) fn flush(&mut self) {
+        for (path, contents) in self.0.drain() {
+            match fs::write(&path, contents) {
+                Ok(()) => info!("Saved {}", path.display()),
+                Err(err) => error!("Failed to save {}: {err}", path.display()),
+            }
+        }
+    }
+}
+
+/// Everything in [`SETTINGS_PATH`]. Most of it mirrors the settings menu, but the stick tuning,
+/// the score layout and color, the grid color, custom keys, the window title and icon and the
+/// [`GameplayDefaults`] have no menu entry and are only set by editing the file.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SavedSettings {
+    pub(crate) interpolation: bool,
+    pub(crate) camera_zoom: bool,
+    pub(crate) resume_on_focus: bool,
+    pub(crate) assist: f32,
+    pub(crate) score_pips: bool,
+    pub(crate) effect_budget: usize,
+    pub(crate) stick_deadzone: f32,
+    pub(crate) stick_curve: StickCurve,
+    pub(crate) score_anchor: ScoreAnchor,
+    pub(crate) score_size: f32,
+    /// Hex color of the score text.
+    pub(crate) score_color: Option<String>,
+    pub(crate) instant_replay: bool,
+    pub(crate) edge_glow: f32,
+    pub(crate) screen_shake: f32,
+    pub(crate) volume: f32,
+    pub(crate) match_point_slow_motion: bool,
+    pub(crate) reduce_motion: bool,
+    pub(crate) physics: PhysicsProfile,
+    pub(crate) grid_cell: f32,
+    pub(crate) celebration: f32,
+    /// Hex color of the grid lines, with alpha.
+    pub(crate) grid_color: Option<String>,
+    pub(crate) trajectory_preview: PreviewSides,
+    /// Name of the theme file in `THEMES_DIR` without its extension, or the built-in look when unset.
+    pub(crate) theme: Option<String>,
+    pub(crate) left_keys: KeyLayout,
+    pub(crate) right_keys: KeyLayout,
+    /// Up, down and catch key names replacing a side's preset, like `["KeyW", "KeyS", "KeyD"]`.
+    /// Picking a preset in the menu clears them.
+    pub(crate) left_custom_keys: Option<KeyBindings>,
+    pub(crate) right_custom_keys: Option<KeyBindings>,
+    pub(crate) left_name: String,
+    pub(crate) right_name: String,
+    /// Hex color of the arena background, or Bevy's default when unset.
+    pub(crate) background: Option<String>,
+    pub(crate) window_title: Option<String>,
+    /// Asset path of the window icon, or no icon when unset.
+    pub(crate) window_icon: Option<String>,
+    pub(crate) gameplay: GameplayDefaults,
+}
+
+impl SavedSettings {
+    pub(crate) fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(SETTINGS_PATH) else {
+            return Self::default();
+        };
+        ron::from_str(&contents).unwrap_or_else(|err| {
+            eprintln!("Ignoring {SETTINGS_PATH}: {err}");
+            Self::default()
+        })
+    }
+}
+
+impl Default for SavedSettings {
+    fn default() -> Self {
+        Self {
+            interpolation: Interpolation::default().enabled,
+            camera_zoom: CameraZoom::default().enabled,
+            resume_on_focus: FocusPause::default().resume_on_focus,
+            assist: Assist::default().strength,
+            score_pips: ScoreDisplay::default().pips,
+            effect_budget: EffectBudget::default().0,
+            stick_deadzone: StickResponse::default().deadzone,
+            score_anchor: ScoreAnchor::default(),
+            score_size: TEXT_SIZE,
+            score_color: None,
+            stick_curve: StickResponse::default().curve,
+            instant_replay: InstantReplay::default().enabled,
+            edge_glow: EdgeGlow::default().intensity,
+            screen_shake: ScreenShake::default().intensity,
+            volume: MasterVolume::default().level,
+            match_point_slow_motion: MatchPointSlowMotion::default().enabled,
+            reduce_motion: ReduceMotion::default().enabled,
+            physics: PhysicsProfile::default(),
+            grid_cell: 0.,
+            celebration: Celebration::default().duration,
+            grid_color: None,
+            trajectory_preview: PreviewSides::default(),
+            theme: None,
+            left_keys: KeyLayouts::default().left,
+            right_keys: KeyLayouts::default().right,
+            left_custom_keys: None,
+            right_custom_keys: None,
+            left_name: String::new(),
+            right_name: String::new(),
+            background: None,
+            window_title: None,
+            window_icon: None,
+            gameplay: GameplayDefaults::default(),
+        }
+    }
+}
+
+/// Saved values a flag can override for one session. They hold what the file said until the menu
+/// changes them, and are what gets saved back instead of the flag's value.
+#[derive(Resource)]
+pub(crate) struct SavedChoices {
+    pub(crate) physics: PhysicsProfile,
+    /// Hex color, unset to keep Bevy's default.
+    pub(crate) background: Option<String>,
+}
+
+/// Gameplay tuning from the settings file, each value used when its flag isn't given. Like the
+/// flags, they must match between online peers and when playing back a replay.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub(crate) struct GameplayDefaults {
+    paddle_speed: f32,
+    /// Speed of the ball off a paddle, which serves can't exceed.
+    ball_speed: f32,
+    serve_speed: f32,
+    /// Points that win a match, or an endless match when unset.
+    first_to: Option<u32>,
+    /// Lead the winner of a first to match needs, 2 to play on past a tie one point from winning.
+    win_by: u32,
+}
+
+impl GameplayDefaults {
+    /// The same values with any out of range one replaced by its default.
+    fn validated(self) -> Self {
+        let default = Self::default();
+        let paddle_speed = if self.paddle_speed > 0. {
+            self.paddle_speed
+        } else {
+            eprintln!("Ignoring paddle speed in {SETTINGS_PATH}: must be above 0");
+            default.paddle_speed
+        };
+        let ball_speed = if self.ball_speed > 0. {
+            self.ball_speed
+        } else {
+            eprintln!("Ignoring ball speed in {SETTINGS_PATH}: must be above 0");
+            default.ball_speed
+        };
+        let serve_speed = if self.serve_speed > 0. && self.serve_speed <= ball_speed {
+            self.serve_speed
+        } else {
+            eprintln!(
+                "Ignoring serve speed in {SETTINGS_PATH}: must be above 0 and at most {ball_speed}"
+            );
+            default.serve_speed.min(ball_speed)
+        };
+        let first_to = self.first_to.filter(|&points| {
+            if points == 0 {
+                eprintln!("Ignoring first to in {SETTINGS_PATH}: must be at least 1");
+            }
+            points > 0
+        });
+        let win_by = if self.win_by > 0 {
+            self.win_by
+        } else {
+            eprintln!("Ignoring win by in {SETTINGS_PATH}: must be at least 1");
+            default.win_by
+        };
+        Self {
+            paddle_speed,
+            ball_speed,
+            serve_speed,
+            first_to,
+            win_by,
+        }
+    }
+}
+
+impl Default for GameplayDefaults {
+    fn default() -> Self {
+        Self {
+            paddle_speed: PaddleSpeed::default().0,
+            ball_speed: BALL_SPEED,
+            serve_speed: BALL_SPEED,
+            first_to: None,
+            win_by: 1,
+        }
+    }
+}
+
+pub(crate) fn cli_flag(flag: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == flag)
+}
+
+pub(crate) fn cli_arg<T>(flag: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    cli_args(flag).into_iter().next()
+}
+
+/// Every valid value of a flag that can be given more than once.
+fn cli_args<T>(flag: &str) -> Vec<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let mut values = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg != flag {
+            continue;
+        }
+        match args.next().as_deref().map(str::parse) {
+            Some(Ok(value)) => values.push(value),
+            Some(Err(err)) => eprintln!("Ignoring {flag}: {err}"),
+            None => eprintln!("Ignoring {flag}: missing value"),
+        }
+    }
+    values
+}
+
+/// Window title and icon, for branding builds such as a tournament's. Unset fields keep the
+/// defaults.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct WindowBranding {
+    pub(crate) title: Option<String>,
+    /// Asset path of the icon image.
+    pub(crate) icon: Option<String>,
+}
+
+/// Icon still loading, set on the window once it is ready.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+pub(crate) struct WindowIcon(pub(crate) Handle<Image>);
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Resolution {
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |dimension: &str| {
+            dimension
+                .parse::<u32>()
+                .ok()
+                .filter(|&pixels| pixels > 0)
+                .map(|pixels| pixels as f32)
+        };
+        s.split_once('x')
+            .and_then(|(width, height)| Some((parse(width)?, parse(height)?)))
+            .map(|(width, height)| Self { width, height })
+            .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{s}'"))
+    }
+}
+
+/// Waits for the icon to load, then sets it on every window. A missing or unreadable icon is
+/// skipped with a warning, leaving the platform default.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn set_window_icon(
+    mut commands: Commands,
+    icon: Res<WindowIcon>,
+    images: Res<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    windows: NonSend<WinitWindows>,
+) {
+    if let LoadState::Failed(err) = asset_server.load_state(&icon.0) {
+        warn!("Failed to load the window icon: {err}");
+        commands.remove_resource::<WindowIcon>();
+        return;
+    }
+    let Some(image) = images.get(&icon.0) else {
+        return;
+    };
+    if windows.windows.is_empty() {
+        return;
+    }
+    commands.remove_resource::<WindowIcon>();
+
+    let Some(rgba) = image
+        .convert(TextureFormat::Rgba8UnormSrgb)
+        .and_then(|image| image.data)
+    else {
+        warn!("Failed to convert the window icon to RGBA");
+        return;
+    };
+    match winit::window::Icon::from_rgba(rgba, image.width(), image.height()) {
+        Ok(icon) => {
+            for window in windows.windows.values() {
+                window.set_window_icon(Some(icon.clone()));
+            }
+        }
+        Err(err) => warn!("Failed to set the window icon: {err}"),
+    }
+}
+
+pub(crate) fn flush_saves_on_exit(
+    mut exits: EventReader<AppExit>,
+    mut saves: ResMut<PendingSaves>,
+) {
+    if exits.read().next().is_some() {
+        saves.flush();
+    }
+}
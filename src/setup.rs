@@ -0,0 +1,435 @@
+//! Spawning the arena, the paddles and the HUD at startup.
+
+use crate::{
+    ball::{BALL_SIZE, Ball, Collider, Velocity},
+    effects::{EDGE_GLOW_WIDTH, EdgeGlowPanel, GoalFlash, ParticleMesh},
+    interpolation::Interpolated,
+    paddle::{Ai, AiConfig, Paddle, PaddleConfig, PaddleController},
+    pickups::DOUBLE_POINTS_COLOR,
+    practice::{
+        Backboard, BallMachineFeed, Practice, TARGET_COLOR, TARGET_SIZE, Target,
+        random_target_position,
+    },
+    replay::{REPLAY_STATUS_TOP, ReplayStatusText},
+    score::{MatchHistory, Score},
+    serve::{GameRng, GameSeed, ServeConfig, Serving},
+    ui::{
+        BallMachineMenu, BurstBanner, COMBO_TEXT_SIZE, COMBO_TIERS, COUNTDOWN_TEXT_SIZE, ClockText,
+        ComboText, ControlsHintText, CountdownText, DoublePointsBanner, HINT_TEXT_SIZE,
+        HistoryMenu, NameEntry, PIP_GAP, PIP_ROWS_TOP, PipRow, ResultBanner, SERVE_INDICATOR_COLOR,
+        SERVE_INDICATOR_SIZE, SERVE_INDICATOR_Y, ScoreText, SeedText, ServeIndicator, Settings,
+        SettingsMenu, TEXT_PADDING, TEXT_SIZE, spawn_menu,
+    },
+    wall::{
+        BUMPER_INSET, BUMPER_LENGTH, Bumper, BumperConfig, DAMP_ZONE_COLOR, DampZone,
+        DampZoneConfig, Goal, MovableWall, MovableWalls, TopologyMode, WALL_LENGTH,
+        WALL_NUDGE_RANGE, WALL_NUDGE_SPEED, WALL_OFFSET, WALL_WIDTH, Wall,
+    },
+};
+use bevy::prelude::*;
+
+/// Spawns the arena once at startup, headless runs included.
+pub struct SetupPlugin;
+
+impl Plugin for SetupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup);
+    }
+}
+
+pub(crate) fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    seed: Res<GameSeed>,
+    topology: Res<TopologyMode>,
+    settings: Settings,
+    paddles: Res<PaddleConfig>,
+    serve: Res<ServeConfig>,
+    ai: Res<AiConfig>,
+    bumpers: Res<BumperConfig>,
+    damp_zones: Res<DampZoneConfig>,
+    movable_walls: Res<MovableWalls>,
+    practice: Res<Practice>,
+    history: Res<MatchHistory>,
+    ball_machine: Res<BallMachineFeed>,
+    mut rng: ResMut<GameRng>,
+) {
+    info!("Share code: {}", *seed);
+
+    commands.spawn(Camera2d);
+    commands.insert_resource(ParticleMesh(meshes.add(Circle::default())));
+
+    commands.spawn((
+        Mesh2d(meshes.add(Circle::default())),
+        MeshMaterial2d(materials.add(Color::WHITE)),
+        Transform::from_xyz(0., 0., 0.).with_scale(Vec2::splat(BALL_SIZE).extend(1.)),
+        Ball,
+        Velocity(seed.serve.velocity(&serve, &mut **rng)),
+        Interpolated::new(Vec3::ZERO),
+        Serving,
+    ));
+
+    commands.spawn((
+        Mesh2d(meshes.add(Triangle2d::new(
+            Vec2::new(-0.5, 0.5),
+            Vec2::new(-0.5, -0.5),
+            Vec2::new(0.5, 0.),
+        ))),
+        MeshMaterial2d(materials.add(SERVE_INDICATOR_COLOR)),
+        Transform::from_xyz(0., SERVE_INDICATOR_Y, -1.)
+            .with_scale(Vec2::splat(SERVE_INDICATOR_SIZE).extend(1.)),
+        ServeIndicator,
+    ));
+
+    let mut create_paddle = |x: f32| {
+        let [up, down, catch] = settings.keys.keys(x);
+        let controlled_by_ai = ai.sides.controls(x);
+        let mut paddle = commands.spawn((
+            Mesh2d(meshes.add(Rectangle::default())),
+            MeshMaterial2d(materials.add(Color::WHITE)),
+            Transform::from_xyz(x, 0., 0.).with_scale(paddles.size(x).extend(1.)),
+            Paddle {
+                up,
+                down,
+                catch,
+                controller: if controlled_by_ai {
+                    PaddleController::Ai
+                } else {
+                    PaddleController::Local
+                },
+            },
+            Collider,
+            Interpolated::new(Vec3::new(x, 0., 0.)),
+        ));
+        if controlled_by_ai {
+            paddle.insert(Ai::new(&ai));
+        }
+        let paddle = paddle.id();
+
+        let (left, right) = if x < 0. {
+            (TEXT_PADDING, Val::Auto)
+        } else {
+            (Val::Auto, TEXT_PADDING)
+        };
+        commands.spawn((
+            Text::default(),
+            TextFont {
+                font_size: HINT_TEXT_SIZE,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: TEXT_PADDING,
+                left,
+                right,
+                ..default()
+            },
+            ControlsHintText(paddle),
+        ));
+    };
+    if !practice.enabled {
+        create_paddle(paddles.offset);
+    }
+    create_paddle(-paddles.offset);
+
+    let mut create_wall = |commands: &mut Commands, x, y, width, height| {
+        commands
+            .spawn((
+                Mesh2d(meshes.add(Rectangle::default())),
+                MeshMaterial2d(materials.add(Color::WHITE)),
+                Transform::from_xyz(x, y, 0.).with_scale(Vec2::new(width, height).extend(1.)),
+                Wall,
+            ))
+            .id()
+    };
+    // The side walls are only drawn; the `Goal` zones on top of them do the scoring.
+    let player_colors = settings.look.themes.player_colors();
+    let right_wall = create_wall(&mut commands, WALL_OFFSET, 0., WALL_WIDTH, WALL_LENGTH);
+    let left_wall = create_wall(&mut commands, -WALL_OFFSET, 0., WALL_WIDTH, WALL_LENGTH);
+    commands
+        .entity(right_wall)
+        .insert(GoalFlash::new(player_colors[1]));
+    commands
+        .entity(left_wall)
+        .insert(GoalFlash::new(player_colors[0]));
+    if *topology == TopologyMode::Bounce {
+        for (y, (up, down)) in [WALL_OFFSET, -WALL_OFFSET]
+            .into_iter()
+            .zip(movable_walls.keys)
+        {
+            let wall = create_wall(&mut commands, 0., y, WALL_LENGTH, WALL_WIDTH);
+            commands.entity(wall).insert(Collider);
+            if movable_walls.enabled {
+                commands.entity(wall).insert(MovableWall {
+                    range: WALL_NUDGE_RANGE,
+                    speed: WALL_NUDGE_SPEED,
+                    rest: y,
+                    up,
+                    down,
+                });
+            }
+        }
+    }
+    for (x, player) in [(-WALL_OFFSET, 1), (WALL_OFFSET, 0)] {
+        if practice.enabled && x > 0. {
+            continue;
+        }
+        commands.spawn((
+            Transform::from_xyz(x, 0., 0.)
+                .with_scale(Vec2::new(WALL_WIDTH, WALL_LENGTH).extend(1.)),
+            Goal { player },
+        ));
+    }
+
+    if practice.enabled {
+        commands.entity(right_wall).insert((Backboard, Collider));
+        commands.spawn((
+            Mesh2d(meshes.add(Rectangle::default())),
+            MeshMaterial2d(materials.add(TARGET_COLOR)),
+            Transform::from_translation(random_target_position(&mut **rng).extend(-1.))
+                .with_scale(TARGET_SIZE.extend(1.)),
+            Target,
+        ));
+    }
+
+    if bumpers.enabled {
+        for corner in [
+            Vec2::ONE,
+            Vec2::NEG_ONE,
+            Vec2::new(1., -1.),
+            Vec2::new(-1., 1.),
+        ] {
+            let position = corner * (WALL_OFFSET - BUMPER_INSET);
+            let normal = -corner.normalize();
+            commands.spawn((
+                Mesh2d(meshes.add(Rectangle::default())),
+                MeshMaterial2d(materials.add(Color::WHITE)),
+                Transform::from_translation(position.extend(0.))
+                    .with_rotation(Quat::from_rotation_z(normal.perp().to_angle()))
+                    .with_scale(Vec2::new(BUMPER_LENGTH, WALL_WIDTH).extend(1.)),
+                Bumper {
+                    normal,
+                    half_length: BUMPER_LENGTH / 2.,
+                },
+            ));
+        }
+    }
+
+    for zone in &damp_zones.zones {
+        commands.spawn((
+            Mesh2d(meshes.add(Rectangle::default())),
+            MeshMaterial2d(materials.add(DAMP_ZONE_COLOR)),
+            Transform::from_translation(zone.center.extend(-1.)).with_scale(zone.size.extend(1.)),
+            DampZone {
+                factor: zone.factor,
+            },
+        ));
+    }
+
+    let layout = &settings.look.score_layout;
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: layout.size,
+            ..default()
+        },
+        TextColor(layout.color),
+        layout.anchor.node(TEXT_PADDING),
+        ScoreText,
+    ));
+    commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: PIP_GAP,
+            ..layout.anchor.node(PIP_ROWS_TOP)
+        })
+        .with_children(|parent| {
+            for side in 0..2 {
+                parent.spawn((
+                    Node {
+                        column_gap: PIP_GAP,
+                        ..default()
+                    },
+                    PipRow(side),
+                ));
+            }
+        });
+
+    commands.spawn((
+        Text::new(format!("Seed {}", *seed)),
+        TextFont {
+            font_size: TEXT_SIZE / 2.,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: TEXT_PADDING,
+            right: TEXT_PADDING,
+            ..default()
+        },
+        SeedText,
+    ));
+
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: TEXT_SIZE / 2.,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: REPLAY_STATUS_TOP,
+            right: TEXT_PADDING,
+            ..default()
+        },
+        ReplayStatusText,
+    ));
+
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: COMBO_TEXT_SIZE,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: TEXT_PADDING,
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        ComboText,
+    ));
+
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: COMBO_TEXT_SIZE,
+            ..default()
+        },
+        TextColor(COMBO_TIERS[COMBO_TIERS.len() - 1].1),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Visibility::Hidden,
+        BurstBanner,
+    ));
+
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: COMBO_TEXT_SIZE,
+            ..default()
+        },
+        TextColor(DOUBLE_POINTS_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(22.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Visibility::Hidden,
+        DoublePointsBanner,
+    ));
+
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: COMBO_TEXT_SIZE,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: TEXT_PADDING,
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        ClockText,
+    ));
+
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: TEXT_SIZE,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(45.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Visibility::Hidden,
+        ResultBanner,
+    ));
+
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: COUNTDOWN_TEXT_SIZE,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        CountdownText,
+    ));
+
+    for (x, color) in [(-1., player_colors[0]), (1., player_colors[1])] {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: EDGE_GLOW_WIDTH,
+                height: Val::Percent(100.),
+                left: if x < 0. { Val::ZERO } else { Val::Auto },
+                right: if x > 0. { Val::ZERO } else { Val::Auto },
+                ..default()
+            },
+            BackgroundColor(Color::NONE),
+            EdgeGlowPanel { x, color },
+        ));
+    }
+
+    spawn_menu(
+        &mut commands,
+        settings.labels(&NameEntry::default()),
+        (SettingsMenu, Visibility::Hidden),
+    );
+    spawn_menu(
+        &mut commands,
+        vec![history.summary(), "Back".to_string()],
+        (HistoryMenu, Visibility::Hidden),
+    );
+    spawn_menu(
+        &mut commands,
+        ball_machine.labels(),
+        (BallMachineMenu, Visibility::Hidden),
+    );
+
+    commands.insert_resource(Score(0, 0));
+}
@@ -0,0 +1,256 @@
+//! Headless batch runner for balancing. `--simulate N` plays N AI-versus-AI games without a window,
+//! as fast as the simulation allows, then prints aggregate stats as a single line of JSON.
+//!
+//! Every other gameplay flag applies as usual, so parameter sweeps are just repeated runs. Games
+//! end on the match limit; endless ones need `--simulate-points` to end before the time cap.
+//! [`HeadlessPlugin`] on its own is also what replay verification runs under.
+
+use crate::{
+    FIXED_TIMESTEP_HZ, Simulation,
+    ball::{Ball, BallCollision, CollisionKind, Velocity},
+    flow::MatchReset,
+    paddle::Paddle,
+    score::{MatchLimit, Score},
+};
+use bevy::{prelude::*, state::app::StatesPlugin, time::TimeUpdateStrategy};
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{LineWriter, Write},
+    time::Duration,
+};
+
+/// Games still undecided after this long count as draws, so two unbeatable AIs can't hang the run.
+const MAX_GAME_SECONDS: f32 = 600.;
+
+/// Runs one fixed step per update, as fast as possible and without a window.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        // Setup still spawns meshes and text, so the asset storage has to exist even with nothing
+        // to render it.
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), StatesPlugin))
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1. / FIXED_TIMESTEP_HZ,
+            )));
+    }
+}
+
+pub struct SimulatePlugin {
+    pub games: u32,
+    /// Points that win a game under [`MatchLimit::Endless`].
+    pub points: Option<u32>,
+}
+
+impl Plugin for SimulatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(HeadlessPlugin)
+            .insert_resource(Stats {
+                games: self.games,
+                points_to_win: self.points,
+                ..default()
+            })
+            .add_systems(FixedUpdate, record_stats.after(Simulation));
+    }
+}
+
+#[derive(Resource, Default)]
+struct Stats {
+    games: u32,
+    points_to_win: Option<u32>,
+    left_wins: u32,
+    right_wins: u32,
+    draws: u32,
+    points: u32,
+    paddle_hits: u32,
+    total_seconds: f32,
+}
+
+impl Stats {
+    fn played(&self) -> u32 {
+        self.left_wins + self.right_wins + self.draws
+    }
+
+    fn to_json(&self) -> String {
+        let games = self.played().max(1) as f32;
+        format!(
+            concat!(
+                "{{\"games\":{},\"left_win_rate\":{},\"right_win_rate\":{},\"draw_rate\":{},",
+                "\"average_rally_length\":{},\"average_game_seconds\":{}}}"
+            ),
+            self.played(),
+            self.left_wins as f32 / games,
+            self.right_wins as f32 / games,
+            self.draws as f32 / games,
+            self.paddle_hits as f32 / self.points.max(1) as f32,
+            self.total_seconds / games,
+        )
+    }
+}
+
+/// Counts each game once the match limit ends it, then starts the next one.
+fn record_stats(
+    mut stats: ResMut<Stats>,
+    mut game: MatchReset,
+    mut collisions: EventReader<BallCollision>,
+    mut exit: EventWriter<AppExit>,
+    limit: Res<MatchLimit>,
+) {
+    for collision in collisions.read() {
+        match collision.kind {
+            CollisionKind::Paddle => stats.paddle_hits += 1,
+            CollisionKind::Goal => stats.points += 1,
+            CollisionKind::Wall => {}
+        }
+    }
+
+    let (left, right) = (game.scores.0, game.scores.1);
+    let seconds = game.clock.elapsed;
+    let over = game.clock.over
+        || matches!(*limit, MatchLimit::Endless)
+            && stats
+                .points_to_win
+                .is_some_and(|points| left.max(right) >= points);
+    if !over && seconds < MAX_GAME_SECONDS {
+        return;
+    }
+    match left.cmp(&right) {
+        _ if !over => stats.draws += 1,
+        Ordering::Greater => stats.left_wins += 1,
+        Ordering::Less => stats.right_wins += 1,
+        // A time limit tied with draws allowed.
+        Ordering::Equal => stats.draws += 1,
+    }
+    stats.total_seconds += seconds;
+    game.reset();
+
+    if stats.played() >= stats.games {
+        println!("{}", stats.to_json());
+        exit.write(AppExit::Success);
+    }
+}
+
+/// Writes the state hash after every simulated step to a file, one line per step, so the logs
+/// of two runs with the same seed and inputs can be diffed for the first step they disagree on.
+#[derive(Resource)]
+pub(crate) struct HashLog {
+    pub(crate) file: LineWriter<File>,
+    pub(crate) step: u64,
+}
+
+/// FNV-1a over the quantized game state. Quantizing keeps the hash meaningful for float state
+/// without depending on how it would be formatted or serialized.
+pub(crate) fn state_hash<'a>(
+    balls: impl IntoIterator<Item = (&'a Transform, &'a Velocity)>,
+    paddles: impl IntoIterator<Item = &'a Transform>,
+    score: &Score,
+) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let quantize = |value: f32| ((value * 1000.).round() as i64).to_le_bytes();
+
+    let mut paddles: Vec<Vec3> = paddles
+        .into_iter()
+        .map(|transform| transform.translation)
+        .collect();
+    paddles.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+    // Extra balls from multiball can be iterated in any order, so they go in by position.
+    let mut balls: Vec<[f32; 4]> = balls
+        .into_iter()
+        .map(|(transform, velocity)| {
+            let translation = transform.translation;
+            [translation.x, translation.y, velocity.x, velocity.y]
+        })
+        .collect();
+    balls.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+
+    let mut values: Vec<_> = balls
+        .iter()
+        .flatten()
+        .map(|&value| quantize(value))
+        .collect();
+    values.extend(paddles.iter().map(|translation| quantize(translation.y)));
+    values.push(i64::from(score.0).to_le_bytes());
+    values.push(i64::from(score.1).to_le_bytes());
+
+    values.iter().flatten().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+pub(crate) fn log_state_hash(
+    mut log: ResMut<HashLog>,
+    balls: Query<(&Transform, &Velocity), With<Ball>>,
+    paddles: Query<&Transform, (With<Paddle>, Without<Ball>)>,
+    score: Res<Score>,
+) {
+    let hash = state_hash(balls, paddles, &score);
+    let step = log.step;
+    if let Err(err) = writeln!(log.file, "{step} {hash:016x}") {
+        error!("Failed to write the state hash log: {err}");
+    }
+    log.step += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ball::StallWatchdog,
+        score::{MatchClock, ScoreChanged, ScoreDecay},
+        serve::{GameRng, GameSeed, ServeConfig, ServeDirection},
+        testing,
+    };
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn finished_games_are_counted_and_the_next_one_starts_over() {
+        let mut decay = ScoreDecay::every(5.);
+        decay.timer.tick(Duration::from_secs(3));
+        let mut watchdog = StallWatchdog::default();
+        watchdog.timer.tick(Duration::from_secs(4));
+        let mut app = testing::app();
+        app.add_event::<BallCollision>()
+            .add_event::<ScoreChanged>()
+            .insert_resource(Stats {
+                games: 2,
+                ..default()
+            })
+            .insert_resource(MatchLimit::Score {
+                points: 3,
+                win_by: 1,
+            })
+            .insert_resource(Score(3, 1))
+            .insert_resource(MatchClock {
+                elapsed: 20.,
+                over: true,
+            })
+            .insert_resource(GameRng(StdRng::seed_from_u64(0)))
+            .insert_resource(GameSeed {
+                seed: 0,
+                serve: ServeDirection::Right,
+            })
+            .init_resource::<ServeConfig>()
+            .insert_resource(decay)
+            .insert_resource(watchdog)
+            .add_systems(FixedUpdate, record_stats);
+        app.update();
+
+        let stats = app.world().resource::<Stats>();
+        assert_eq!((stats.left_wins, stats.right_wins, stats.draws), (1, 0, 0));
+        assert_eq!(stats.total_seconds, 20.);
+        let score = app.world().resource::<Score>();
+        assert_eq!((score.0, score.1), (0, 0));
+        let clock = app.world().resource::<MatchClock>();
+        assert!(!clock.over && clock.elapsed == 0.);
+        let decay = app.world().resource::<ScoreDecay>();
+        assert_eq!(decay.timer.elapsed(), Duration::ZERO);
+        let watchdog = app.world().resource::<StallWatchdog>();
+        assert_eq!(watchdog.timer.elapsed(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,87 @@
+//! Sound effects. They are synthesized tones rather than audio files, so there are no assets to
+//! ship.
+
+use crate::ball::{BallCollision, CollisionKind};
+use bevy::{audio::Volume, prelude::*};
+use std::time::Duration;
+
+/// Frequency in Hz and length in seconds of the tone each kind of collision plays.
+const PADDLE_TONE: (f32, f32) = (440., 0.05);
+const WALL_TONE: (f32, f32) = (330., 0.04);
+const GOAL_TONE: (f32, f32) = (220., 0.3);
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_sounds)
+            .add_systems(Update, play_collision_sounds);
+    }
+}
+
+/// Loudness of every sound effect.
+#[derive(Resource)]
+pub(crate) struct MasterVolume {
+    /// From 0 (muted) to 1.
+    pub(crate) level: f32,
+}
+
+impl Default for MasterVolume {
+    fn default() -> Self {
+        Self { level: 0.5 }
+    }
+}
+
+#[derive(Resource)]
+struct CollisionSounds {
+    paddle: Handle<Pitch>,
+    wall: Handle<Pitch>,
+    goal: Handle<Pitch>,
+}
+
+impl CollisionSounds {
+    fn get(&self, kind: CollisionKind) -> &Handle<Pitch> {
+        match kind {
+            CollisionKind::Paddle => &self.paddle,
+            CollisionKind::Wall => &self.wall,
+            CollisionKind::Goal => &self.goal,
+        }
+    }
+}
+
+fn load_sounds(mut commands: Commands, mut pitches: ResMut<Assets<Pitch>>) {
+    let mut tone = |(frequency, seconds): (f32, f32)| {
+        pitches.add(Pitch::new(frequency, Duration::from_secs_f32(seconds)))
+    };
+    commands.insert_resource(CollisionSounds {
+        paddle: tone(PADDLE_TONE),
+        wall: tone(WALL_TONE),
+        goal: tone(GOAL_TONE),
+    });
+}
+
+/// Plays each kind of collision at most once a frame, so a ball grinding along a wall doesn't
+/// stack copies of the same tone.
+fn play_collision_sounds(
+    mut commands: Commands,
+    mut collisions: EventReader<BallCollision>,
+    sounds: Res<CollisionSounds>,
+    volume: Res<MasterVolume>,
+) {
+    let kinds: Vec<CollisionKind> = collisions.read().map(|collision| collision.kind).collect();
+    if volume.level <= 0. {
+        return;
+    }
+    for kind in [
+        CollisionKind::Paddle,
+        CollisionKind::Wall,
+        CollisionKind::Goal,
+    ] {
+        if kinds.contains(&kind) {
+            commands.spawn((
+                AudioPlayer(sounds.get(kind).clone()),
+                PlaybackSettings::DESPAWN.with_volume(Volume::Linear(volume.level)),
+            ));
+        }
+    }
+}
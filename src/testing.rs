@@ -0,0 +1,22 @@
+//! A headless app for tests, where time only moves when a test steps it.
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use std::time::Duration;
+
+use crate::FIXED_TIMESTEP_HZ;
+
+/// Game time each update advances by, exactly one fixed step.
+pub(crate) const STEP: f32 = 1. / FIXED_TIMESTEP_HZ as f32;
+
+/// An app with only the minimal plugins, where every [`App::update`] after this runs
+/// `FixedUpdate` exactly once.
+pub(crate) fn app() -> App {
+    let step = Duration::from_secs_f64(1. / FIXED_TIMESTEP_HZ);
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(step))
+        .insert_resource(Time::<Fixed>::from_duration(step));
+    // The first update only starts the clock, so nothing has moved yet.
+    app.update();
+    app
+}
@@ -0,0 +1,185 @@
+//! Colors and textures of the arena, built in or loaded from theme files.
+
+use crate::{
+    ball::{Ball, ExtraBall},
+    effects::{EdgeGlowPanel, GoalFlash},
+    settings::SavedChoices,
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::{fs, io, path::Path, str::FromStr};
+
+const LEFT_PLAYER_COLOR: Color = Color::srgb(0.3, 0.6, 1.);
+const RIGHT_PLAYER_COLOR: Color = Color::srgb(1., 0.45, 0.3);
+const THEMES_DIR: &str = "assets/themes";
+/// Only added with a window. The registry itself is loaded with the rest of the settings, since
+/// setup colors the goals from it.
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            apply_theme.run_if(resource_changed::<ThemeRegistry>),
+        );
+    }
+}
+
+/// A look for the arena loaded from a RON file in `THEMES_DIR`, so new ones need no rebuild.
+pub(crate) struct Theme {
+    pub(crate) name: String,
+    background: Option<Color>,
+    players: [Color; 2],
+    /// Image drawn on the ball, relative to the assets folder.
+    ball_texture: Option<String>,
+}
+
+/// A theme as written in its file, with colors as hex strings such as `1a1a2e`. Anything left
+/// out keeps the built-in look.
+#[derive(Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    background: Option<String>,
+    #[serde(default)]
+    left_player: Option<String>,
+    #[serde(default)]
+    right_player: Option<String>,
+    #[serde(default)]
+    ball_texture: Option<String>,
+}
+
+impl Theme {
+    fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let file: ThemeFile = ron::from_str(&contents).map_err(|err| err.to_string())?;
+        let color = |hex: Option<String>, default: Color| match hex {
+            Some(hex) => hex.parse::<Background>().map(|color| color.0),
+            None => Ok(default),
+        };
+        Ok(Self {
+            name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            background: file
+                .background
+                .map(|hex| hex.parse::<Background>().map(|color| color.0))
+                .transpose()?,
+            players: [
+                color(file.left_player, LEFT_PLAYER_COLOR)?,
+                color(file.right_player, RIGHT_PLAYER_COLOR)?,
+            ],
+            ball_texture: file.ball_texture,
+        })
+    }
+}
+
+/// Every theme found at startup, and which one is in use.
+#[derive(Resource, Default)]
+pub(crate) struct ThemeRegistry {
+    themes: Vec<Theme>,
+    /// `None` for the built-in look.
+    selected: Option<usize>,
+}
+
+impl ThemeRegistry {
+    /// Loads every `.ron` file in `THEMES_DIR`, skipping the ones that fail, and selects the
+    /// theme called `selected` if there is one.
+    pub(crate) fn load(selected: Option<&str>) -> Self {
+        let mut themes = Vec::new();
+        match fs::read_dir(THEMES_DIR) {
+            Ok(entries) => {
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    if path.extension().is_none_or(|extension| extension != "ron") {
+                        continue;
+                    }
+                    match Theme::load(&path) {
+                        Ok(theme) => themes.push(theme),
+                        Err(err) => eprintln!("Skipping theme {}: {err}", path.display()),
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => eprintln!("Failed to read {THEMES_DIR}: {err}"),
+        }
+        themes.sort_by(|a, b| a.name.cmp(&b.name));
+        let selected = selected.and_then(|name| themes.iter().position(|theme| theme.name == name));
+        Self { themes, selected }
+    }
+
+    pub(crate) fn selected(&self) -> Option<&Theme> {
+        self.themes.get(self.selected?)
+    }
+
+    /// Steps through the themes in order, back to the built-in look after the last one.
+    pub(crate) fn select_next(&mut self) {
+        self.selected = match self.selected {
+            None if !self.themes.is_empty() => Some(0),
+            Some(index) if index + 1 < self.themes.len() => Some(index + 1),
+            _ => None,
+        };
+    }
+
+    pub(crate) fn player_colors(&self) -> [Color; 2] {
+        self.selected()
+            .map_or([LEFT_PLAYER_COLOR, RIGHT_PLAYER_COLOR], |theme| {
+                theme.players
+            })
+    }
+}
+
+/// Arena background, parsed from a hex color such as `1a1a2e`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Background(pub(crate) Color);
+
+impl Background {
+    /// Contrast ratio of white elements drawn on this background, from 1 (none) to 21.
+    pub(crate) fn contrast_with_white(self) -> f32 {
+        let linear = self.0.to_linear();
+        let luminance = 0.2126 * linear.red + 0.7152 * linear.green + 0.0722 * linear.blue;
+        1.05 / (luminance + 0.05)
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Srgba::hex(s)
+            .map(|color| Self(color.into()))
+            .map_err(|err| format!("expected a hex color, got '{s}': {err}"))
+    }
+}
+
+/// Recolors everything drawn in a player's color and textures the ball. The background is only
+/// changed when a theme is picked in the menu, so the saved or `--background` one still applies
+/// at startup.
+fn apply_theme(
+    themes: Res<ThemeRegistry>,
+    mut clear_color: ResMut<ClearColor>,
+    mut saved: ResMut<SavedChoices>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    ball: Single<&MeshMaterial2d<ColorMaterial>, (With<Ball>, Without<ExtraBall>)>,
+    flashes: Query<(&Transform, &mut GoalFlash)>,
+    panels: Query<&mut EdgeGlowPanel>,
+) {
+    let theme = themes.selected();
+    let player_colors = themes.player_colors();
+    for (transform, mut flash) in flashes {
+        flash.color = player_colors[usize::from(transform.translation.x > 0.)];
+    }
+    for mut panel in panels {
+        panel.color = player_colors[usize::from(panel.x > 0.)];
+    }
+    if let Some(material) = materials.get_mut(&ball.0) {
+        material.texture = theme
+            .and_then(|theme| theme.ball_texture.as_ref())
+            .map(|path| asset_server.load(path));
+    }
+    let picked = theme.filter(|_| !themes.is_added());
+    if let Some(background) = picked.and_then(|theme| theme.background) {
+        clear_color.0 = background;
+        saved.background = Some(background.to_srgba().to_hex());
+    }
+}
@@ -0,0 +1,1362 @@
+//! The HUD and the menus.
+
+use crate::{
+    ball::{Assist, Ball, BallCollision, CollisionKind, ExtraBall, PhysicsProfile, Velocity},
+    effects::{
+        ArenaGrid, CameraZoom, EdgeGlow, EffectBudget, MatchPointSlowMotion, ReduceMotion,
+        ScreenShake, TrajectoryPreview, WinZoom, start_win_zoom,
+    },
+    flow::{GameState, Rematches},
+    interpolation::Interpolation,
+    paddle::{
+        CatchConfig, InputSource, KeyLayouts, Paddle, PaddleController, StickResponse,
+        apply_key_layouts, key_label,
+    },
+    pickups::{DoublePoints, SpeedBurst},
+    practice::{BALL_MACHINE_ANGLES, BALL_MACHINE_SPEEDS, BallMachineFeed, Practice},
+    replay::{BEST_RALLY_PATH, BestRally, InstantReplay},
+    score::{
+        Celebrating, Celebration, MatchClock, MatchLimit, PlayerNames, Ratings, Score, ScoreChanged,
+    },
+    settings::{
+        GameplayDefaults, PendingSaves, SETTINGS_PATH, SavedChoices, SavedSettings, WindowBranding,
+    },
+    sound::MasterVolume,
+    theme::ThemeRegistry,
+    wall::{WALL_OFFSET, WALL_WIDTH},
+};
+use bevy::{
+    ecs::system::SystemParam,
+    input::{
+        ButtonState,
+        keyboard::{Key, KeyboardInput},
+    },
+    math::StableInterpolate,
+    prelude::*,
+    window::WindowFocused,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+pub(crate) const TEXT_PADDING: Val = Val::Px(20.);
+pub(crate) const MIN_SCORE_SIZE: f32 = 12.;
+pub(crate) const MAX_SCORE_SIZE: f32 = 96.;
+pub(crate) const TEXT_SIZE: f32 = 36.;
+
+pub(crate) const COMBO_TEXT_SIZE: f32 = 48.;
+pub(crate) const COUNTDOWN_TEXT_SIZE: f32 = 72.;
+const COMBO_MIN_DISPLAYED: u32 = 2;
+pub(crate) const COMBO_TIERS: [(u32, Color); 3] = [
+    (5, Color::srgb(1., 1., 0.6)),
+    (10, Color::srgb(1., 0.8, 0.3)),
+    (20, Color::srgb(1., 0.45, 0.2)),
+];
+
+pub(crate) const HINT_TEXT_SIZE: f32 = 20.;
+pub(crate) const HINT_DURATION: f32 = 5.;
+const HINT_FADE_RATE: f32 = 4.;
+
+const ASSIST_LEVELS: [f32; 4] = [0., 0.25, 0.5, 1.];
+pub(crate) const EFFECT_BUDGETS: [usize; 4] = [0, 50, 200, 500];
+const EDGE_GLOW_LEVELS: [f32; 3] = [0., 0.5, 1.];
+const SCREEN_SHAKE_LEVELS: [f32; 3] = [0., 0.5, 1.];
+const VOLUME_LEVELS: [f32; 5] = [0., 0.25, 0.5, 0.75, 1.];
+/// Seconds the ball waits at the center after a goal, 0 for none.
+const CELEBRATION_DURATIONS: [f32; 5] = [0., 0.5, 1., 2., 3.];
+/// Cell sizes the grid cycles through, 0 for none.
+const GRID_CELL_SIZES: [f32; 4] = [0., 20., 40., 80.];
+
+pub(crate) const SERVE_INDICATOR_SIZE: f32 = 16.;
+/// Distance of the serve indicator from the center line, toward the side being served to.
+const SERVE_INDICATOR_OFFSET: f32 = 40.;
+pub(crate) const SERVE_INDICATOR_Y: f32 = WALL_OFFSET - 3. * WALL_WIDTH;
+pub(crate) const SERVE_INDICATOR_COLOR: Color = Color::srgba(1., 1., 1., 0.6);
+
+const PIP_SIZE: Val = Val::Px(12.);
+pub(crate) const PIP_GAP: Val = Val::Px(6.);
+const PIP_EMPTY_COLOR: Color = Color::srgba(1., 1., 1., 0.2);
+/// Below the score text.
+pub(crate) const PIP_ROWS_TOP: Val = Val::Px(64.);
+
+const MAX_NAME_LENGTH: usize = 12;
+
+const MENU_ITEM_GAP: Val = Val::Px(6.);
+/// Small enough for the whole settings menu to fit a 720 pixel tall window.
+const MENU_TEXT_SIZE: f32 = 22.;
+const MENU_SELECTED_COLOR: Color = Color::WHITE;
+const MENU_IDLE_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const MENU_BACKGROUND: Color = Color::srgba(0., 0., 0., 0.8);
+
+/// Only added with a window, so none of this runs headless.
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MenuConfirmed>()
+            .init_resource::<Combo>()
+            .init_resource::<NameEntry>()
+            .init_resource::<ControlsHint>()
+            .add_systems(OnEnter(GameState::Title), spawn_title)
+            .add_systems(OnExit(GameState::Title), despawn_title)
+            .add_systems(
+                Update,
+                (
+                    update_score.run_if(
+                        on_event::<ScoreChanged>
+                            .or(resource_changed::<PlayerNames>)
+                            .or(resource_changed::<ScoreDisplay>),
+                    ),
+                    update_pips.run_if(
+                        on_event::<ScoreChanged>
+                            .or(resource_changed::<ScoreDisplay>)
+                            .or(resource_changed::<ThemeRegistry>),
+                    ),
+                    (update_combo, update_combo_display).chain(),
+                    (
+                        update_burst_banner,
+                        update_double_points_banner,
+                        update_serve_indicator,
+                        update_serve_countdown,
+                    ),
+                    (
+                        update_match_clock,
+                        // Waits for the win zoom to play out.
+                        show_match_result.after(start_win_zoom).run_if(
+                            resource_changed::<MatchClock>
+                                .or(resource_removed::<WinZoom>)
+                                .and(|clock: Res<MatchClock>| clock.over)
+                                .and(not(resource_exists::<WinZoom>)),
+                        ),
+                    ),
+                    (toggle_controls_hint, update_controls_hint)
+                        .chain()
+                        .after(apply_key_layouts),
+                    (
+                        enter_player_name,
+                        pause_on_focus_loss,
+                        toggle_settings_menu,
+                        menu_navigation,
+                        confirm_settings,
+                        confirm_history,
+                        confirm_ball_machine,
+                        refresh_settings_menu,
+                        refresh_ball_machine_menu,
+                        sync_menu_labels,
+                        save_settings,
+                    )
+                        .chain(),
+                ),
+            );
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct BurstBanner;
+
+/// Arrow along the top wall pointing at the side being served to, shown until the first paddle
+/// hit of the rally.
+#[derive(Component)]
+pub(crate) struct ServeIndicator;
+
+#[derive(Component)]
+pub(crate) struct DoublePointsBanner;
+
+#[derive(Component)]
+pub(crate) struct ComboText;
+
+#[derive(Component)]
+pub(crate) struct ScoreText;
+
+/// Where and how the score is drawn, for instance centered for a stream overlay. The pips follow
+/// the same anchor.
+#[derive(Resource)]
+pub(crate) struct ScoreLayout {
+    pub(crate) anchor: ScoreAnchor,
+    pub(crate) size: f32,
+    pub(crate) color: Color,
+}
+
+/// Where along the top of the window the score sits.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum ScoreAnchor {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl ScoreAnchor {
+    /// An absolutely positioned node `top` from the top of the window along this anchor.
+    pub(crate) fn node(self, top: Val) -> Node {
+        let node = Node {
+            position_type: PositionType::Absolute,
+            top,
+            ..default()
+        };
+        match self {
+            ScoreAnchor::Left => Node {
+                left: TEXT_PADDING,
+                ..node
+            },
+            ScoreAnchor::Center => Node {
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..node
+            },
+            ScoreAnchor::Right => Node {
+                right: TEXT_PADDING,
+                align_items: AlignItems::End,
+                ..node
+            },
+        }
+    }
+}
+
+#[derive(Component)]
+struct TitleText;
+
+#[derive(Component)]
+pub(crate) struct ClockText;
+
+#[derive(Component)]
+pub(crate) struct ResultBanner;
+
+/// Seconds left until the serve, shown above the ball while it waits out a goal celebration.
+#[derive(Component)]
+pub(crate) struct CountdownText;
+
+/// Shows each side's score as a row of pips rather than a number.
+#[derive(Resource)]
+pub(crate) struct ScoreDisplay {
+    pub(crate) pips: bool,
+    /// Pips per row, filled as points are scored. A row grows if a side scores more.
+    pub(crate) count: u32,
+}
+
+impl Default for ScoreDisplay {
+    fn default() -> Self {
+        Self {
+            pips: false,
+            count: 11,
+        }
+    }
+}
+
+/// Pip row for the left (0) or right (1) player.
+#[derive(Component)]
+pub(crate) struct PipRow(pub(crate) usize);
+
+#[derive(Component)]
+pub(crate) struct SeedText;
+
+/// Labels the controls of the paddle it points to.
+#[derive(Component)]
+pub(crate) struct ControlsHintText(pub(crate) Entity);
+
+/// Hint is shown for the first few seconds of a game, or until toggled.
+#[derive(Resource)]
+pub(crate) struct ControlsHint {
+    pub(crate) shown: bool,
+    pub(crate) auto_hide: Option<Timer>,
+    pub(crate) toggle: KeyCode,
+}
+
+impl Default for ControlsHint {
+    fn default() -> Self {
+        Self {
+            shown: true,
+            auto_hide: Some(Timer::from_seconds(HINT_DURATION, TimerMode::Once)),
+            toggle: KeyCode::KeyH,
+        }
+    }
+}
+
+/// Selection of a menu; its entries are the children tagged with [`MenuItem`].
+#[derive(Component, Default)]
+struct MenuState {
+    selected: usize,
+}
+
+#[derive(Component)]
+struct MenuItems(Vec<String>);
+
+#[derive(Component)]
+struct MenuItem(usize);
+
+/// Sent when Enter is pressed on a visible menu.
+#[derive(Event)]
+struct MenuConfirmed {
+    menu: Entity,
+    item: usize,
+}
+
+#[derive(Component)]
+pub(crate) struct SettingsMenu;
+
+/// An entry of the [`SettingsMenu`]. Both its label and what confirming it does are picked by
+/// variant, so the two can't drift apart.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SettingsItem {
+    Interpolation,
+    CameraZoom,
+    ResumeOnFocus,
+    Assist,
+    Score,
+    Effects,
+    InstantReplay,
+    EdgeGlow,
+    PathPreview,
+    Theme,
+    ScreenShake,
+    SlowMotion,
+    ReduceMotion,
+    Grid,
+    LeftKeys,
+    RightKeys,
+    Celebration,
+    Volume,
+    Physics,
+    LeftName,
+    RightName,
+    History,
+    SaveBestRally,
+    BallMachine,
+    Resume,
+    Quit,
+}
+
+impl SettingsItem {
+    /// Every entry, in menu order.
+    const ALL: [SettingsItem; 26] = [
+        SettingsItem::Interpolation,
+        SettingsItem::CameraZoom,
+        SettingsItem::ResumeOnFocus,
+        SettingsItem::Assist,
+        SettingsItem::Score,
+        SettingsItem::Effects,
+        SettingsItem::InstantReplay,
+        SettingsItem::EdgeGlow,
+        SettingsItem::PathPreview,
+        SettingsItem::Theme,
+        SettingsItem::ScreenShake,
+        SettingsItem::SlowMotion,
+        SettingsItem::ReduceMotion,
+        SettingsItem::Grid,
+        SettingsItem::LeftKeys,
+        SettingsItem::RightKeys,
+        SettingsItem::Celebration,
+        SettingsItem::Volume,
+        SettingsItem::Physics,
+        SettingsItem::LeftName,
+        SettingsItem::RightName,
+        SettingsItem::History,
+        SettingsItem::SaveBestRally,
+        SettingsItem::BallMachine,
+        SettingsItem::Resume,
+        SettingsItem::Quit,
+    ];
+}
+
+/// Menu opened from the settings menu, which Escape returns to.
+#[derive(Component, Default)]
+struct Submenu;
+
+#[derive(Component)]
+#[require(Submenu)]
+pub(crate) struct HistoryMenu;
+
+#[derive(Component)]
+#[require(Submenu)]
+pub(crate) struct BallMachineMenu;
+
+/// Which player's name is being typed into the settings menu, 0 for left and 1 for right.
+#[derive(Resource, Default)]
+pub(crate) struct NameEntry(pub(crate) Option<usize>);
+
+/// Consecutive paddle hits in the current rally.
+#[derive(Resource, Default)]
+struct Combo(u32);
+
+/// Pauses the game when the window loses focus. Without `resume_on_focus` it opens the settings
+/// menu instead, so the player resumes it like any other manual pause.
+#[derive(Resource, Default)]
+pub(crate) struct FocusPause {
+    pub(crate) resume_on_focus: bool,
+}
+
+pub(crate) fn spawn_menu(
+    commands: &mut Commands,
+    items: Vec<String>,
+    bundle: impl Bundle,
+) -> Entity {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: MENU_ITEM_GAP,
+                ..default()
+            },
+            BackgroundColor(MENU_BACKGROUND),
+            MenuState::default(),
+            bundle,
+        ))
+        .with_children(|parent| {
+            for (index, label) in items.iter().enumerate() {
+                parent.spawn((
+                    Text::new(label.clone()),
+                    TextFont {
+                        font_size: MENU_TEXT_SIZE,
+                        ..default()
+                    },
+                    TextColor(MENU_IDLE_COLOR),
+                    MenuItem(index),
+                ));
+            }
+        })
+        .insert(MenuItems(items))
+        .id()
+}
+
+/// Everything shown in the settings menu, plus the stick tuning, saved to [`SETTINGS_PATH`].
+#[derive(SystemParam)]
+pub(crate) struct Settings<'w> {
+    pub(crate) interpolation: Res<'w, Interpolation>,
+    pub(crate) zoom: Res<'w, CameraZoom>,
+    pub(crate) focus: Res<'w, FocusPause>,
+    pub(crate) assist: Res<'w, Assist>,
+    pub(crate) names: Res<'w, PlayerNames>,
+    pub(crate) score_display: Res<'w, ScoreDisplay>,
+    pub(crate) effects: Res<'w, EffectBudget>,
+    pub(crate) stick: Res<'w, StickResponse>,
+    pub(crate) instant_replay: Res<'w, InstantReplay>,
+    pub(crate) keys: Res<'w, KeyLayouts>,
+    pub(crate) celebration: Res<'w, Celebration>,
+    pub(crate) ratings: Res<'w, Ratings>,
+    pub(crate) branding: Res<'w, WindowBranding>,
+    pub(crate) gameplay: Res<'w, GameplayDefaults>,
+    pub(crate) physics: Res<'w, PhysicsProfile>,
+    pub(crate) look: Appearance<'w>,
+}
+
+/// The look and sound of the arena, and the saved choices flags can override, split out of
+/// [`Settings`] to keep each under Bevy's limit of 16 system params.
+#[derive(SystemParam)]
+pub(crate) struct Appearance<'w> {
+    pub(crate) saved: Res<'w, SavedChoices>,
+    pub(crate) score_layout: Res<'w, ScoreLayout>,
+    pub(crate) edge_glow: Res<'w, EdgeGlow>,
+    pub(crate) shake: Res<'w, ScreenShake>,
+    pub(crate) slow_motion: Res<'w, MatchPointSlowMotion>,
+    pub(crate) grid: Res<'w, ArenaGrid>,
+    pub(crate) preview: Res<'w, TrajectoryPreview>,
+    pub(crate) themes: Res<'w, ThemeRegistry>,
+    pub(crate) volume: Res<'w, MasterVolume>,
+    pub(crate) reduce_motion: Res<'w, ReduceMotion>,
+}
+
+impl Appearance<'_> {
+    pub(crate) fn is_changed(&self) -> bool {
+        self.saved.is_changed()
+            || self.edge_glow.is_changed()
+            || self.shake.is_changed()
+            || self.slow_motion.is_changed()
+            || self.grid.is_changed()
+            || self.preview.is_changed()
+            || self.themes.is_changed()
+            || self.volume.is_changed()
+            || self.reduce_motion.is_changed()
+    }
+}
+
+/// The preferences the settings menu changes.
+#[derive(SystemParam)]
+struct SettingsMut<'w> {
+    interpolation: ResMut<'w, Interpolation>,
+    zoom: ResMut<'w, CameraZoom>,
+    focus: ResMut<'w, FocusPause>,
+    assist: ResMut<'w, Assist>,
+    score_display: ResMut<'w, ScoreDisplay>,
+    effects: ResMut<'w, EffectBudget>,
+    instant_replay: ResMut<'w, InstantReplay>,
+    edge_glow: ResMut<'w, EdgeGlow>,
+    shake: ResMut<'w, ScreenShake>,
+    slow_motion: ResMut<'w, MatchPointSlowMotion>,
+    grid: ResMut<'w, ArenaGrid>,
+    preview: ResMut<'w, TrajectoryPreview>,
+    themes: ResMut<'w, ThemeRegistry>,
+    keys: ResMut<'w, KeyLayouts>,
+    celebration: ResMut<'w, Celebration>,
+    volume: ResMut<'w, MasterVolume>,
+}
+
+impl Settings<'_> {
+    pub(crate) fn is_changed(&self) -> bool {
+        self.interpolation.is_changed()
+            || self.zoom.is_changed()
+            || self.focus.is_changed()
+            || self.assist.is_changed()
+            || self.names.is_changed()
+            || self.score_display.is_changed()
+            || self.effects.is_changed()
+            || self.stick.is_changed()
+            || self.instant_replay.is_changed()
+            || self.keys.is_changed()
+            || self.celebration.is_changed()
+            || self.look.is_changed()
+    }
+
+    pub(crate) fn labels(&self, entry: &NameEntry) -> Vec<String> {
+        SettingsItem::ALL
+            .iter()
+            .map(|&item| self.label(item, entry))
+            .collect()
+    }
+
+    fn label(&self, item: SettingsItem, entry: &NameEntry) -> String {
+        let name_label = |slot: usize, side: &str, name: &str, shown: &str| {
+            if entry.0 == Some(slot) {
+                format!("{side} player: {name}_")
+            } else if name.trim().is_empty() {
+                format!("{side} player: {shown}")
+            } else {
+                let rating = self.ratings.get(name.trim());
+                format!("{side} player: {shown} ({rating:.0})")
+            }
+        };
+        match item {
+            SettingsItem::Interpolation => {
+                format!("Interpolation: {}", on_off(self.interpolation.enabled))
+            }
+            SettingsItem::CameraZoom => format!("Camera zoom: {}", on_off(self.zoom.enabled)),
+            SettingsItem::ResumeOnFocus => {
+                format!("Resume on focus: {}", on_off(self.focus.resume_on_focus))
+            }
+            SettingsItem::Assist => {
+                if self.assist.strength > 0. {
+                    format!("Assist: {:.0}%", self.assist.strength * 100.)
+                } else {
+                    "Assist: Off".to_string()
+                }
+            }
+            SettingsItem::Score => format!(
+                "Score: {}",
+                if self.score_display.pips {
+                    "Pips"
+                } else {
+                    "Numbers"
+                }
+            ),
+            SettingsItem::Effects => match self.effects.0 {
+                0 => "Effects: Off".to_string(),
+                budget => format!("Effects: {budget}"),
+            },
+            SettingsItem::InstantReplay => {
+                format!("Instant replay: {}", on_off(self.instant_replay.enabled))
+            }
+            SettingsItem::EdgeGlow => {
+                if self.look.edge_glow.intensity > 0. {
+                    format!("Edge glow: {:.0}%", self.look.edge_glow.intensity * 100.)
+                } else {
+                    "Edge glow: Off".to_string()
+                }
+            }
+            SettingsItem::PathPreview => {
+                format!("Path preview: {}", self.look.preview.sides.label())
+            }
+            SettingsItem::Theme => format!(
+                "Theme: {}",
+                self.look
+                    .themes
+                    .selected()
+                    .map_or("Default", |theme| &theme.name)
+            ),
+            SettingsItem::ScreenShake => {
+                if self.look.shake.intensity > 0. {
+                    format!("Screen shake: {:.0}%", self.look.shake.intensity * 100.)
+                } else {
+                    "Screen shake: Off".to_string()
+                }
+            }
+            SettingsItem::SlowMotion => format!(
+                "Match point slow motion: {}",
+                on_off(self.look.slow_motion.enabled)
+            ),
+            SettingsItem::ReduceMotion => {
+                format!("Reduce motion: {}", on_off(self.look.reduce_motion.enabled))
+            }
+            SettingsItem::Grid => {
+                if self.look.grid.cell > 0. {
+                    format!("Grid: {}px", self.look.grid.cell)
+                } else {
+                    "Grid: Off".to_string()
+                }
+            }
+            SettingsItem::LeftKeys => format!("Left keys: {}", self.keys.label(-1.)),
+            SettingsItem::RightKeys => {
+                if self.keys.conflicts().is_empty() {
+                    format!("Right keys: {}", self.keys.label(1.))
+                } else {
+                    format!("Right keys: {} (conflicts)", self.keys.label(1.))
+                }
+            }
+            SettingsItem::Celebration => {
+                if self.celebration.duration > 0. {
+                    format!("Goal celebration: {}s", self.celebration.duration)
+                } else {
+                    "Goal celebration: Off".to_string()
+                }
+            }
+            SettingsItem::Volume => {
+                if self.look.volume.level > 0. {
+                    format!("Volume: {:.0}%", self.look.volume.level * 100.)
+                } else {
+                    "Volume: Off".to_string()
+                }
+            }
+            SettingsItem::Physics => format!("Physics: {}", self.physics.label()),
+            SettingsItem::LeftName => name_label(0, "Left", &self.names.left, self.names.left()),
+            SettingsItem::RightName => {
+                name_label(1, "Right", &self.names.right, self.names.right())
+            }
+            SettingsItem::History => "Match history".to_string(),
+            SettingsItem::SaveBestRally => "Save best rally".to_string(),
+            SettingsItem::BallMachine => "Ball machine".to_string(),
+            SettingsItem::Resume => "Resume".to_string(),
+            SettingsItem::Quit => "Quit".to_string(),
+        }
+    }
+
+    fn saved(&self) -> SavedSettings {
+        SavedSettings {
+            interpolation: self.interpolation.enabled,
+            camera_zoom: self.zoom.enabled,
+            resume_on_focus: self.focus.resume_on_focus,
+            assist: self.assist.strength,
+            score_pips: self.score_display.pips,
+            effect_budget: self.effects.0,
+            stick_deadzone: self.stick.deadzone,
+            score_anchor: self.look.score_layout.anchor,
+            score_size: self.look.score_layout.size,
+            score_color: Some(self.look.score_layout.color.to_srgba().to_hex()),
+            stick_curve: self.stick.curve,
+            instant_replay: self.instant_replay.enabled,
+            edge_glow: self.look.edge_glow.intensity,
+            screen_shake: self.look.shake.intensity,
+            volume: self.look.volume.level,
+            match_point_slow_motion: self.look.slow_motion.enabled,
+            reduce_motion: self.look.reduce_motion.enabled,
+            physics: self.look.saved.physics,
+            grid_cell: self.look.grid.cell,
+            celebration: self.celebration.duration,
+            grid_color: Some(self.look.grid.color.to_srgba().to_hex()),
+            trajectory_preview: self.look.preview.sides,
+            theme: self.look.themes.selected().map(|theme| theme.name.clone()),
+            left_keys: self.keys.left,
+            right_keys: self.keys.right,
+            left_custom_keys: self.keys.left_custom,
+            right_custom_keys: self.keys.right_custom,
+            left_name: self.names.left.clone(),
+            right_name: self.names.right.clone(),
+            background: self.look.saved.background.clone(),
+            window_title: self.branding.title.clone(),
+            window_icon: self.branding.icon.clone(),
+            gameplay: *self.gameplay,
+        }
+    }
+}
+
+fn menu_navigation(
+    mut menus: Query<(Entity, &mut MenuState, &MenuItems, &InheritedVisibility)>,
+    items: Query<(&MenuItem, &ChildOf, &mut TextColor)>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut confirmed: EventWriter<MenuConfirmed>,
+) {
+    for (menu, mut state, menu_items, visibility) in &mut menus {
+        let len = menu_items.0.len();
+        if !visibility.get() || len == 0 {
+            continue;
+        }
+        if keyboard.just_pressed(KeyCode::ArrowDown) {
+            state.selected = (state.selected + 1) % len;
+        }
+        if keyboard.just_pressed(KeyCode::ArrowUp) {
+            state.selected = (state.selected + len - 1) % len;
+        }
+        if keyboard.just_pressed(KeyCode::Enter) {
+            confirmed.write(MenuConfirmed {
+                menu,
+                item: state.selected,
+            });
+        }
+    }
+
+    for (item, child_of, mut color) in items {
+        if let Ok((_, state, ..)) = menus.get(child_of.parent()) {
+            color.0 = if item.0 == state.selected {
+                MENU_SELECTED_COLOR
+            } else {
+                MENU_IDLE_COLOR
+            };
+        }
+    }
+}
+
+fn sync_menu_labels(
+    menus: Query<&MenuItems, Changed<MenuItems>>,
+    items: Query<(&MenuItem, &ChildOf, &mut Text)>,
+) {
+    for (item, child_of, mut text) in items {
+        if let Some(label) = menus
+            .get(child_of.parent())
+            .ok()
+            .and_then(|menu_items| menu_items.0.get(item.0))
+        {
+            text.0.clone_from(label);
+        }
+    }
+}
+
+pub(crate) fn on_off(enabled: bool) -> &'static str {
+    if enabled { "On" } else { "Off" }
+}
+
+fn toggle_settings_menu(
+    mut menu: Single<&mut Visibility, With<SettingsMenu>>,
+    submenus: Query<&mut Visibility, (With<Submenu>, Without<SettingsMenu>)>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard.just_pressed(KeyCode::Escape) || *state.get() == GameState::Title {
+        return;
+    }
+    // The menu opens over the result too, but leaves the match over.
+    let in_match = *state.get() != GameState::GameOver;
+    let mut in_submenu = false;
+    for mut submenu in submenus {
+        if *submenu != Visibility::Hidden {
+            *submenu = Visibility::Hidden;
+            in_submenu = true;
+        }
+    }
+    if in_submenu {
+        **menu = Visibility::Visible;
+    } else if **menu == Visibility::Hidden {
+        **menu = Visibility::Visible;
+        if in_match {
+            next_state.set(GameState::Paused);
+        }
+    } else {
+        **menu = Visibility::Hidden;
+        if in_match {
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+/// Types into the name picked in the settings menu until Enter or Escape. While typing, every key
+/// press is swallowed so it doesn't also drive the menu or other shortcuts.
+pub(crate) fn enter_player_name(
+    mut inputs: EventReader<KeyboardInput>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut entry: ResMut<NameEntry>,
+    mut names: ResMut<PlayerNames>,
+) {
+    let Some(slot) = entry.0 else {
+        inputs.clear();
+        return;
+    };
+    for input in inputs.read() {
+        if input.state != ButtonState::Pressed {
+            continue;
+        }
+        let name = if slot == 0 {
+            &mut names.left
+        } else {
+            &mut names.right
+        };
+        match &input.logical_key {
+            Key::Enter | Key::Escape => {
+                entry.0 = None;
+                break;
+            }
+            Key::Backspace => {
+                name.pop();
+            }
+            Key::Character(text) => {
+                for c in text.chars().filter(|c| !c.is_control()) {
+                    if name.chars().count() < MAX_NAME_LENGTH {
+                        name.push(c);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    keyboard.reset_all();
+}
+
+fn spawn_title(mut commands: Commands) {
+    commands.spawn((
+        Text::new("Press Enter to start"),
+        TextFont {
+            font_size: TEXT_SIZE,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(45.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        TitleText,
+    ));
+}
+
+fn despawn_title(mut commands: Commands, texts: Query<Entity, With<TitleText>>) {
+    for text in &texts {
+        commands.entity(text).despawn();
+    }
+}
+
+fn pause_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    focus: Res<FocusPause>,
+    mut menu: Single<&mut Visibility, With<SettingsMenu>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    // Whether the current pause was started by losing focus rather than by the player.
+    mut paused_by_focus: Local<bool>,
+) {
+    // Focus can flicker several times in one frame; only where it settled matters.
+    let Some(event) = focus_events.read().last() else {
+        return;
+    };
+    if !event.focused {
+        if *state.get() != GameState::Playing {
+            return;
+        }
+        next_state.set(GameState::Paused);
+        if focus.resume_on_focus {
+            *paused_by_focus = true;
+        } else {
+            **menu = Visibility::Visible;
+        }
+    } else if *paused_by_focus {
+        *paused_by_focus = false;
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn confirm_settings(
+    mut confirmed: EventReader<MenuConfirmed>,
+    menu: Single<(Entity, &mut Visibility), With<SettingsMenu>>,
+    mut submenus: Query<
+        (&mut Visibility, Has<HistoryMenu>),
+        (With<Submenu>, Without<SettingsMenu>),
+    >,
+    mut settings: SettingsMut,
+    mut physics: ResMut<PhysicsProfile>,
+    mut saved: ResMut<SavedChoices>,
+    mut reduce_motion: ResMut<ReduceMotion>,
+    mut entry: ResMut<NameEntry>,
+    rally: Res<BestRally>,
+    mut saves: ResMut<PendingSaves>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let (menu, mut visibility) = menu.into_inner();
+    for event in confirmed.read().filter(|event| event.menu == menu) {
+        let Some(&item) = SettingsItem::ALL.get(event.item) else {
+            continue;
+        };
+        match item {
+            SettingsItem::Interpolation => {
+                settings.interpolation.enabled = !settings.interpolation.enabled
+            }
+            SettingsItem::CameraZoom => settings.zoom.enabled = !settings.zoom.enabled,
+            SettingsItem::ResumeOnFocus => {
+                settings.focus.resume_on_focus = !settings.focus.resume_on_focus
+            }
+            SettingsItem::Assist => {
+                settings.assist.strength = ASSIST_LEVELS
+                    .into_iter()
+                    .find(|&level| level > settings.assist.strength)
+                    .unwrap_or(0.);
+            }
+            SettingsItem::Score => settings.score_display.pips = !settings.score_display.pips,
+            SettingsItem::Effects => {
+                settings.effects.0 = EFFECT_BUDGETS
+                    .into_iter()
+                    .find(|&budget| budget > settings.effects.0)
+                    .unwrap_or(0);
+            }
+            SettingsItem::InstantReplay => {
+                settings.instant_replay.enabled = !settings.instant_replay.enabled
+            }
+            SettingsItem::EdgeGlow => {
+                settings.edge_glow.intensity = EDGE_GLOW_LEVELS
+                    .into_iter()
+                    .find(|&level| level > settings.edge_glow.intensity)
+                    .unwrap_or(0.);
+            }
+            SettingsItem::PathPreview => settings.preview.sides = settings.preview.sides.next(),
+            SettingsItem::Theme => settings.themes.select_next(),
+            SettingsItem::ScreenShake => {
+                settings.shake.intensity = SCREEN_SHAKE_LEVELS
+                    .into_iter()
+                    .find(|&level| level > settings.shake.intensity)
+                    .unwrap_or(0.);
+            }
+            SettingsItem::SlowMotion => {
+                settings.slow_motion.enabled = !settings.slow_motion.enabled
+            }
+            SettingsItem::ReduceMotion => reduce_motion.enabled = !reduce_motion.enabled,
+            SettingsItem::Grid => {
+                settings.grid.cell = GRID_CELL_SIZES
+                    .into_iter()
+                    .find(|&size| size > settings.grid.cell)
+                    .unwrap_or(0.);
+            }
+            SettingsItem::LeftKeys => settings.keys.cycle(-1.),
+            SettingsItem::RightKeys => settings.keys.cycle(1.),
+            SettingsItem::Celebration => {
+                settings.celebration.duration = CELEBRATION_DURATIONS
+                    .into_iter()
+                    .find(|&duration| duration > settings.celebration.duration)
+                    .unwrap_or(0.);
+            }
+            SettingsItem::Volume => {
+                settings.volume.level = VOLUME_LEVELS
+                    .into_iter()
+                    .find(|&level| level > settings.volume.level)
+                    .unwrap_or(0.);
+            }
+            SettingsItem::Physics => {
+                *physics = physics.next();
+                saved.physics = *physics;
+            }
+            SettingsItem::LeftName => entry.0 = Some(0),
+            SettingsItem::RightName => entry.0 = Some(1),
+            SettingsItem::History | SettingsItem::BallMachine => {
+                *visibility = Visibility::Hidden;
+                let opening_history = item == SettingsItem::History;
+                for (mut submenu, is_history) in &mut submenus {
+                    if is_history == opening_history {
+                        *submenu = Visibility::Visible;
+                    }
+                }
+            }
+            SettingsItem::SaveBestRally => match &rally.best {
+                Some((hits, replay)) => match ron::to_string(replay) {
+                    Ok(contents) => {
+                        info!("Saving the best rally, {hits} hits long");
+                        saves.queue(BEST_RALLY_PATH, contents);
+                        saves.flush();
+                    }
+                    Err(err) => error!("Failed to serialize the best rally: {err}"),
+                },
+                None => info!("No rally has finished yet"),
+            },
+            SettingsItem::Resume => {
+                *visibility = Visibility::Hidden;
+                if *state.get() == GameState::Paused {
+                    next_state.set(GameState::Playing);
+                }
+            }
+            SettingsItem::Quit => {
+                exit.write(AppExit::Success);
+            }
+        }
+    }
+}
+
+/// Any entry of the history screen leads back to the settings menu.
+fn confirm_history(
+    mut confirmed: EventReader<MenuConfirmed>,
+    history: Single<(Entity, &mut Visibility), With<HistoryMenu>>,
+    mut menu: Single<&mut Visibility, (With<SettingsMenu>, Without<HistoryMenu>)>,
+) {
+    let (history, mut visibility) = history.into_inner();
+    if confirmed.read().any(|event| event.menu == history) {
+        *visibility = Visibility::Hidden;
+        **menu = Visibility::Visible;
+    }
+}
+
+fn confirm_ball_machine(
+    mut confirmed: EventReader<MenuConfirmed>,
+    machine_menu: Single<(Entity, &mut Visibility), With<BallMachineMenu>>,
+    mut menu: Single<&mut Visibility, (With<SettingsMenu>, Without<BallMachineMenu>)>,
+    mut feed: ResMut<BallMachineFeed>,
+) {
+    let (machine_menu, mut visibility) = machine_menu.into_inner();
+    for event in confirmed.read().filter(|event| event.menu == machine_menu) {
+        match event.item {
+            0 => feed.enabled = !feed.enabled,
+            1 => {
+                feed.angle_degrees = BALL_MACHINE_ANGLES
+                    .into_iter()
+                    .find(|&angle| angle > feed.angle_degrees)
+                    .unwrap_or(BALL_MACHINE_ANGLES[0]);
+            }
+            2 => {
+                feed.speed = BALL_MACHINE_SPEEDS
+                    .into_iter()
+                    .find(|&speed| speed > feed.speed)
+                    .unwrap_or(BALL_MACHINE_SPEEDS[0]);
+            }
+            _ => {
+                *visibility = Visibility::Hidden;
+                **menu = Visibility::Visible;
+            }
+        }
+    }
+}
+
+fn refresh_ball_machine_menu(
+    mut menu: Single<&mut MenuItems, With<BallMachineMenu>>,
+    feed: Res<BallMachineFeed>,
+) {
+    if feed.is_changed() {
+        menu.0 = feed.labels();
+    }
+}
+
+fn refresh_settings_menu(
+    mut menu: Single<&mut MenuItems, With<SettingsMenu>>,
+    settings: Settings,
+    entry: Res<NameEntry>,
+) {
+    if settings.is_changed() || entry.is_changed() {
+        menu.0 = settings.labels(&entry);
+    }
+}
+
+fn update_serve_countdown(
+    mut text: Single<&mut Text, With<CountdownText>>,
+    ball: Single<Option<&Celebrating>, (With<Ball>, Without<ExtraBall>)>,
+) {
+    let label = ball
+        .map(|celebrating| celebrating.0.remaining_secs().ceil() as u32)
+        .filter(|&seconds| seconds > 0)
+        .map_or_else(String::new, |seconds| seconds.to_string());
+    if text.0 != label {
+        text.0 = label;
+    }
+}
+
+fn update_double_points_banner(
+    banner: Single<(&mut Text, &mut Visibility), With<DoublePointsBanner>>,
+    double: Option<Res<DoublePoints>>,
+    names: Res<PlayerNames>,
+) {
+    let (mut text, mut visibility) = banner.into_inner();
+    let (label, shown) = match double {
+        Some(double) => {
+            let name = if double.player == 0 {
+                names.left()
+            } else {
+                names.right()
+            };
+            (format!("Double points: {name}"), Visibility::Inherited)
+        }
+        None => (String::new(), Visibility::Hidden),
+    };
+    if text.0 != label {
+        text.0 = label;
+    }
+    visibility.set_if_neq(shown);
+}
+
+fn update_serve_indicator(
+    indicator: Single<(&mut Transform, &mut Visibility), With<ServeIndicator>>,
+    ball: Single<&Velocity, (With<Ball>, Without<ExtraBall>)>,
+    mut collisions: EventReader<BallCollision>,
+    mut in_rally: Local<bool>,
+) {
+    for collision in collisions.read() {
+        match collision.kind {
+            CollisionKind::Paddle => *in_rally = true,
+            CollisionKind::Goal => *in_rally = false,
+            CollisionKind::Wall => {}
+        }
+    }
+    let (mut transform, mut visibility) = indicator.into_inner();
+    let direction = ball.x.signum();
+    transform.translation.x = direction * SERVE_INDICATOR_OFFSET;
+    transform.scale.x = direction * SERVE_INDICATOR_SIZE;
+    visibility.set_if_neq(if *in_rally {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    });
+}
+
+fn update_burst_banner(
+    banner: Single<(&mut Text, &mut Visibility), With<BurstBanner>>,
+    bursts: Query<&SpeedBurst>,
+) {
+    let (mut text, mut visibility) = banner.into_inner();
+    let (label, shown) = match bursts.iter().next() {
+        Some(burst) if burst.warning.finished() => ("Speed burst!", Visibility::Inherited),
+        Some(_) => ("Speed burst incoming", Visibility::Inherited),
+        None => ("", Visibility::Hidden),
+    };
+    if text.0 != label {
+        text.0 = label.to_string();
+    }
+    visibility.set_if_neq(shown);
+}
+
+fn combo_color(combo: u32) -> Color {
+    COMBO_TIERS
+        .iter()
+        .rev()
+        .find(|(threshold, _)| combo >= *threshold)
+        .map_or(Color::WHITE, |(_, color)| *color)
+}
+
+fn update_combo_display(
+    combo: Res<Combo>,
+    text: Single<(&mut Text, &mut TextColor), With<ComboText>>,
+    ball: Single<&MeshMaterial2d<ColorMaterial>, (With<Ball>, Without<ExtraBall>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !combo.is_changed() {
+        return;
+    }
+    let color = combo_color(combo.0);
+    let (mut text, mut text_color) = text.into_inner();
+    text.0 = if combo.0 >= COMBO_MIN_DISPLAYED {
+        format!("Combo x{}", combo.0)
+    } else {
+        String::new()
+    };
+    text_color.0 = color;
+    if let Some(material) = materials.get_mut(&ball.0) {
+        material.color = color;
+    }
+}
+
+fn save_settings(settings: Settings, mut saves: ResMut<PendingSaves>) {
+    if !settings.is_changed() {
+        return;
+    }
+    match ron::ser::to_string_pretty(&settings.saved(), default()) {
+        Ok(contents) => saves.queue(SETTINGS_PATH, contents),
+        Err(err) => error!("Failed to serialize settings: {err}"),
+    }
+}
+
+fn update_match_clock(
+    mut text: Single<&mut Text, With<ClockText>>,
+    clock: Res<MatchClock>,
+    limit: Res<MatchLimit>,
+) {
+    let label = match *limit {
+        MatchLimit::Time { seconds, .. } if clock.elapsed < seconds => {
+            let remaining = (seconds - clock.elapsed).ceil() as u32;
+            format!("{}:{:02}", remaining / 60, remaining % 60)
+        }
+        MatchLimit::Time { .. } if !clock.over => "Overtime".to_string(),
+        _ => String::new(),
+    };
+    if text.0 != label {
+        text.0 = label;
+    }
+}
+
+fn show_match_result(
+    banner: Single<(&mut Text, &mut Visibility), With<ResultBanner>>,
+    scores: Res<Score>,
+    names: Res<PlayerNames>,
+    rematches: Res<Rematches>,
+) {
+    let (mut text, mut visibility) = banner.into_inner();
+    text.0 = match scores.0.cmp(&scores.1) {
+        Ordering::Greater => format!("{} wins", names.left()),
+        Ordering::Less => format!("{} wins", names.right()),
+        Ordering::Equal => "Draw".to_string(),
+    };
+    if rematches.enabled {
+        text.0
+            .push_str("\nEnter to play again, Backspace for the title screen");
+    }
+    *visibility = Visibility::Inherited;
+}
+
+fn toggle_controls_hint(mut hint: ResMut<ControlsHint>, keyboard: Res<ButtonInput<KeyCode>>) {
+    if keyboard.just_pressed(hint.toggle) {
+        hint.shown = !hint.shown;
+        hint.auto_hide = None;
+    }
+}
+
+fn update_controls_hint(
+    mut hint: ResMut<ControlsHint>,
+    texts: Query<(&ControlsHintText, &mut Text, &mut TextColor)>,
+    paddles: Query<(&Paddle, &InputSource)>,
+    catch: Res<CatchConfig>,
+    time: Res<Time>,
+) {
+    if let Some(timer) = &mut hint.auto_hide
+        && timer.tick(time.delta()).just_finished()
+    {
+        hint.shown = false;
+        hint.auto_hide = None;
+    }
+
+    let target = if hint.shown { 1. } else { 0. };
+    for (hint_text, mut text, mut color) in texts {
+        let Ok((paddle, source)) = paddles.get(hint_text.0) else {
+            continue;
+        };
+        let label = match (paddle.controller, source) {
+            (PaddleController::Local, InputSource::Gamepad(_)) => "Gamepad".to_string(),
+            (PaddleController::Local, InputSource::Mouse) => "Mouse".to_string(),
+            (PaddleController::Local, InputSource::Keyboard) if catch.enabled => format!(
+                "{} / {}, catch {}",
+                key_label(paddle.up),
+                key_label(paddle.down),
+                key_label(paddle.catch)
+            ),
+            (PaddleController::Local, InputSource::Keyboard) => {
+                format!("{} / {}", key_label(paddle.up), key_label(paddle.down))
+            }
+            (PaddleController::Ai, _) => "AI".to_string(),
+            #[cfg(feature = "net")]
+            (PaddleController::Remote, _) => "Remote".to_string(),
+        };
+        if text.0 != label {
+            text.0 = label;
+        }
+        let mut alpha = color.0.alpha();
+        alpha.smooth_nudge(&target, HINT_FADE_RATE, time.delta_secs());
+        color.0.set_alpha(alpha);
+    }
+}
+
+fn update_pips(
+    mut commands: Commands,
+    rows: Query<(Entity, &PipRow)>,
+    scores: Res<Score>,
+    display: Res<ScoreDisplay>,
+    practice: Res<Practice>,
+    themes: Res<ThemeRegistry>,
+) {
+    let player_colors = themes.player_colors();
+    for (row, side) in rows {
+        commands.entity(row).despawn_related::<Children>();
+        if !display.pips || practice.enabled {
+            continue;
+        }
+        let (points, color) = match side.0 {
+            0 => (scores.0, player_colors[0]),
+            _ => (scores.1, player_colors[1]),
+        };
+        commands.entity(row).with_children(|row| {
+            for pip in 0..display.count.max(points) {
+                row.spawn((
+                    Node {
+                        width: PIP_SIZE,
+                        height: PIP_SIZE,
+                        ..default()
+                    },
+                    BorderRadius::MAX,
+                    BackgroundColor(if pip < points { color } else { PIP_EMPTY_COLOR }),
+                ));
+            }
+        });
+    }
+}
+
+fn update_score(
+    scores: Option<Res<Score>>,
+    practice: Res<Practice>,
+    names: Res<PlayerNames>,
+    display: Res<ScoreDisplay>,
+    text: Option<Single<&mut Text, With<ScoreText>>>,
+) {
+    // Both only exist once a match is set up.
+    let (Some(scores), Some(mut text)) = (scores, text) else {
+        return;
+    };
+    text.0 = if practice.enabled {
+        format!("Hits {} - Misses {}", practice.hits, scores.1)
+    } else if display.pips {
+        format!("{} - {}", names.left(), names.right())
+    } else {
+        format!(
+            "{} {} - {} {}",
+            names.left(),
+            scores.0,
+            scores.1,
+            names.right()
+        )
+    };
+}
+
+fn update_combo(mut collisions: EventReader<BallCollision>, mut combo: ResMut<Combo>) {
+    for collision in collisions.read() {
+        match collision.kind {
+            CollisionKind::Paddle => combo.0 += 1,
+            CollisionKind::Goal => combo.0 = 0,
+            CollisionKind::Wall => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn the_score_text_waits_for_a_match() {
+        let mut app = testing::app();
+        app.insert_resource(Practice {
+            enabled: false,
+            hits: 0,
+        })
+        .insert_resource(PlayerNames {
+            left: "Ada".to_string(),
+            right: String::new(),
+        })
+        .init_resource::<ScoreDisplay>()
+        .add_systems(Update, update_score);
+        // Neither the score nor its text exist yet.
+        app.update();
+
+        let text = app.world_mut().spawn((Text::default(), ScoreText)).id();
+        app.update();
+        assert_eq!(app.world().get::<Text>(text).unwrap().0, "");
+
+        app.insert_resource(Score(3, 1));
+        app.update();
+        assert_eq!(
+            app.world().get::<Text>(text).unwrap().0,
+            "Ada 3 - 1 Player 2"
+        );
+    }
+
+    #[test]
+    fn every_settings_item_is_listed_once() {
+        for (index, item) in SettingsItem::ALL.iter().enumerate() {
+            assert!(
+                !SettingsItem::ALL[..index].contains(item),
+                "{item:?} is listed twice"
+            );
+        }
+    }
+}
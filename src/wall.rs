@@ -0,0 +1,194 @@
+//! The arena's walls and goals, along with the optional bumpers, damp zones and movable walls.
+
+use crate::Phase;
+use bevy::prelude::*;
+use std::str::FromStr;
+
+pub(crate) const WALL_WIDTH: f32 = 10.;
+pub(crate) const WALL_OFFSET: f32 = 220.;
+pub(crate) const WALL_LENGTH: f32 = WALL_OFFSET * 2. + WALL_WIDTH;
+
+pub(crate) const BUMPER_LENGTH: f32 = 80.;
+pub(crate) const BUMPER_INSET: f32 = 30.;
+
+pub(crate) const DAMP_ZONE_COLOR: Color = Color::srgba(0.55, 0.4, 0.25, 0.35);
+
+pub(crate) const WALL_NUDGE_RANGE: f32 = 40.;
+pub(crate) const WALL_NUDGE_SPEED: f32 = 150.;
+
+pub struct WallPlugin;
+
+impl Plugin for WallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, nudge_walls.in_set(Phase::Move));
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct Wall;
+
+/// Invisible zone behind a side wall. The ball entering it scores a point for `player`, 0 being
+/// the left player and 1 the right.
+#[derive(Component)]
+pub(crate) struct Goal {
+    pub(crate) player: usize,
+}
+
+/// An angled wall segment, centered on its transform and facing `normal`.
+#[derive(Component)]
+pub(crate) struct Bumper {
+    pub(crate) normal: Vec2,
+    pub(crate) half_length: f32,
+}
+
+#[derive(Resource)]
+pub(crate) struct BumperConfig {
+    pub(crate) enabled: bool,
+}
+
+/// Zones placed with `--damp-zone`, any number of times.
+#[derive(Resource, Default)]
+pub(crate) struct DampZoneConfig {
+    pub(crate) zones: Vec<DampZoneSpec>,
+}
+
+/// A damp zone written as `X,Y,WIDTH,HEIGHT,FACTOR`, centered on `X,Y` from the arena center.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DampZoneSpec {
+    pub(crate) center: Vec2,
+    pub(crate) size: Vec2,
+    pub(crate) factor: f32,
+}
+
+impl FromStr for DampZoneSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<f32> = s
+            .split(',')
+            .map(|value| value.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|err| format!("{err} in '{s}'"))?;
+        let [x, y, width, height, factor] = values[..] else {
+            return Err(format!("expected X,Y,WIDTH,HEIGHT,FACTOR, got '{s}'"));
+        };
+        if width <= 0. || height <= 0. {
+            return Err(format!("zone size must be positive, got {width}x{height}"));
+        }
+        if factor <= 0. || factor > 1. {
+            return Err(format!("factor must be in (0, 1], got {factor}"));
+        }
+        Ok(Self {
+            center: Vec2::new(x, y),
+            size: Vec2::new(width, height),
+            factor,
+        })
+    }
+}
+
+/// Mud-like area the ball crosses at `factor` of its speed. Like a speed burst it scales how far
+/// the ball moves rather than its velocity, so the speed is back on exit and the speed floor,
+/// which only acts on bounces, never fights it.
+#[derive(Component)]
+pub(crate) struct DampZone {
+    pub(crate) factor: f32,
+}
+
+/// Co-op twist where extra players slide the top and bottom walls to deflect the ball. Wall
+/// nudges are not recorded, so replays and lockstep games with them will not play back the same.
+#[derive(Resource)]
+pub(crate) struct MovableWalls {
+    pub(crate) enabled: bool,
+    /// Up and down keys of the top wall, then the bottom one.
+    pub(crate) keys: [(KeyCode, KeyCode); 2],
+}
+
+impl Default for MovableWalls {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keys: [
+                (KeyCode::KeyT, KeyCode::KeyG),
+                (KeyCode::KeyI, KeyCode::KeyK),
+            ],
+        }
+    }
+}
+
+/// A wall that slides up to `range` either side of where it started.
+#[derive(Component)]
+pub(crate) struct MovableWall {
+    pub(crate) range: f32,
+    pub(crate) speed: f32,
+    pub(crate) rest: f32,
+    pub(crate) up: KeyCode,
+    pub(crate) down: KeyCode,
+}
+
+/// Whether the ball bounces off the top and bottom of the arena or wraps around to the other side.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub(crate) enum TopologyMode {
+    #[default]
+    Bounce,
+    Wrap,
+}
+
+impl FromStr for TopologyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bounce" => Ok(TopologyMode::Bounce),
+            "wrap" => Ok(TopologyMode::Wrap),
+            _ => Err(format!("unknown topology '{s}', expected bounce or wrap")),
+        }
+    }
+}
+
+fn nudge_walls(
+    walls: Query<(&mut Transform, &MovableWall)>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+) {
+    for (mut transform, wall) in walls {
+        let mut direction = 0.;
+        if keyboard.pressed(wall.up) {
+            direction += 1.;
+        }
+        if keyboard.pressed(wall.down) {
+            direction -= 1.;
+        }
+        let y = transform.translation.y + direction * wall.speed * time.delta_secs();
+        transform.translation.y = y.clamp(wall.rest - wall.range, wall.rest + wall.range);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damp_zones_parse_from_five_numbers() {
+        let zone: DampZoneSpec = "-40, 25.5,100,60,0.5".parse().unwrap();
+        assert_eq!(zone.center, Vec2::new(-40., 25.5));
+        assert_eq!(zone.size, Vec2::new(100., 60.));
+        assert_eq!(zone.factor, 0.5);
+        assert!("0,0,10,10,1".parse::<DampZoneSpec>().is_ok());
+
+        for bad in [
+            "0,0,10,10",
+            "0,0,10,10,0.5,1",
+            "0,0,ten,10,0.5",
+            "0,0,0,10,0.5",
+            "0,0,10,-5,0.5",
+            "0,0,10,10,0",
+            "0,0,10,10,1.5",
+            "",
+        ] {
+            assert!(
+                bad.parse::<DampZoneSpec>().is_err(),
+                "'{bad}' should be rejected"
+            );
+        }
+    }
+}
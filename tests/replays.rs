@@ -0,0 +1,51 @@
+//! Plays back the replays in `tests/fixtures` headlessly and checks each ends the way it was
+//! recorded, so changes to gameplay can't slip through unnoticed. When gameplay changes on
+//! purpose, run with `BLESS=1` to record the new outcomes instead, then review the fixture diff.
+
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[test]
+fn replays_match_their_recorded_outcomes() {
+    let check = if std::env::var_os("BLESS").is_some() {
+        "--bless"
+    } else {
+        "--verify"
+    };
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut replays: Vec<_> = fs::read_dir(&fixtures)
+        .expect("fixtures directory")
+        .map(|entry| entry.expect("fixture").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .collect();
+    replays.sort();
+    assert!(!replays.is_empty(), "no replays in {}", fixtures.display());
+
+    // A fresh directory away from the crate root, so no settings file can change the gameplay
+    // defaults and nothing the game writes is left behind.
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos());
+    let dir = std::env::temp_dir().join(format!("bong-replays-{}-{started}", std::process::id()));
+    fs::create_dir(&dir).expect("failed to create a directory to run in");
+    let failed: Vec<_> = replays
+        .iter()
+        .filter(|replay| {
+            let output = Command::new(env!("CARGO_BIN_EXE_bong"))
+                .args(["--replay".as_ref(), replay.as_os_str(), check.as_ref()])
+                .current_dir(&dir)
+                .output()
+                .expect("failed to run the game");
+            if !output.status.success() {
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            }
+            !output.status.success()
+        })
+        .collect();
+    fs::remove_dir_all(&dir).expect("failed to remove the directory run in");
+    assert!(failed.is_empty(), "replays failed: {failed:?}");
+}